@@ -1,24 +1,41 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use bytes::Bytes;
 use http_body_util::combinators::BoxBody;
 use http_body_util::{BodyExt, Full};
 use hyper::body::Incoming;
 use hyper::{Request, Response, StatusCode};
+use crate::cash_client::limiter::Limiter;
 
 mod todo;
 
 pub const NOTFOUND: &[u8] = b"Not Found";
 pub const BAD_REQUEST: &[u8] = b"Bad Request";
+pub const TOO_MANY_REQUESTS: &[u8] = b"Too Many Requests";
 pub const INTERNAL_SERVER_ERROR: &[u8] = b"Internal Server Error";
 
 pub type ResponseResult = Result<Response<BoxBody<Bytes, hyper::Error>>, Box<dyn std::error::Error + Send + Sync>>;
 
 
-pub async fn router(req: Request<Incoming>) -> ResponseResult {
-    match path(&req) {
+///Направляет запрос в API. Клиенты ограничены по адресу, предварительные запросы `OPTIONS` не учитываются.
+///Ошибка обработчика, например недоступный mini-casher, отдается клиенту как `500`, а не обрывает соединение
+pub async fn router(req: Request<Incoming>, addr: SocketAddr, limiter: Arc<Limiter>) -> ResponseResult {
+    if req.method() != hyper::Method::OPTIONS {
+        if let Some(retry_after) = limiter.check(&addr.ip().to_string()).await {
+            return too_many_requests(retry_after);
+        }
+    }
+
+    let result = match path(&req) {
         "todo" => todo::todo_api(req).await,
         _ => not_found()
-    }
+    };
+
+    result.or_else(|err| {
+        log::error!("{:?}", err);
+        internal_server_error()
+    })
 }
 
 async fn preflight(req: Request<Incoming>) -> ResponseResult {
@@ -42,7 +59,7 @@ pub fn params(req: &Request<Incoming>) -> HashMap<String, String> {
                 .into_owned()
                 .collect()
         })
-        .unwrap_or_else(HashMap::new)
+        .unwrap_or_default()
 }
 
 pub fn full<T: Into<Bytes>>(chunk: T) -> BoxBody<Bytes, hyper::Error> {
@@ -60,10 +77,6 @@ pub fn response<T: Into<Bytes>>(chunk: T, status_code: StatusCode) -> ResponseRe
         .body(full(chunk)).unwrap())
 }
 
-pub fn empty_response() -> ResponseResult {
-    response("", StatusCode::NO_CONTENT)
-}
-
 pub fn not_found() -> ResponseResult {
     response(NOTFOUND, StatusCode::NOT_FOUND)
 }
@@ -72,6 +85,12 @@ pub fn bad_request() -> ResponseResult {
     response(BAD_REQUEST, StatusCode::BAD_REQUEST)
 }
 
+pub fn too_many_requests(retry_after: i64) -> ResponseResult {
+    let mut response = response(TOO_MANY_REQUESTS, StatusCode::TOO_MANY_REQUESTS)?;
+    response.headers_mut().insert("Retry-After", retry_after.into());
+    Ok(response)
+}
+
 pub fn internal_server_error() -> ResponseResult {
     response(INTERNAL_SERVER_ERROR, StatusCode::INTERNAL_SERVER_ERROR)
 }
@@ -81,7 +100,6 @@ fn path(req: &Request<Incoming>) -> &str {
         .uri()
         .path()
         .split("/")
-        .filter(|s| !s.is_empty())
-        .next()
+        .find(|s| !s.is_empty())
         .unwrap_or("")
 }
\ No newline at end of file
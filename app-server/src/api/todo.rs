@@ -10,6 +10,11 @@ pub async fn todo_api(req: Request<Incoming>) -> ResponseResult {
         (&Method::OPTIONS, "/todo/create") => preflight(req).await,
         (&Method::GET, "/todo/get") => todo::get(req).await,
         (&Method::GET, "/todo/all") => todo::all(req).await,
+        (&Method::GET, "/todo/find") => todo::find(req).await,
+        (&Method::OPTIONS, "/todo/status") => preflight(req).await,
+        (&Method::POST, "/todo/status") => todo::status(req).await,
+        (&Method::OPTIONS, "/todo/comment") => preflight(req).await,
+        (&Method::POST, "/todo/comment") => todo::comment(req).await,
         (&Method::OPTIONS, "/todo/delete") => preflight(req).await,
         (&Method::DELETE, "/todo/delete") => todo::delete(req).await,
         _ => not_found()
@@ -1,68 +1,252 @@
+use std::time::Duration;
 use bytes::Bytes;
 use mini_casher::client::Client;
-use mini_casher::SOCKET_ADDR;
+use mini_casher::socket_addr;
+use mini_casher::core::command::acl::Auth;
+use mini_casher::core::command::client::ClientCmd;
+use mini_casher::core::command::filter::FilterCmd;
+use mini_casher::core::command::json::{JsonCmd, Path};
+use mini_casher::core::command::search::{Field, FieldKind, IndexDef, Query, SearchCmd, SearchOptions};
+use mini_casher::core::command::string::Condition;
+use mini_casher::core::command::throttle::Throttle;
 use mini_casher::core::frames::Frame;
 use crate::error::ServerError;
 
+///Префикс ключей задач в mini-casher
+pub const KEY_PREFIX: &str = "todo:";
+
+///Фильтр с кукушкой названий задач: дешевая проверка на дубликат без чтения задачи
+pub const TITLES_KEY: &str = "todo-titles";
+
+///Индекс задач в mini-casher для поиска по статусу и названию
+pub const INDEX: &str = "todo";
+
+///Префикс ключей состояния ограничителя запросов, после него - адрес клиента API
+pub const THROTTLE_PREFIX: &str = "throttle:";
+
+///Клиент API может сделать до `RATE_BURST` запросов подряд, дальше - `RATE_PER_MINUTE` в минуту
+pub const RATE_BURST: u64 = 30;
+pub const RATE_PER_MINUTE: u64 = 60;
+
+///Учетные данные пользователя ACL, под которым app-server работает с mini-casher
+pub const USER_ENV: &str = "CASH_USER";
+pub const PASSWORD_ENV: &str = "CASH_PASSWORD";
+
 pub struct CashClient {
     connection: Client,
 }
 
 impl CashClient {
+    ///Открывает соединение, проходит `AUTH`, если заданы учетные данные,
+    ///и подписывает соединение именем экземпляра app-server,
+    ///чтобы его можно было найти в `CLIENT LIST`
     pub async fn connect() -> Self {
-        Self { connection: Client::connect(SOCKET_ADDR).await }
+        match CashClient::try_connect().await {
+            Ok(client) => client,
+            Err(err) => panic!("Failed connection: {:?}", err)
+        }
+    }
+
+    ///Подключение без паники, когда без mini-casher можно обойтись
+    pub async fn try_connect() -> Result<Self, ServerError> {
+        let mut connection = Client::try_connect(&socket_addr()).await?;
+
+        if let Ok(password) = std::env::var(PASSWORD_ENV) {
+            let auth = Auth::new(std::env::var(USER_ENV).ok(), password);
+            match connection.auth(&auth).await {
+                Ok(Frame::Error(err)) => log::error!("{}", err),
+                Err(err) => log::error!("{}", err),
+                _ => {}
+            }
+        }
+
+        let name = format!("app-server-{}", std::process::id());
+
+        if let Err(err) = connection.client(&ClientCmd::SetName(name)).await {
+            log::error!("{}", err);
+        }
+
+        Ok(Self { connection })
     }
 
     pub async fn get(&mut self, key: &str) -> Result<Option<String>, ServerError> {
-        match self.connection.get(key).await? {
-            Frame::BulkString(value) => Ok(Some(String::from_utf8(value.to_vec())?)),
+        let cmd = JsonCmd::Get(Bytes::from(key_of(key)), vec![Path::root()]);
+
+        match self.connection.json(&cmd).await? {
+            Frame::BulkString(value) => {
+                //путь `$` возвращает массив из одного документа
+                let mut docs: Vec<serde_json::Value> = serde_json::from_slice(&value)?;
+                Ok(docs.pop().map(|doc| doc.to_string()))
+            }
             Frame::Null => Ok(None),
             _ => Err(ServerError::Cash("unexpected result".to_string()))
         }
     }
 
-    pub async fn set(&mut self, key: &str, value: Bytes) -> Result<String, ServerError> {
-        match self.connection.set(key, value).await? {
+    ///Записывает задачу целиком как JSON-документ
+    pub async fn set(&mut self, key: &str, value: serde_json::Value) -> Result<String, ServerError> {
+        let cmd = JsonCmd::Set(Bytes::from(key_of(key)), Path::root(), value, None);
+
+        match self.connection.json(&cmd).await? {
             Frame::Simple(str) => Ok(str),
             _ => Err(ServerError::Cash("unexpected result".to_string()))
         }
     }
 
+    ///Меняет одно поле задачи на сервере. `false` - задачи нет
+    pub async fn set_field(&mut self, key: &str, path: &str, value: serde_json::Value) -> Result<bool, ServerError> {
+        let cmd = JsonCmd::Set(Bytes::from(key_of(key)), Path::parse(path)?, value, Some(Condition::Xx));
+
+        match self.connection.json(&cmd).await? {
+            Frame::Simple(_) => Ok(true),
+            Frame::Null => Ok(false),
+            Frame::Error(err) => Err(ServerError::Cash(err)),
+            _ => Err(ServerError::Cash("unexpected result".to_string()))
+        }
+    }
+
+    ///Добавляет значение в массив задачи, создавая массив, если его ещё нет.
+    ///`false` - задачи нет
+    pub async fn append(&mut self, key: &str, path: &str, value: serde_json::Value) -> Result<bool, ServerError> {
+        let cmd = JsonCmd::ArrAppend(Bytes::from(key_of(key)), Path::parse(path)?, vec![value.clone()]);
+
+        match self.connection.json(&cmd).await? {
+            Frame::Array(lengths) if lengths.iter().any(|len| matches!(len, Frame::Integer(_))) => Ok(true),
+            Frame::Array(_) => {
+                let cmd = JsonCmd::Set(Bytes::from(key_of(key)), Path::parse(path)?, serde_json::json!([value]), None);
+                match self.connection.json(&cmd).await? {
+                    Frame::Simple(_) => Ok(true),
+                    Frame::Null => Ok(false),
+                    Frame::Error(err) => Err(ServerError::Cash(err)),
+                    _ => Err(ServerError::Cash("unexpected result".to_string()))
+                }
+            }
+            //для пути `$` такую ошибку дает только отсутствующий ключ
+            Frame::Error(err) if err.ends_with("does not exist") => Ok(false),
+            Frame::Error(err) => Err(ServerError::Cash(err)),
+            _ => Err(ServerError::Cash("unexpected result".to_string()))
+        }
+    }
+
+    ///Есть ли уже задача с таким названием. Фильтр отвечает «точно нет» без обращения к задаче,
+    ///а «возможно» проверяется чтением, потому что фильтр допускает ложные срабатывания
+    pub async fn title_taken(&mut self, name: &str) -> Result<bool, ServerError> {
+        let cmd = FilterCmd::CfExists(Bytes::from(TITLES_KEY), Bytes::from(name.to_string()));
+
+        match self.connection.filter(&cmd).await? {
+            Frame::Integer(0) => Ok(false),
+            Frame::Integer(_) => Ok(self.get(name).await?.is_some()),
+            Frame::Error(err) => Err(ServerError::Cash(err)),
+            _ => Err(ServerError::Cash("unexpected result".to_string()))
+        }
+    }
+
+    pub async fn remember_title(&mut self, name: &str) -> Result<(), ServerError> {
+        let cmd = FilterCmd::CfAdd(Bytes::from(TITLES_KEY), Bytes::from(name.to_string()));
+
+        match self.connection.filter(&cmd).await? {
+            Frame::Error(err) => Err(ServerError::Cash(err)),
+            _ => Ok(())
+        }
+    }
+
+    pub async fn forget_title(&mut self, name: &str) -> Result<(), ServerError> {
+        let cmd = FilterCmd::CfDel(Bytes::from(TITLES_KEY), Bytes::from(name.to_string()));
+        self.connection.filter(&cmd).await?;
+        Ok(())
+    }
+
+    ///Удаляет задачу и возвращает её, `None` - задачи не было.
+    ///`JSON.GETDEL` забирает документ и удаляет его одной командой, так что изменение между ними не потеряется
     pub async fn delete(&mut self, key: &str) -> Result<Option<String>, ServerError> {
-        match self.connection.delete(key).await? {
+        match self.connection.json(&JsonCmd::GetDel(Bytes::from(key_of(key)))).await? {
             Frame::BulkString(value) => Ok(Some(String::from_utf8(value.to_vec())?)),
+            Frame::Null => Ok(None),
+            Frame::Error(err) => Err(ServerError::Cash(err)),
             _ => Err(ServerError::Cash("unexpected result".to_string()))
         }
     }
 
-    pub async fn all(&mut self) -> Result<Vec<Bytes>, ServerError> {
-        match self.connection.all().await? {
-            Frame::Array(arr) => {
-                let mut json = vec![];
-
-                for frame in arr {
-                    match frame {
-                        Frame::BulkString(value) => json.push(value),
-                        _ => return Err(ServerError::Cash("unexpected result".to_string()))
+    ///Создает индекс задач, если его ещё нет (например, после перезапуска mini-casher)
+    async fn ensure_index(&mut self) -> Result<(), ServerError> {
+        let field = |path: &str, name: &str, kind| -> Result<Field, ServerError> {
+            Ok(Field { path: Path::parse(path)?, name: name.to_string(), kind })
+        };
+        let def = IndexDef {
+            name: INDEX.to_string(),
+            prefixes: vec![Bytes::from(KEY_PREFIX)],
+            fields: vec![field("$.status", "status", FieldKind::Tag(','))?, field("$.name", "name", FieldKind::Text)?],
+        };
 
-                    }
-                }
-                Ok(json)
-            },
+        match self.connection.search(&SearchCmd::Create(def)).await? {
+            Frame::Simple(_) => Ok(()),
+            Frame::Error(err) if err.contains("already exists") => Ok(()),
+            Frame::Error(err) => Err(ServerError::Cash(err)),
             _ => Err(ServerError::Cash("unexpected result".to_string()))
         }
     }
 
+    ///Задачи по запросу `FT.SEARCH` и их общее количество
+    pub async fn find(&mut self, query: &str, offset: usize, limit: usize) -> Result<(i64, Vec<Bytes>), ServerError> {
+        self.ensure_index().await?;
 
-}
+        let options = SearchOptions { no_content: false, offset, limit };
+        let cmd = SearchCmd::Search(INDEX.to_string(), Query::parse(query)?, options);
 
-async fn value_of_bulk(frame: &Frame) -> Result<String, ServerError> {
-    match frame {
-        Frame::BulkString(value) => Ok(String::from_utf8(value.to_vec())?),
-        _ => Err(ServerError::Cash("unexpected result".to_string()))
+        match self.connection.search(&cmd).await? {
+            Frame::Array(frames) => {
+                let mut frames = frames.into_iter();
+                let Some(Frame::Integer(total)) = frames.next() else {
+                    return Err(ServerError::Cash("unexpected result".to_string()));
+                };
+
+                //после ключа идет пара `$` и документ
+                let docs = frames
+                    .filter_map(|frame| match frame {
+                        Frame::Array(mut doc) => match doc.pop() {
+                            Some(Frame::BulkString(json)) => Some(json),
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .collect();
+                Ok((total, docs))
+            }
+            Frame::Error(err) => Err(ServerError::Cash(err)),
+            _ => Err(ServerError::Cash("unexpected result".to_string()))
+        }
     }
-}
 
+    ///Учитывает запрос клиента API в общем для всех экземпляров app-server ограничителе.
+    ///`Some` - лимит исчерпан, через сколько секунд можно повторить
+    pub async fn throttle(&mut self, client: &str) -> Result<Option<i64>, ServerError> {
+        let cmd = Throttle {
+            key: Bytes::from(format!("{}{}", THROTTLE_PREFIX, client)),
+            max_burst: RATE_BURST - 1,
+            count: RATE_PER_MINUTE,
+            period: Duration::from_secs(60),
+            quantity: 1,
+        };
 
+        match self.connection.throttle(&cmd).await? {
+            Frame::Array(frames) => match frames[..] {
+                [Frame::Integer(0), ..] => Ok(None),
+                [Frame::Integer(_), _, _, Frame::Integer(retry_after), _] => Ok(Some(retry_after.max(1))),
+                _ => Err(ServerError::Cash("unexpected result".to_string()))
+            },
+            Frame::Error(err) => Err(ServerError::Cash(err)),
+            _ => Err(ServerError::Cash("unexpected result".to_string()))
+        }
+    }
 
+    ///Все задачи. Берутся через индекс, а не `ALL`: рядом с задачами лежат служебные ключи,
+    ///например состояние ограничителя запросов и фильтр названий
+    pub async fn all(&mut self) -> Result<Vec<Bytes>, ServerError> {
+        let (_, docs) = self.find("*", 0, i64::MAX as usize).await?;
+        Ok(docs)
+    }
+}
 
+fn key_of(key: &str) -> String {
+    format!("{}{}", KEY_PREFIX, key)
+}
@@ -0,0 +1,48 @@
+use std::sync::Mutex;
+use crate::cash_client::client::CashClient;
+
+///Сколько свободных соединений ограничитель держит открытыми между запросами
+const POOL_SIZE: usize = 8;
+
+///Ограничитель запросов к API с небольшим пулом соединений с mini-casher.
+///Запрос берет свободное соединение или открывает новое, так что одновременные запросы не ждут друг друга;
+///после ответа соединение возвращается в пул, а после ошибки закрывается.
+///Если mini-casher недоступен, запрос пропускается: ограничитель защищает от перегрузки,
+///а не от доступа, и без хранилища должны работать хотя бы те запросы, которым оно не нужно
+#[derive(Default)]
+pub struct Limiter {
+    idle: Mutex<Vec<CashClient>>,
+}
+
+impl Limiter {
+    ///`Some` - лимит клиента исчерпан, через сколько секунд можно повторить
+    pub async fn check(&self, client: &str) -> Option<i64> {
+        let idle = self.idle.lock().ok().and_then(|mut idle| idle.pop());
+
+        let mut connection = match idle {
+            Some(connection) => connection,
+            None => match CashClient::try_connect().await {
+                Ok(opened) => opened,
+                Err(err) => {
+                    log::warn!("rate limiter is unavailable, request allowed: {:?}", err);
+                    return None;
+                }
+            },
+        };
+
+        match connection.throttle(client).await {
+            Ok(retry_after) => {
+                if let Ok(mut idle) = self.idle.lock() {
+                    if idle.len() < POOL_SIZE {
+                        idle.push(connection);
+                    }
+                }
+                retry_after
+            }
+            Err(err) => {
+                log::warn!("rate limiter failed, request allowed: {:?}", err);
+                None
+            }
+        }
+    }
+}
@@ -5,7 +5,9 @@ use crate::json::Serializable;
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Item {
     name: String,
-    status: String
+    status: String,
+    #[serde(default)]
+    comments: Vec<String>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -13,6 +15,14 @@ pub struct Items {
     items: Vec<Item>
 }
 
+///Страница результатов поиска и общее количество найденных задач
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Found {
+    pub total: i64,
+    #[serde(flatten)]
+    pub items: Items
+}
+
 impl Items {
     pub fn new(items: Vec<Item>) -> Self {
         Self { items }
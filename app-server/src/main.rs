@@ -1,8 +1,10 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use tokio::net::TcpListener;
+use crate::cash_client::limiter::Limiter;
 
 
 extern crate pretty_env_logger;
@@ -24,14 +26,17 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let listener = TcpListener::bind(addr).await?;
     log::info!("Listening on http://{}", addr);
 
+    let limiter = Arc::new(Limiter::default());
+
     loop {
-        let (stream, _) = listener.accept().await?;
+        let (stream, client_addr) = listener.accept().await?;
+        let limiter = limiter.clone();
 
         tokio::task::spawn(async move {
 
             let service = service_fn(move |req| {
                 log::info!("{:?}", &req);
-                api::router(req)
+                api::router(req, client_addr, limiter.clone())
             });
 
             if let Err(err) = http1::Builder::new()
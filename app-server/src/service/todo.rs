@@ -1,8 +1,7 @@
-use bytes::Bytes;
 use hyper::{Request, StatusCode};
 use hyper::body::Incoming;
 use crate::api::{bad_request, not_found, params, response, ResponseResult};
-use crate::json::scheme::{Item, Items};
+use crate::json::scheme::{Found, Item, Items};
 use crate::json::Serializable;
 use crate::cash_client::client::CashClient;
 
@@ -10,13 +9,21 @@ use crate::cash_client::client::CashClient;
 pub async fn create(req: Request<Incoming>) -> ResponseResult {
     let bytes = Item::bytes(req).await.unwrap();
     let json_scheme = Item::serialize(&bytes).await.unwrap();
-    let json = serde_json::to_string(&json_scheme)?;
-    let byte_json = Bytes::from(json.clone());
+    let json = serde_json::to_value(&json_scheme)?;
+
+    let name = json_scheme.key().await;
 
     let mut client = CashClient::connect().await;
 
-    match client.set(json_scheme.key().await.as_str(), byte_json).await {
-        Ok(value) => response(value, StatusCode::OK),
+    if client.title_taken(&name).await? {
+        return response("Task already exists", StatusCode::CONFLICT);
+    }
+
+    match client.set(&name, json).await {
+        Ok(value) => {
+            client.remember_title(&name).await?;
+            response(value, StatusCode::OK)
+        }
         Err(err) => response(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
     }
 }
@@ -43,15 +50,13 @@ pub async fn all(_req: Request<Incoming>) -> ResponseResult {
     let items: Vec<Item> = items
         .iter()
         .filter_map(|i| {
-            let result = serde_json::from_slice::<Item>(i);
-
-            if result.is_ok()  {
-                Some(result.unwrap())
-            } else {
-                log::error!("{}", result.err().unwrap());
-                None
+            match serde_json::from_slice::<Item>(i) {
+                Ok(item) => Some(item),
+                Err(err) => {
+                    log::error!("{}", err);
+                    None
+                }
             }
-
         })
         .collect();
 
@@ -62,13 +67,79 @@ pub async fn all(_req: Request<Incoming>) -> ResponseResult {
     res
 }
 
+///Задачи по статусу и словам названия, с постраничным выводом.
+///Фильтр выполняется индексом mini-casher, а не разбором всех задач
+pub async fn find(req: Request<Incoming>) -> ResponseResult {
+    let params = params(&req);
+    let clean = |s: &str| s.replace(['{', '}', '(', ')', '|', '@'], " ");
+
+    let mut clauses = vec![];
+    if let Some(status) = params.get("status") {
+        clauses.push(format!("@status:{{{}}}", clean(status)));
+    }
+    if let Some(name) = params.get("name") {
+        clauses.push(format!("@name:({})", clean(name)));
+    }
+    let query = if clauses.is_empty() { "*".to_string() } else { clauses.join(" ") };
+
+    let offset = params.get("offset").and_then(|v| v.parse().ok()).unwrap_or(0);
+    let limit = params.get("limit").and_then(|v| v.parse().ok()).unwrap_or(10);
+
+    let mut client = CashClient::connect().await;
+    let (total, docs) = match client.find(&query, offset, limit).await {
+        Ok(found) => found,
+        Err(err) => return response(err.to_string(), StatusCode::BAD_REQUEST)
+    };
+
+    let items: Vec<Item> = docs
+        .iter()
+        .filter_map(|doc| serde_json::from_slice(doc).ok())
+        .collect();
+
+    let json = serde_json::to_string(&Found { total, items: Items::new(items) })?;
+    response(json, StatusCode::OK)
+}
+
+///Меняет статус задачи на сервере без перезаписи всего документа
+pub async fn status(req: Request<Incoming>) -> ResponseResult {
+    let params = params(&req);
+    let (Some(key), Some(status)) = (params.get("key"), params.get("status")) else {
+        return bad_request();
+    };
+
+    let mut client = CashClient::connect().await;
+
+    match client.set_field(key, "$.status", serde_json::json!(status)).await? {
+        true => response("Ok", StatusCode::OK),
+        false => not_found()
+    }
+}
+
+///Добавляет комментарий к задаче
+pub async fn comment(req: Request<Incoming>) -> ResponseResult {
+    let params = params(&req);
+    let (Some(key), Some(text)) = (params.get("key"), params.get("text")) else {
+        return bad_request();
+    };
+
+    let mut client = CashClient::connect().await;
+
+    match client.append(key, "$.comments", serde_json::json!(text)).await? {
+        true => response("Ok", StatusCode::OK),
+        false => not_found()
+    }
+}
+
 pub async fn delete(req: Request<Incoming>) -> ResponseResult {
     let params = params(&req);
     if let Some(key) = params.get("key") {
         let mut client = CashClient::connect().await;
 
         match client.delete(key).await? {
-            Some(json) => response(json, StatusCode::OK),
+            Some(json) => {
+                client.forget_title(key).await?;
+                response(json, StatusCode::OK)
+            }
             None => not_found()
         }
 
@@ -1,9 +1,20 @@
+use log::LevelFilter;
 use mini_casher::server;
+use mini_casher::server::config::Config;
 
 #[tokio::main]
 async fn main() {
-    std::env::set_var("RUST_LOG", "info");
-    pretty_env_logger::init();
-    server::run().await;
-}
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    //Фильтр логгера пропускает все, уровень задает `loglevel` и меняет `CONFIG SET`
+    pretty_env_logger::formatted_builder().filter_level(LevelFilter::Trace).init();
+    log::set_max_level(config.log_level().unwrap());
 
+    server::run(config).await;
+}
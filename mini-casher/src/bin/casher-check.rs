@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+use std::process::exit;
+use mini_casher::server::aof::truncate;
+use mini_casher::server::check::{check, dump, Check};
+
+const USAGE: &str = "usage: casher-check [--fix] [--dump] <dump.cdb|appendonly.aof>";
+
+///Проверяет снимок или AOF без запуска сервера.
+///Код выхода: 0 - файл цел, 1 - поврежден, 2 - неверные аргументы или файл не читается
+fn main() {
+    let mut fix = false;
+    let mut show = false;
+    let mut path = None;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--fix" => fix = true,
+            "--dump" => show = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        }
+    }
+
+    let Some(path) = path else {
+        eprintln!("{}", USAGE);
+        exit(2);
+    };
+
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("can't read '{}': {}", path.display(), err);
+            exit(2);
+        }
+    };
+
+    let check = match check(&path, &bytes) {
+        Ok(check) => check,
+        Err(err) => {
+            println!("{}: {}", path.display(), err);
+            exit(1);
+        }
+    };
+
+    match &check {
+        Check::Snapshot(snapshot) => {
+            println!(
+                "{}: snapshot is valid, {} keys, {} indexes",
+                path.display(),
+                snapshot.entries.len(),
+                snapshot.indexes.len()
+            );
+        }
+        Check::Aof { preamble, scan, unknown, len } => {
+            if let Some(snapshot) = preamble {
+                println!("{}: snapshot preamble is valid, {} keys", path.display(), snapshot.entries.len());
+            }
+            println!("{}: {} valid commands, {} of {} bytes", path.display(), scan.commands, scan.valid, len);
+            for at in unknown {
+                println!("{}: unknown command at byte {}, it is skipped on load", path.display(), at);
+            }
+
+            match &scan.corruption {
+                Some(err) => println!("{}: AOF is corrupted at byte {}: {}", path.display(), scan.valid, err),
+                None if scan.valid < *len => {
+                    println!("{}: incomplete command at byte {}", path.display(), scan.valid)
+                }
+                None => println!("{}: AOF is valid", path.display()),
+            }
+
+            if fix && !check.is_ok() {
+                if let Err(err) = truncate(&path, scan.valid) {
+                    eprintln!("{}", err);
+                    exit(2);
+                }
+                println!("{}: truncated to {} bytes, {} bytes removed", path.display(), scan.valid, len - scan.valid);
+                show_dump(show, &check);
+                return;
+            }
+        }
+    }
+
+    show_dump(show, &check);
+    if !check.is_ok() {
+        exit(1);
+    }
+}
+
+fn show_dump(show: bool, check: &Check) {
+    if let (true, Some(snapshot)) = (show, check.snapshot()) {
+        for line in dump(snapshot) {
+            println!("{}", line);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::process::exit;
+use bytes::Bytes;
+use mini_casher::client::Client;
+use mini_casher::core::command::acl::Auth;
+use mini_casher::core::command::keyspace::{KeyspaceCmd, Restore, Scan};
+use mini_casher::core::error::CashError;
+use mini_casher::core::frames::Frame;
+use mini_casher::socket_addr;
+use mini_casher::storage::export::{Format, Record, CSV_HEADER};
+use mini_casher::storage::snapshot::{dump_value, restore_value};
+
+const USAGE: &str = "usage:
+  casher-export export [--format jsonl|csv] [--match pattern] [--user user] [--password password] [file]
+  casher-export import [--format jsonl|csv] [--on-conflict fail|skip|replace] [--user user] [--password password] [file]";
+
+///Ключей за один `SCAN`
+const BATCH: u64 = 100;
+
+///Что делать при импорте ключа, который уже есть на сервере
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Conflict {
+    Fail,
+    Skip,
+    Replace,
+}
+
+struct Options {
+    import: bool,
+    format: Option<Format>,
+    pattern: Option<String>,
+    conflict: Conflict,
+    user: Option<String>,
+    password: Option<String>,
+    path: Option<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    exit(1);
+}
+
+fn options() -> Options {
+    let mut args = std::env::args().skip(1);
+    let import = match args.next().as_deref() {
+        Some("export") => false,
+        Some("import") => true,
+        _ => usage(),
+    };
+
+    let mut options =
+        Options { import, format: None, pattern: None, conflict: Conflict::Fail, user: None, password: None, path: None };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--format" => options.format = Some(value().parse().unwrap_or_else(|e| fail(e))),
+            "--match" if !import => options.pattern = Some(value()),
+            "--on-conflict" if import => {
+                options.conflict = match value().as_str() {
+                    "fail" => Conflict::Fail,
+                    "skip" => Conflict::Skip,
+                    "replace" => Conflict::Replace,
+                    _ => usage(),
+                }
+            }
+            "--user" => options.user = Some(value()),
+            "--password" => options.password = Some(value()),
+            _ if options.path.is_none() && !arg.starts_with("--") => options.path = Some(arg),
+            _ => usage(),
+        }
+    }
+    options
+}
+
+///Выгружает все ключи через `SCAN`, `DUMP` и `PTTL` или загружает файл командами `RESTORE`.
+///Сервер берется из `CASH_ADDR`, файл по умолчанию - stdout/stdin, формат - по расширению `.csv`
+#[tokio::main]
+async fn main() {
+    let options = options();
+    let format = options.format.unwrap_or(match &options.path {
+        Some(path) if path.ends_with(".csv") => Format::Csv,
+        _ => Format::JsonLines,
+    });
+
+    let mut client = Client::try_connect(&socket_addr()).await.unwrap_or_else(|e| fail(e));
+    if let Some(password) = &options.password {
+        let auth = Auth::new(options.user.clone(), password.clone());
+        match client.auth(&auth).await {
+            Ok(Frame::Error(err)) => fail(err),
+            Err(err) => fail(err),
+            Ok(_) => {}
+        }
+    }
+
+    if options.import {
+        let input: Box<dyn Read> = match &options.path {
+            Some(path) => Box::new(File::open(path).unwrap_or_else(|e| fail(format!("can't open '{}': {}", path, e)))),
+            None => Box::new(io::stdin()),
+        };
+        import(&mut client, input, format, options.conflict).await;
+    } else {
+        let output: Box<dyn Write> = match &options.path {
+            Some(path) => Box::new(File::create(path).unwrap_or_else(|e| fail(format!("can't create '{}': {}", path, e)))),
+            None => Box::new(io::stdout()),
+        };
+        export(&mut client, Sink::new(output, format), options.pattern).await;
+    }
+}
+
+///Файл выгрузки
+enum Sink {
+    JsonLines(BufWriter<Box<dyn Write>>),
+    Csv(Box<csv::Writer<Box<dyn Write>>>),
+}
+
+impl Sink {
+    fn new(output: Box<dyn Write>, format: Format) -> Sink {
+        match format {
+            Format::JsonLines => Sink::JsonLines(BufWriter::new(output)),
+            Format::Csv => {
+                let mut csv = csv::Writer::from_writer(output);
+                csv.write_record(CSV_HEADER).unwrap_or_else(|e| fail(e));
+                Sink::Csv(Box::new(csv))
+            }
+        }
+    }
+
+    fn write(&mut self, record: &Record) {
+        let written = match self {
+            Sink::JsonLines(output) => writeln!(output, "{}", record.to_json_line()),
+            Sink::Csv(csv) => csv.write_record(record.to_csv_row()).map_err(io::Error::from),
+        };
+        written.unwrap_or_else(|e| fail(e));
+    }
+
+    fn flush(&mut self) {
+        let flushed = match self {
+            Sink::JsonLines(output) => output.flush(),
+            Sink::Csv(csv) => csv.flush(),
+        };
+        flushed.unwrap_or_else(|e| fail(e));
+    }
+}
+
+async fn export(client: &mut Client, mut sink: Sink, pattern: Option<String>) {
+    let mut cursor = 0;
+    let mut exported = 0;
+    loop {
+        let scan = Scan { cursor, pattern: pattern.clone().map(Bytes::from), count: BATCH, kind: None };
+        let reply = client.keyspace(&KeyspaceCmd::Scan(scan)).await.unwrap_or_else(|e| fail(e));
+        let Frame::Array(reply) = reply else { fail(format!("unexpected scan reply {:?}", reply)) };
+        let [Frame::BulkString(next), Frame::Array(keys)] = &reply[..] else { fail(format!("unexpected scan reply {:?}", reply)) };
+
+        for key in keys {
+            let Frame::BulkString(key) = key else { continue };
+            if let Some(record) = record(client, key.clone()).await {
+                sink.write(&record);
+                exported += 1;
+            }
+        }
+
+        cursor = std::str::from_utf8(next).ok().and_then(|c| c.parse().ok()).unwrap_or(0);
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    sink.flush();
+    eprintln!("exported {} keys", exported);
+}
+
+///Ключ, удаленный или истекший во время выгрузки, пропускается
+async fn record(client: &mut Client, key: Bytes) -> Option<Record> {
+    let value = match client.keyspace(&KeyspaceCmd::Dump(key.clone())).await {
+        Ok(Frame::BulkString(payload)) => restore_value(&payload).unwrap_or_else(|e| fail(e)),
+        Ok(Frame::Null) => return None,
+        Ok(frame) => fail(format!("unexpected dump reply {:?}", frame)),
+        Err(err) => fail(err),
+    };
+
+    match client.keyspace(&KeyspaceCmd::Pttl(key.clone())).await {
+        Ok(Frame::Integer(-2)) => None,
+        Ok(Frame::Integer(ttl)) => Some(Record { key, value, ttl }),
+        Ok(frame) => fail(format!("unexpected pttl reply {:?}", frame)),
+        Err(err) => fail(err),
+    }
+}
+
+async fn import(client: &mut Client, input: impl Read, format: Format, conflict: Conflict) {
+    let mut stats = (0, 0);
+    match format {
+        Format::JsonLines => {
+            for (i, line) in BufReader::new(input).lines().enumerate() {
+                let line = line.unwrap_or_else(|e| fail(e));
+                if !line.trim().is_empty() {
+                    restore(client, i + 1, Record::from_json_line(&line), conflict, &mut stats).await;
+                }
+            }
+        }
+        Format::Csv => {
+            for row in csv::Reader::from_reader(input).records() {
+                let row = row.unwrap_or_else(|e| fail(e));
+                let line = row.position().map_or(0, |p| p.line() as usize);
+                let fields: Vec<&str> = row.iter().collect();
+                restore(client, line, Record::from_csv_row(&fields), conflict, &mut stats).await;
+            }
+        }
+    }
+
+    eprintln!("imported {} keys, skipped {} existing", stats.0, stats.1);
+}
+
+///Загружает ключ командой `RESTORE`. `stats` - число загруженных и пропущенных ключей
+async fn restore(
+    client: &mut Client,
+    line: usize,
+    record: Result<Record, CashError>,
+    conflict: Conflict,
+    stats: &mut (usize, usize),
+) {
+    let record = record.unwrap_or_else(|e| fail(format!("line {}: {}", line, e)));
+    let key = String::from_utf8_lossy(&record.key).to_string();
+    let restore = Restore {
+        key: record.key,
+        ttl: record.ttl.max(0) as u64,
+        payload: Bytes::from(dump_value(&record.value)),
+        replace: conflict == Conflict::Replace,
+        absttl: false,
+    };
+
+    match client.keyspace(&KeyspaceCmd::Restore(restore)).await {
+        Ok(Frame::Error(err)) if err.contains("already exists") && conflict == Conflict::Skip => stats.1 += 1,
+        Ok(Frame::Error(err)) => fail(format!("line {}: key '{}': {}", line, key, err)),
+        Ok(_) => stats.0 += 1,
+        Err(err) => fail(err),
+    }
+}
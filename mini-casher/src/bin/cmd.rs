@@ -1,36 +1,36 @@
 use std::io;
+use bytes::Bytes;
 use mini_casher::client::Client;
-use mini_casher::core::command::{Command};
-use mini_casher::core::error::CashError;
+use mini_casher::core::command::{table, Command};
 use mini_casher::core::frames::Frame;
-
-
-const CMD_MESSAGE: &str =
-    "Enter the command:\r\n\
-    - check the connection - `ping`\r\n\
-    - get value by key - `get key`\r\n\
-    - set a new value - `set key value`\r\n\
-    - map length - `len`\r\n\
-    - load all entity - `all`\r\n\
-    - delete by key - `delete key`
-    ";
+use mini_casher::socket_addr;
 
 
 #[tokio::main]
 async fn main() {
-    println!("{}", CMD_MESSAGE);
+    let mut client = Client::connect(&socket_addr()).await;
+
+    if std::env::args().nth(1).as_deref() == Some("monitor") {
+        monitor(&mut client).await;
+        return;
+    }
+
+    println!("{}", table::help());
     let mut input = String::new();
 
     loop {
-        io::stdin()
+        let read = io::stdin()
             .read_line(&mut input)
             .expect("Failed to read line");
 
-        match Command::from_cmd(input.clone()) {
-            Ok(command) => {
-                let mut client = Client::connect("127.0.0.1:6379").await;
+        if read == 0 {
+            break;
+        }
 
-                match execute(command, &mut client).await {
+        match Command::from_cmd(input.clone()) {
+            Ok(Command::Monitor) => monitor(&mut client).await,
+            Ok(_) => {
+                match client.execute(&words_frame(&input)).await {
                     Ok(frame) => println!("app-server response: {:?}", frame),
                     Err(e) => println!("failed: {:?}", e)
                 }
@@ -43,14 +43,28 @@ async fn main() {
     }
 }
 
-async fn execute(cmd: Command, client: &mut Client) -> Result<Frame, CashError> {
-    match cmd {
-        Command::Get(cmd) => client.get(cmd.key()).await,
-        Command::Set(cmd) => client.set(cmd.key(), cmd.value().clone()).await,
-        Command::Delete(cmd) => client.delete(cmd.key()).await,
-        Command::All => client.all().await,
-        Command::Len => client.len().await,
-        Command::Ping => client.ping().await
+///Печатает каждую команду, которую выполняет сервер, пока соединение открыто
+async fn monitor(client: &mut Client) {
+    match client.monitor().await {
+        Ok(Frame::Simple(_)) => {}
+        Ok(frame) => return println!("app-server response: {:?}", frame),
+        Err(e) => return println!("failed: {:?}", e),
+    }
+
+    loop {
+        match client.read_message().await {
+            Ok(Some(Frame::Simple(line))) => println!("{}", line),
+            Ok(Some(frame)) => println!("{:?}", frame),
+            Ok(None) => break,
+            Err(e) => return println!("failed: {:?}", e),
+        }
     }
 }
 
+///Команда уже проверена по таблице команд, поэтому отправляется как есть
+fn words_frame(input: &str) -> Frame {
+    Frame::Array(input
+        .split_whitespace()
+        .map(|w| Frame::BulkString(Bytes::from(w.to_string())))
+        .collect())
+}
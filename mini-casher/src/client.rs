@@ -1,8 +1,24 @@
+use std::time::Duration;
 use bytes::Bytes;
 use tokio::net::TcpStream;
-use crate::core::command::Command;
+use crate::core::command::{Command, Set};
+use crate::core::command::acl::{AclCmd, Auth};
+use crate::core::command::bitmap::BitmapCmd;
+use crate::core::command::client::ClientCmd;
+use crate::core::command::introspection::CommandCmd;
+use crate::core::command::slowlog::SlowlogCmd;
+use crate::core::command::config::ConfigCmd;
+use crate::core::command::filter::FilterCmd;
+use crate::core::command::hyperloglog::HllCmd;
+use crate::core::command::json::JsonCmd;
+use crate::core::command::keyspace::KeyspaceCmd;
+use crate::core::command::persistence::PersistenceCmd;
+use crate::core::command::lock::LockCmd;
+use crate::core::command::search::SearchCmd;
+use crate::core::command::string::StringCmd;
+use crate::core::command::throttle::Throttle;
 use crate::core::connection::Connection;
-use crate::core::error::CashError;
+use crate::core::error::{CashError, Error};
 
 use crate::core::frames::Frame;
 
@@ -13,23 +29,114 @@ pub struct Client {
 
 impl Client {
     pub async fn connect(addr: &str) -> Self {
-        match TcpStream::connect(addr).await {
-            Ok(socket) => Self { connection: Connection::new(socket) },
+        match Client::try_connect(addr).await {
+            Ok(client) => client,
             Err(e) => panic!("Failed connection: {:?}", e)
         }
     }
 
-    pub async fn get(&mut self, key: &str) -> Result<Frame, CashError> {
+    ///Подключение без паники, для соединений самого сервера, например в `MIGRATE`
+    pub async fn try_connect(addr: &str) -> Result<Self, CashError> {
+        let socket = TcpStream::connect(addr).await?;
+        Ok(Self { connection: Connection::new(socket) })
+    }
+
+    pub async fn get(&mut self, key: impl AsRef<[u8]>) -> Result<Frame, CashError> {
         let frame = Command::get_frame(key);
         self.execute(&frame).await
     }
 
-    pub async fn set(&mut self, key: &str, value: Bytes) -> Result<Frame, CashError> {
+    pub async fn set(&mut self, key: impl AsRef<[u8]>, value: Bytes) -> Result<Frame, CashError> {
         let frame = Command::set_frame(key, value);
         self.execute(&frame).await
     }
 
-    pub async fn delete(&mut self, key: &str) -> Result<Frame, CashError> {
+    ///`SET` с опциями `NX`/`XX`, `GET` и сроком жизни
+    pub async fn set_with(&mut self, set: &Set) -> Result<Frame, CashError> {
+        let frame = Command::set_with_frame(set);
+        self.execute(&frame).await
+    }
+
+    ///Удаляет ключ и возвращает его значение, `Frame::Null` - ключа не было
+    pub async fn getdel(&mut self, key: impl AsRef<[u8]>) -> Result<Frame, CashError> {
+        let frame = StringCmd::GetDel(Bytes::copy_from_slice(key.as_ref())).frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn string(&mut self, cmd: &StringCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn bitmap(&mut self, cmd: &BitmapCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn hll(&mut self, cmd: &HllCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn json(&mut self, cmd: &JsonCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn search(&mut self, cmd: &SearchCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn filter(&mut self, cmd: &FilterCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn lock(&mut self, cmd: &LockCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    ///Захватывает блокировку и возвращает токен ограждения.
+    ///`None` - блокировка занята и не освободилась за `wait`
+    pub async fn acquire_lock(
+        &mut self,
+        name: impl Into<Bytes>,
+        ttl: Duration,
+        wait: Option<Duration>,
+    ) -> Result<Option<u64>, CashError> {
+        let cmd = LockCmd::Acquire { name: name.into(), ttl, wait };
+        match self.lock(&cmd).await? {
+            Frame::Integer(token) => Ok(Some(token as u64)),
+            Frame::Null => Ok(None),
+            frame => Err(unexpected(frame)),
+        }
+    }
+
+    ///`false` - блокировка уже истекла или принадлежит другому токену
+    pub async fn release_lock(&mut self, name: impl Into<Bytes>, token: u64) -> Result<bool, CashError> {
+        let cmd = LockCmd::Release { name: name.into(), token };
+        match self.lock(&cmd).await? {
+            Frame::Integer(released) => Ok(released == 1),
+            frame => Err(unexpected(frame)),
+        }
+    }
+
+    pub async fn extend_lock(&mut self, name: impl Into<Bytes>, token: u64, ttl: Duration) -> Result<bool, CashError> {
+        let cmd = LockCmd::Extend { name: name.into(), token, ttl };
+        match self.lock(&cmd).await? {
+            Frame::Integer(extended) => Ok(extended == 1),
+            frame => Err(unexpected(frame)),
+        }
+    }
+
+    pub async fn throttle(&mut self, cmd: &Throttle) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn delete(&mut self, key: impl AsRef<[u8]>) -> Result<Frame, CashError> {
         let frame = Command::delete_frame(key);
         self.execute(&frame).await
     }
@@ -49,7 +156,61 @@ impl Client {
         self.execute(&frame).await
     }
 
-    async fn execute(&mut self, frame: &Frame) -> Result<Frame, CashError> {
+    pub async fn client(&mut self, cmd: &ClientCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn auth(&mut self, auth: &Auth) -> Result<Frame, CashError> {
+        let frame = auth.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn acl(&mut self, cmd: &AclCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn command(&mut self, cmd: &CommandCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn slowlog(&mut self, cmd: &SlowlogCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn config(&mut self, cmd: &ConfigCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn persistence(&mut self, cmd: &PersistenceCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    pub async fn keyspace(&mut self, cmd: &KeyspaceCmd) -> Result<Frame, CashError> {
+        let frame = cmd.frame();
+        self.execute(&frame).await
+    }
+
+    ///Переводит соединение в режим `MONITOR`.
+    ///Дальше команды сервера читаются через `read_message`
+    pub async fn monitor(&mut self) -> Result<Frame, CashError> {
+        let frame = Command::monitor_frame();
+        self.execute(&frame).await
+    }
+
+    ///Читает следующее сообщение сервера без отправки команды.
+    ///`None` - сервер закрыл соединение
+    pub async fn read_message(&mut self) -> Result<Option<Frame>, CashError> {
+        self.connection.read_frame().await
+    }
+
+    ///Отправляет произвольную команду и возвращает ответ сервера
+    pub async fn execute(&mut self, frame: &Frame) -> Result<Frame, CashError> {
         self.connection.write_frame(frame).await?;
         let response = self.connection.read_frame().await;
 
@@ -65,3 +226,10 @@ impl Client {
         }
     }
 }
+
+fn unexpected(frame: Frame) -> CashError {
+    match frame {
+        Frame::Error(err) => Error::Storage(err),
+        frame => Error::Protocol(format!("unexpected response {:?}", frame)),
+    }
+}
@@ -0,0 +1,102 @@
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::CashError;
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///`AUTH [user] password`. Без имени пользователя используется `default`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Auth {
+    user: Option<String>,
+    password: String,
+}
+
+impl Auth {
+    pub fn new(user: Option<String>, password: String) -> Self {
+        Self { user, password }
+    }
+
+    pub fn user(&self) -> &str {
+        self.user.as_deref().unwrap_or("default")
+    }
+
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec!["auth".to_string()];
+        args.extend(self.user.clone());
+        args.push(self.password.clone());
+
+        Frame::Array(args.into_iter().map(|a| Frame::BulkString(Bytes::from(a))).collect())
+    }
+}
+
+pub(crate) fn parse_auth(parse: &mut Parse) -> Result<Command, CashError> {
+    let first = parse.next_string()?;
+
+    let auth = if parse.remaining() > 0 {
+        Auth { user: Some(first), password: parse.next_string()? }
+    } else {
+        Auth { user: None, password: first }
+    };
+
+    Ok(Command::Auth(auth))
+}
+
+pub(crate) fn parse_setuser(parse: &mut Parse) -> Result<Command, CashError> {
+    let user = parse.next_string()?;
+    let mut rules = vec![];
+    while parse.remaining() > 0 {
+        rules.push(parse.next_string()?);
+    }
+    Ok(Command::Acl(AclCmd::SetUser(user, rules)))
+}
+
+pub(crate) fn parse_deluser(parse: &mut Parse) -> Result<Command, CashError> {
+    let mut users = vec![];
+    while parse.remaining() > 0 {
+        users.push(parse.next_string()?);
+    }
+    Ok(Command::Acl(AclCmd::DelUser(users)))
+}
+
+///Подкоманды `ACL`
+#[derive(Debug, Clone, PartialEq)]
+pub enum AclCmd {
+    SetUser(String, Vec<String>),
+    List,
+    WhoAmI,
+    DelUser(Vec<String>),
+}
+
+impl AclCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AclCmd::SetUser(..) => "acl|setuser",
+            AclCmd::List => "acl|list",
+            AclCmd::WhoAmI => "acl|whoami",
+            AclCmd::DelUser(_) => "acl|deluser",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec!["acl".to_string()];
+
+        match self {
+            AclCmd::SetUser(user, rules) => {
+                args.extend(["setuser".to_string(), user.clone()]);
+                args.extend(rules.iter().cloned());
+            }
+            AclCmd::List => args.push("list".to_string()),
+            AclCmd::WhoAmI => args.push("whoami".to_string()),
+            AclCmd::DelUser(users) => {
+                args.push("deluser".to_string());
+                args.extend(users.iter().cloned());
+            }
+        }
+
+        Frame::Array(args.into_iter().map(|a| Frame::BulkString(Bytes::from(a))).collect())
+    }
+}
@@ -0,0 +1,331 @@
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Единица диапазона в `BITCOUNT` и `BITPOS`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitUnit {
+    Byte,
+    Bit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+///Тип поля `BITFIELD`: `i1`..`i64` или `u1`..`u63`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl BitType {
+    pub fn min(&self) -> i128 {
+        if self.signed { -(1i128 << (self.bits - 1)) } else { 0 }
+    }
+
+    pub fn max(&self) -> i128 {
+        if self.signed { (1i128 << (self.bits - 1)) - 1 } else { (1i128 << self.bits) - 1 }
+    }
+
+    fn name(&self) -> String {
+        format!("{}{}", if self.signed { 'i' } else { 'u' }, self.bits)
+    }
+}
+
+///Поведение `BITFIELD` при переполнении
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+///Операция `BITFIELD`. Смещение уже в битах, `#N` пересчитано в `N * ширина`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldOp {
+    Get(BitType, u64),
+    Set(BitType, u64, i64),
+    IncrBy(BitType, u64, i64),
+    Overflow(Overflow),
+}
+
+///Команды над строками как над массивами битов.
+///Бит 0 - старший бит первого байта, как в redis
+#[derive(Debug, Clone, PartialEq)]
+pub enum BitmapCmd {
+    SetBit(Bytes, u64, bool),
+    GetBit(Bytes, u64),
+    BitCount(Bytes, Option<(i64, i64, BitUnit)>),
+    BitPos(Bytes, bool, Option<i64>, Option<i64>, BitUnit),
+    BitOp(BitOp, Bytes, Vec<Bytes>),
+    BitField(Bytes, Vec<FieldOp>),
+}
+
+///Наибольшее смещение бита: строка не длиннее 512 мегабайт
+const MAX_BIT_OFFSET: u64 = 512 * 1024 * 1024 * 8 - 1;
+
+fn syntax_error() -> Error {
+    Error::CommandParse("syntax error".to_string())
+}
+
+fn parse_offset(parse: &mut Parse) -> Result<u64, CashError> {
+    match parse.next_signed() {
+        Ok(offset) if (0..=MAX_BIT_OFFSET as i64).contains(&offset) => Ok(offset as u64),
+        _ => Err(Error::CommandParse("bit offset is not an integer or out of range".to_string())),
+    }
+}
+
+fn parse_bit(parse: &mut Parse) -> Result<bool, CashError> {
+    match parse.next_signed() {
+        Ok(0) => Ok(false),
+        Ok(1) => Ok(true),
+        _ => Err(Error::CommandParse("bit is not an integer or out of range".to_string())),
+    }
+}
+
+fn parse_unit(parse: &mut Parse) -> Result<BitUnit, CashError> {
+    if parse.remaining() == 0 {
+        return Ok(BitUnit::Byte);
+    }
+
+    match parse.next_string()?.to_lowercase().as_str() {
+        "byte" => Ok(BitUnit::Byte),
+        "bit" => Ok(BitUnit::Bit),
+        _ => Err(syntax_error()),
+    }
+}
+
+pub(crate) fn parse_setbit(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let offset = parse_offset(parse)?;
+    Ok(Command::Bitmap(BitmapCmd::SetBit(key, offset, parse_bit(parse)?)))
+}
+
+pub(crate) fn parse_getbit(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    Ok(Command::Bitmap(BitmapCmd::GetBit(key, parse_offset(parse)?)))
+}
+
+pub(crate) fn parse_bitcount(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+
+    let range = match parse.remaining() {
+        0 => None,
+        1 => return Err(syntax_error()),
+        _ => Some((parse.next_signed()?, parse.next_signed()?, parse_unit(parse)?)),
+    };
+
+    Ok(Command::Bitmap(BitmapCmd::BitCount(key, range)))
+}
+
+pub(crate) fn parse_bitpos(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let bit = parse_bit(parse)?;
+    let start = if parse.remaining() > 0 { Some(parse.next_signed()?) } else { None };
+    let end = if parse.remaining() > 0 { Some(parse.next_signed()?) } else { None };
+
+    Ok(Command::Bitmap(BitmapCmd::BitPos(key, bit, start, end, parse_unit(parse)?)))
+}
+
+pub(crate) fn parse_bitop(parse: &mut Parse) -> Result<Command, CashError> {
+    let op = match parse.next_string()?.to_lowercase().as_str() {
+        "and" => BitOp::And,
+        "or" => BitOp::Or,
+        "xor" => BitOp::Xor,
+        "not" => BitOp::Not,
+        _ => return Err(syntax_error()),
+    };
+    let dest = parse.next_bytes()?;
+
+    let mut keys = vec![];
+    while parse.remaining() > 0 {
+        keys.push(parse.next_bytes()?);
+    }
+
+    if op == BitOp::Not && keys.len() != 1 {
+        return Err(Error::CommandParse("BITOP NOT must be called with a single source key".to_string()));
+    }
+
+    Ok(Command::Bitmap(BitmapCmd::BitOp(op, dest, keys)))
+}
+
+fn parse_type(parse: &mut Parse) -> Result<BitType, CashError> {
+    let name = parse.next_string()?.to_lowercase();
+    let invalid = || Error::CommandParse(
+        "invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is".to_string());
+
+    let (signed, bits) = match name.split_at_checked(1) {
+        Some(("i", bits)) => (true, bits),
+        Some(("u", bits)) => (false, bits),
+        _ => return Err(invalid()),
+    };
+    let bits: u32 = bits.parse().map_err(|_| invalid())?;
+
+    match (signed, bits) {
+        (true, 1..=64) | (false, 1..=63) => Ok(BitType { signed, bits }),
+        _ => Err(invalid()),
+    }
+}
+
+///Смещение поля: число битов или `#N` - N-е поле данного типа
+fn parse_field_offset(parse: &mut Parse, ty: BitType) -> Result<u64, CashError> {
+    let offset = parse.next_string()?;
+    let invalid = || Error::CommandParse("bit offset is not an integer or out of range".to_string());
+
+    let offset = match offset.strip_prefix('#') {
+        Some(index) => index.parse::<u64>().map_err(|_| invalid())?.checked_mul(ty.bits as u64),
+        None => offset.parse::<u64>().ok(),
+    };
+
+    offset
+        .filter(|o| o.checked_add(ty.bits as u64 - 1).is_some_and(|end| end <= MAX_BIT_OFFSET))
+        .ok_or_else(invalid)
+}
+
+pub(crate) fn parse_bitfield(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let mut ops = vec![];
+
+    while parse.remaining() > 0 {
+        let op = match parse.next_string()?.to_lowercase().as_str() {
+            "get" => {
+                let ty = parse_type(parse)?;
+                FieldOp::Get(ty, parse_field_offset(parse, ty)?)
+            }
+            "set" => {
+                let ty = parse_type(parse)?;
+                let offset = parse_field_offset(parse, ty)?;
+                FieldOp::Set(ty, offset, parse.next_signed()?)
+            }
+            "incrby" => {
+                let ty = parse_type(parse)?;
+                let offset = parse_field_offset(parse, ty)?;
+                FieldOp::IncrBy(ty, offset, parse.next_signed()?)
+            }
+            "overflow" => match parse.next_string()?.to_lowercase().as_str() {
+                "wrap" => FieldOp::Overflow(Overflow::Wrap),
+                "sat" => FieldOp::Overflow(Overflow::Sat),
+                "fail" => FieldOp::Overflow(Overflow::Fail),
+                _ => return Err(Error::CommandParse("invalid OVERFLOW type specified".to_string())),
+            },
+            _ => return Err(syntax_error()),
+        };
+        ops.push(op);
+    }
+
+    Ok(Command::Bitmap(BitmapCmd::BitField(key, ops)))
+}
+
+impl BitmapCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BitmapCmd::SetBit(..) => "setbit",
+            BitmapCmd::GetBit(..) => "getbit",
+            BitmapCmd::BitCount(..) => "bitcount",
+            BitmapCmd::BitPos(..) => "bitpos",
+            BitmapCmd::BitOp(..) => "bitop",
+            BitmapCmd::BitField(..) => "bitfield",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec![Bytes::from(self.name())];
+        let unit = |unit: &BitUnit| Bytes::from(if *unit == BitUnit::Bit { "bit" } else { "byte" });
+        let num = |n: String| Bytes::from(n);
+
+        match self {
+            BitmapCmd::SetBit(key, offset, bit) => {
+                args.extend([key.clone(), num(offset.to_string()), num((*bit as u8).to_string())]);
+            }
+            BitmapCmd::GetBit(key, offset) => args.extend([key.clone(), num(offset.to_string())]),
+            BitmapCmd::BitCount(key, range) => {
+                args.push(key.clone());
+                if let Some((start, end, u)) = range {
+                    args.extend([num(start.to_string()), num(end.to_string()), unit(u)]);
+                }
+            }
+            BitmapCmd::BitPos(key, bit, start, end, u) => {
+                args.extend([key.clone(), num((*bit as u8).to_string())]);
+                args.extend(start.iter().chain(end.iter()).map(|n| num(n.to_string())));
+                if end.is_some() {
+                    args.push(unit(u));
+                }
+            }
+            BitmapCmd::BitOp(op, dest, keys) => {
+                let op = match op {
+                    BitOp::And => "and",
+                    BitOp::Or => "or",
+                    BitOp::Xor => "xor",
+                    BitOp::Not => "not",
+                };
+                args.extend([Bytes::from(op), dest.clone()]);
+                args.extend(keys.iter().cloned());
+            }
+            BitmapCmd::BitField(key, ops) => {
+                args.push(key.clone());
+                for op in ops {
+                    let words = match op {
+                        FieldOp::Get(ty, offset) => vec!["get".to_string(), ty.name(), offset.to_string()],
+                        FieldOp::Set(ty, offset, value) => vec!["set".to_string(), ty.name(), offset.to_string(), value.to_string()],
+                        FieldOp::IncrBy(ty, offset, incr) => vec!["incrby".to_string(), ty.name(), offset.to_string(), incr.to_string()],
+                        FieldOp::Overflow(overflow) => vec!["overflow".to_string(), format!("{:?}", overflow).to_lowercase()],
+                    };
+                    args.extend(words.into_iter().map(Bytes::from));
+                }
+            }
+        }
+
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
+
+#[cfg(test)]
+mod bitmap_tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Command, CashError> {
+        Command::from_cmd(input.to_string())
+    }
+
+    #[test]
+    fn parse_bitfield_ops() {
+        let Ok(Command::Bitmap(BitmapCmd::BitField(_, ops))) = parse("bitfield k set u8 #2 255 overflow sat incrby i5 100 1")
+            else { panic!() };
+
+        let u8 = BitType { signed: false, bits: 8 };
+        let i5 = BitType { signed: true, bits: 5 };
+        assert_eq!(vec![
+            FieldOp::Set(u8, 16, 255),
+            FieldOp::Overflow(Overflow::Sat),
+            FieldOp::IncrBy(i5, 100, 1),
+        ], ops);
+
+        assert!(parse("bitfield k get u64 0").is_err());
+        assert!(parse("bitfield k get i0 0").is_err());
+        assert!(parse(&format!("bitfield k set u8 {} 1", u64::MAX)).is_err());
+        assert!(parse(&format!("bitfield k get i64 #{}", u64::MAX / 64)).is_err());
+    }
+
+    #[test]
+    fn parse_ranges_and_bits() {
+        assert!(matches!(parse("bitcount k 1 -1 bit"), Ok(Command::Bitmap(BitmapCmd::BitCount(_, Some((1, -1, BitUnit::Bit)))))));
+        assert!(parse("bitcount k 1").is_err());
+        assert!(parse("setbit k 7 2").is_err());
+        assert!(parse("setbit k -1 1").is_err());
+        assert!(parse("bitop not dest a b").is_err());
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let cmd = BitmapCmd::BitPos(Bytes::from("k"), true, Some(2), Some(-1), BitUnit::Bit);
+        assert!(matches!(Command::from_frame(cmd.frame()), Ok(Command::Bitmap(parsed)) if parsed == cmd));
+    }
+}
@@ -0,0 +1,234 @@
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Подкоманды `CLIENT` для управления соединениями
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientCmd {
+    SetName(String),
+    GetName,
+    Id,
+    List,
+    Kill(Kill),
+    Pause(Pause),
+    Unpause,
+}
+
+///Фильтр `CLIENT KILL`.
+///`legacy` - старая форма `CLIENT KILL addr`, отвечает `Ok` вместо количества
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kill {
+    id: Option<u64>,
+    addr: Option<String>,
+    user: Option<String>,
+    legacy: bool,
+}
+
+impl Kill {
+    pub fn by_id(id: u64) -> Self {
+        Self { id: Some(id), addr: None, user: None, legacy: false }
+    }
+
+    pub fn by_addr(addr: String) -> Self {
+        Self { id: None, addr: Some(addr), user: None, legacy: false }
+    }
+
+    pub fn by_user(user: String) -> Self {
+        Self { id: None, addr: None, user: Some(user), legacy: false }
+    }
+
+    pub fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    pub fn addr(&self) -> Option<&String> {
+        self.addr.as_ref()
+    }
+
+    pub fn user(&self) -> Option<&String> {
+        self.user.as_ref()
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PauseMode {
+    All,
+    Write,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pause {
+    millis: u64,
+    mode: PauseMode,
+}
+
+impl Pause {
+    pub fn new(millis: u64, mode: PauseMode) -> Self {
+        Self { millis, mode }
+    }
+
+    pub fn millis(&self) -> u64 {
+        self.millis
+    }
+
+    pub fn mode(&self) -> PauseMode {
+        self.mode
+    }
+}
+
+pub(crate) fn parse_setname(parse: &mut Parse) -> Result<Command, CashError> {
+    let name = parse.next_string()?;
+    if name.chars().any(|c| c <= ' ' || c > '~') {
+        return Err(Error::CommandParse(
+            "client names cannot contain spaces, newlines or special characters".to_string()));
+    }
+    Ok(Command::Client(ClientCmd::SetName(name)))
+}
+
+pub(crate) fn parse_pause(parse: &mut Parse) -> Result<Command, CashError> {
+    let millis = parse.next_int()?;
+    let mode = if parse.remaining() > 0 {
+        match parse.next_string()?.to_lowercase().as_str() {
+            "all" => PauseMode::All,
+            "write" => PauseMode::Write,
+            _ => return Err(Error::CommandParse("pause mode must be WRITE or ALL".to_string()))
+        }
+    } else {
+        PauseMode::All
+    };
+    Ok(Command::Client(ClientCmd::Pause(Pause::new(millis, mode))))
+}
+
+pub(crate) fn parse_kill(parse: &mut Parse) -> Result<Command, CashError> {
+    if parse.remaining() == 1 {
+        let addr = parse.next_string()?;
+        let kill = Kill { id: None, addr: Some(addr), user: None, legacy: true };
+        return Ok(Command::Client(ClientCmd::Kill(kill)));
+    }
+
+    let mut kill = Kill { id: None, addr: None, user: None, legacy: false };
+
+    while parse.remaining() > 0 {
+        match parse.next_string()?.to_lowercase().as_str() {
+            "id" => kill.id = Some(parse.next_int()?),
+            "addr" => kill.addr = Some(parse.next_string()?),
+            "user" => kill.user = Some(parse.next_string()?),
+            filter => return Err(Error::CommandParse(format!("unsupported kill filter `{}`", filter)))
+        }
+    }
+
+    if kill.id.is_none() && kill.addr.is_none() && kill.user.is_none() {
+        return Err(Error::CommandParse("kill filter is required".to_string()));
+    }
+
+    Ok(Command::Client(ClientCmd::Kill(kill)))
+}
+
+impl ClientCmd {
+    ///Имя подкоманды для `CLIENT LIST`, например `client|list`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientCmd::SetName(_) => "client|setname",
+            ClientCmd::GetName => "client|getname",
+            ClientCmd::Id => "client|id",
+            ClientCmd::List => "client|list",
+            ClientCmd::Kill(_) => "client|kill",
+            ClientCmd::Pause(_) => "client|pause",
+            ClientCmd::Unpause => "client|unpause",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec!["client".to_string()];
+
+        match self {
+            ClientCmd::SetName(name) => args.extend(["setname".to_string(), name.clone()]),
+            ClientCmd::GetName => args.push("getname".to_string()),
+            ClientCmd::Id => args.push("id".to_string()),
+            ClientCmd::List => args.push("list".to_string()),
+            ClientCmd::Kill(kill) => {
+                args.push("kill".to_string());
+                if kill.legacy {
+                    args.extend(kill.addr.clone());
+                } else {
+                    if let Some(id) = kill.id {
+                        args.extend(["id".to_string(), id.to_string()]);
+                    }
+                    if let Some(addr) = &kill.addr {
+                        args.extend(["addr".to_string(), addr.clone()]);
+                    }
+                    if let Some(user) = &kill.user {
+                        args.extend(["user".to_string(), user.clone()]);
+                    }
+                }
+            }
+            ClientCmd::Pause(pause) => {
+                let mode = match pause.mode {
+                    PauseMode::All => "all",
+                    PauseMode::Write => "write",
+                };
+                args.extend(["pause".to_string(), pause.millis.to_string(), mode.to_string()]);
+            }
+            ClientCmd::Unpause => args.push("unpause".to_string()),
+        }
+
+        Frame::Array(args.into_iter().map(|a| Frame::BulkString(Bytes::from(a))).collect())
+    }
+}
+
+#[cfg(test)]
+mod client_tests {
+    use super::*;
+
+    fn client_cmd(input: &str) -> Result<ClientCmd, CashError> {
+        match Command::from_cmd(input.to_string())? {
+            Command::Client(cmd) => Ok(cmd),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn parse_kill_filters() {
+        let kill = match client_cmd("client kill id 7 addr 127.0.0.1:5000").unwrap() {
+            ClientCmd::Kill(kill) => kill,
+            other => panic!("unexpected {:?}", other)
+        };
+        assert_eq!(Some(7), kill.id());
+        assert_eq!(Some(&"127.0.0.1:5000".to_string()), kill.addr());
+        assert!(!kill.is_legacy());
+    }
+
+    #[test]
+    fn parse_kill_legacy() {
+        let cmd = client_cmd("client kill 127.0.0.1:5000").unwrap();
+
+        assert!(matches!(cmd, ClientCmd::Kill(kill) if kill.is_legacy()));
+    }
+
+    #[test]
+    fn parse_setname_rejects_spaces() {
+        let frame = Frame::Array(vec![
+            Frame::BulkString(Bytes::from("client")),
+            Frame::BulkString(Bytes::from("setname")),
+            Frame::BulkString(Bytes::from("app server")),
+        ]);
+
+        assert!(Command::from_frame(frame).is_err());
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let cmd = ClientCmd::Pause(Pause::new(500, PauseMode::Write));
+
+        match Command::from_frame(cmd.frame()).unwrap() {
+            Command::Client(parsed) => assert_eq!(cmd, parsed),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Подкоманды `CONFIG`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigCmd {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    Rewrite,
+}
+
+pub(crate) fn parse_get(parse: &mut Parse) -> Result<Command, CashError> {
+    let mut patterns = vec![];
+    while parse.remaining() > 0 {
+        patterns.push(parse.next_string()?.to_lowercase());
+    }
+    Ok(Command::Config(ConfigCmd::Get(patterns)))
+}
+
+pub(crate) fn parse_set(parse: &mut Parse) -> Result<Command, CashError> {
+    if !parse.remaining().is_multiple_of(2) {
+        return Err(Error::CommandParse("wrong number of arguments for 'config|set' command".to_string()));
+    }
+
+    let mut pairs = vec![];
+    while parse.remaining() > 0 {
+        pairs.push((parse.next_string()?.to_lowercase(), parse.next_string()?));
+    }
+    Ok(Command::Config(ConfigCmd::Set(pairs)))
+}
+
+impl ConfigCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigCmd::Get(_) => "config|get",
+            ConfigCmd::Set(_) => "config|set",
+            ConfigCmd::Rewrite => "config|rewrite",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec!["config".to_string()];
+
+        match self {
+            ConfigCmd::Get(patterns) => {
+                args.push("get".to_string());
+                args.extend(patterns.iter().cloned());
+            }
+            ConfigCmd::Set(pairs) => {
+                args.push("set".to_string());
+                for (name, value) in pairs {
+                    args.extend([name.clone(), value.clone()]);
+                }
+            }
+            ConfigCmd::Rewrite => args.push("rewrite".to_string()),
+        }
+
+        Frame::Array(args.into_iter().map(|a| Frame::BulkString(Bytes::from(a))).collect())
+    }
+}
@@ -0,0 +1,232 @@
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+use crate::storage::filter::{Bloom, Cuckoo, BLOOM_EXPANSION, CUCKOO_BUCKET_SIZE, CUCKOO_EXPANSION, CUCKOO_MAX_ITERATIONS,
+    MAX_EXPANSION};
+
+///Команды вероятностных фильтров: `BF.*` - фильтр Блума, `CF.*` - фильтр с кукушкой.
+///`expansion = 0` в `BfReserve` - фильтр без роста (`NONSCALING`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterCmd {
+    BfReserve { key: Bytes, error_rate: f64, capacity: u64, expansion: u32 },
+    BfAdd(Bytes, Bytes),
+    BfMAdd(Bytes, Vec<Bytes>),
+    BfExists(Bytes, Bytes),
+    BfMExists(Bytes, Vec<Bytes>),
+    CfReserve { key: Bytes, capacity: u64, bucket_size: u8, max_iterations: u16, expansion: u32 },
+    CfAdd(Bytes, Bytes),
+    CfAddNx(Bytes, Bytes),
+    CfExists(Bytes, Bytes),
+    CfDel(Bytes, Bytes),
+}
+
+fn positive(parse: &mut Parse, name: &str) -> Result<u64, CashError> {
+    match parse.next_int()? {
+        0 => Err(Error::CommandParse(format!("{} must be positive", name))),
+        value => Ok(value),
+    }
+}
+
+fn expansion(value: u64) -> Result<u32, CashError> {
+    match value <= MAX_EXPANSION as u64 {
+        true => Ok(value as u32),
+        false => Err(Error::CommandParse(format!("expansion must not exceed {}", MAX_EXPANSION))),
+    }
+}
+
+fn capacity_too_large() -> Error {
+    Error::CommandParse("capacity is too large".to_string())
+}
+
+fn rest(parse: &mut Parse) -> Result<Vec<Bytes>, CashError> {
+    let mut items = vec![];
+    while parse.remaining() > 0 {
+        items.push(parse.next_bytes()?);
+    }
+    Ok(items)
+}
+
+///`BF.RESERVE key error_rate capacity [EXPANSION n] [NONSCALING]`
+pub(crate) fn parse_bf_reserve(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let error_rate: f64 = parse
+        .next_string()?
+        .parse()
+        .ok()
+        .filter(|rate| *rate > 0.0 && *rate < 1.0)
+        .ok_or_else(|| Error::CommandParse("error rate must be in the range (0, 1)".to_string()))?;
+    let capacity = positive(parse, "capacity")?;
+    let mut expansion = BLOOM_EXPANSION;
+
+    while parse.remaining() > 0 {
+        match parse.next_string()?.to_lowercase().as_str() {
+            "expansion" => expansion = self::expansion(positive(parse, "expansion")?)?,
+            "nonscaling" => expansion = 0,
+            _ => return Err(Error::CommandParse("syntax error".to_string())),
+        }
+    }
+
+    Bloom::estimate_size(error_rate, capacity).ok_or_else(capacity_too_large)?;
+    Ok(Command::Filter(FilterCmd::BfReserve { key, error_rate, capacity, expansion }))
+}
+
+pub(crate) fn parse_bf_add(parse: &mut Parse) -> Result<Command, CashError> {
+    let (key, item) = key_item(parse)?;
+    Ok(Command::Filter(FilterCmd::BfAdd(key, item)))
+}
+
+pub(crate) fn parse_bf_madd(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    Ok(Command::Filter(FilterCmd::BfMAdd(key, rest(parse)?)))
+}
+
+pub(crate) fn parse_bf_exists(parse: &mut Parse) -> Result<Command, CashError> {
+    let (key, item) = key_item(parse)?;
+    Ok(Command::Filter(FilterCmd::BfExists(key, item)))
+}
+
+pub(crate) fn parse_bf_mexists(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    Ok(Command::Filter(FilterCmd::BfMExists(key, rest(parse)?)))
+}
+
+///`CF.RESERVE key capacity [BUCKETSIZE n] [MAXITERATIONS n] [EXPANSION n]`
+pub(crate) fn parse_cf_reserve(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let capacity = positive(parse, "capacity")?;
+    let (mut bucket_size, mut max_iterations, mut expansion) =
+        (CUCKOO_BUCKET_SIZE, CUCKOO_MAX_ITERATIONS, CUCKOO_EXPANSION);
+
+    while parse.remaining() > 0 {
+        let option = parse.next_string()?.to_lowercase();
+        let value = parse.next_int()?;
+        match option.as_str() {
+            "bucketsize" if (1..=255).contains(&value) => bucket_size = value as u8,
+            "maxiterations" if (1..=u16::MAX as u64).contains(&value) => max_iterations = value as u16,
+            "expansion" => expansion = self::expansion(value)?,
+            _ => return Err(Error::CommandParse(format!("invalid value for '{}'", option))),
+        }
+    }
+
+    Cuckoo::estimate_size(capacity, bucket_size).ok_or_else(capacity_too_large)?;
+    Ok(Command::Filter(FilterCmd::CfReserve { key, capacity, bucket_size, max_iterations, expansion }))
+}
+
+fn key_item(parse: &mut Parse) -> Result<(Bytes, Bytes), CashError> {
+    Ok((parse.next_bytes()?, parse.next_bytes()?))
+}
+
+pub(crate) fn parse_cf_add(parse: &mut Parse) -> Result<Command, CashError> {
+    let (key, item) = key_item(parse)?;
+    Ok(Command::Filter(FilterCmd::CfAdd(key, item)))
+}
+
+pub(crate) fn parse_cf_addnx(parse: &mut Parse) -> Result<Command, CashError> {
+    let (key, item) = key_item(parse)?;
+    Ok(Command::Filter(FilterCmd::CfAddNx(key, item)))
+}
+
+pub(crate) fn parse_cf_exists(parse: &mut Parse) -> Result<Command, CashError> {
+    let (key, item) = key_item(parse)?;
+    Ok(Command::Filter(FilterCmd::CfExists(key, item)))
+}
+
+pub(crate) fn parse_cf_del(parse: &mut Parse) -> Result<Command, CashError> {
+    let (key, item) = key_item(parse)?;
+    Ok(Command::Filter(FilterCmd::CfDel(key, item)))
+}
+
+impl FilterCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterCmd::BfReserve { .. } => "bf.reserve",
+            FilterCmd::BfAdd(..) => "bf.add",
+            FilterCmd::BfMAdd(..) => "bf.madd",
+            FilterCmd::BfExists(..) => "bf.exists",
+            FilterCmd::BfMExists(..) => "bf.mexists",
+            FilterCmd::CfReserve { .. } => "cf.reserve",
+            FilterCmd::CfAdd(..) => "cf.add",
+            FilterCmd::CfAddNx(..) => "cf.addnx",
+            FilterCmd::CfExists(..) => "cf.exists",
+            FilterCmd::CfDel(..) => "cf.del",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec![Bytes::from(self.name())];
+
+        match self {
+            FilterCmd::BfReserve { key, error_rate, capacity, expansion } => {
+                args.extend([key.clone(), Bytes::from(error_rate.to_string()), Bytes::from(capacity.to_string())]);
+                match expansion {
+                    0 => args.push(Bytes::from("nonscaling")),
+                    n => args.extend([Bytes::from("expansion"), Bytes::from(n.to_string())]),
+                }
+            }
+            FilterCmd::BfMAdd(key, items) | FilterCmd::BfMExists(key, items) => {
+                args.push(key.clone());
+                args.extend(items.iter().cloned());
+            }
+            FilterCmd::CfReserve { key, capacity, bucket_size, max_iterations, expansion } => {
+                args.extend([
+                    key.clone(),
+                    Bytes::from(capacity.to_string()),
+                    Bytes::from("bucketsize"),
+                    Bytes::from(bucket_size.to_string()),
+                    Bytes::from("maxiterations"),
+                    Bytes::from(max_iterations.to_string()),
+                    Bytes::from("expansion"),
+                    Bytes::from(expansion.to_string()),
+                ]);
+            }
+            FilterCmd::BfAdd(key, item)
+            | FilterCmd::BfExists(key, item)
+            | FilterCmd::CfAdd(key, item)
+            | FilterCmd::CfAddNx(key, item)
+            | FilterCmd::CfExists(key, item)
+            | FilterCmd::CfDel(key, item) => args.extend([key.clone(), item.clone()]),
+        }
+
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Command, CashError> {
+        Command::from_cmd(input.to_string())
+    }
+
+    #[test]
+    fn parse_reserve() {
+        assert!(matches!(
+            parse("bf.reserve k 0.001 1000 nonscaling"),
+            Ok(Command::Filter(FilterCmd::BfReserve { expansion: 0, capacity: 1000, .. }))
+        ));
+        assert!(parse("bf.reserve k 1.5 1000").is_err());
+        assert!(parse("bf.reserve k 0.01 0").is_err());
+        assert!(parse("cf.reserve k 100 bucketsize 0").is_err());
+        assert!(parse("bf.reserve k 0.01 10000000000000").is_err());
+        assert!(parse("bf.reserve k 0.01 100 expansion 32769").is_err());
+        assert!(parse(&format!("cf.reserve k {}", u64::MAX)).is_err());
+        assert!(parse("cf.reserve k 100 expansion 32769").is_err());
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let cmds = [
+            FilterCmd::BfReserve { key: Bytes::from("k"), error_rate: 0.001, capacity: 10, expansion: 4 },
+            FilterCmd::BfMAdd(Bytes::from("k"), vec![Bytes::from("a")]),
+            FilterCmd::BfMExists(Bytes::from("k"), vec![Bytes::from("a"), Bytes::from("b")]),
+            FilterCmd::CfReserve { key: Bytes::from("k"), capacity: 10, bucket_size: 4, max_iterations: 50, expansion: 0 },
+        ];
+
+        for cmd in cmds {
+            assert!(matches!(Command::from_frame(cmd.frame()), Ok(Command::Filter(parsed)) if parsed == cmd));
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::CashError;
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Команды HyperLogLog: приблизительный подсчет уникальных элементов
+#[derive(Debug, Clone, PartialEq)]
+pub enum HllCmd {
+    Add(Bytes, Vec<Bytes>),
+    Count(Vec<Bytes>),
+    Merge(Bytes, Vec<Bytes>),
+}
+
+fn rest(parse: &mut Parse) -> Result<Vec<Bytes>, CashError> {
+    let mut values = vec![];
+    while parse.remaining() > 0 {
+        values.push(parse.next_bytes()?);
+    }
+    Ok(values)
+}
+
+pub(crate) fn parse_pfadd(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    Ok(Command::Hll(HllCmd::Add(key, rest(parse)?)))
+}
+
+pub(crate) fn parse_pfcount(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::Hll(HllCmd::Count(rest(parse)?)))
+}
+
+pub(crate) fn parse_pfmerge(parse: &mut Parse) -> Result<Command, CashError> {
+    let dest = parse.next_bytes()?;
+    Ok(Command::Hll(HllCmd::Merge(dest, rest(parse)?)))
+}
+
+impl HllCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HllCmd::Add(..) => "pfadd",
+            HllCmd::Count(_) => "pfcount",
+            HllCmd::Merge(..) => "pfmerge",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec![Bytes::from(self.name())];
+
+        match self {
+            HllCmd::Add(key, values) | HllCmd::Merge(key, values) => {
+                args.push(key.clone());
+                args.extend(values.iter().cloned());
+            }
+            HllCmd::Count(keys) => args.extend(keys.iter().cloned()),
+        }
+
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
@@ -0,0 +1,88 @@
+use bytes::Bytes;
+use crate::core::command::table::{self, COMMANDS};
+use crate::core::command::Command;
+use crate::core::error::CashError;
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Подкоманды `COMMAND`
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandCmd {
+    List,
+    Count,
+    Info(Vec<String>),
+    Docs(Vec<String>),
+}
+
+pub(crate) fn parse_info(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::Command(CommandCmd::Info(names(parse)?)))
+}
+
+pub(crate) fn parse_docs(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::Command(CommandCmd::Docs(names(parse)?)))
+}
+
+fn names(parse: &mut Parse) -> Result<Vec<String>, CashError> {
+    let mut names = vec![];
+    while parse.remaining() > 0 {
+        names.push(parse.next_string()?.to_lowercase());
+    }
+    Ok(names)
+}
+
+impl CommandCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandCmd::List => "command",
+            CommandCmd::Count => "command|count",
+            CommandCmd::Info(_) => "command|info",
+            CommandCmd::Docs(_) => "command|docs",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec!["command".to_string()];
+
+        match self {
+            CommandCmd::List => {}
+            CommandCmd::Count => args.push("count".to_string()),
+            CommandCmd::Info(names) => {
+                args.push("info".to_string());
+                args.extend(names.iter().cloned());
+            }
+            CommandCmd::Docs(names) => {
+                args.push("docs".to_string());
+                args.extend(names.iter().cloned());
+            }
+        }
+
+        Frame::Array(args.into_iter().map(|a| Frame::BulkString(Bytes::from(a))).collect())
+    }
+
+    ///Ответ строится только по таблице команд и не зависит от состояния сервера
+    pub fn execute(&self) -> Frame {
+        match self {
+            CommandCmd::List => Frame::Array(COMMANDS.iter().map(|c| c.info_frame()).collect()),
+            CommandCmd::Count => Frame::Integer(COMMANDS.len() as i64),
+            CommandCmd::Info(names) if names.is_empty() => CommandCmd::List.execute(),
+            CommandCmd::Info(names) => Frame::Array(names
+                .iter()
+                .map(|n| table::lookup_full(n).map_or(Frame::Null, |c| c.info_frame()))
+                .collect()),
+            CommandCmd::Docs(names) => {
+                let specs: Vec<_> = if names.is_empty() {
+                    COMMANDS.iter().collect()
+                } else {
+                    names.iter().filter_map(|n| table::lookup_full(n)).collect()
+                };
+
+                let mut docs = vec![];
+                for spec in specs {
+                    docs.push(Frame::BulkString(Bytes::from(spec.name)));
+                    docs.push(spec.docs_frame());
+                }
+                Frame::Array(docs)
+            }
+        }
+    }
+}
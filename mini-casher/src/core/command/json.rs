@@ -0,0 +1,291 @@
+use bytes::Bytes;
+use serde_json::Value as Json;
+use crate::core::command::Command;
+use crate::core::command::string::Condition;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Шаг JSONPath. `Descend` (`..`) - текущий узел и все его потомки,
+///следующий шаг применяется к каждому из них
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    Descend,
+}
+
+///Путь внутри документа. Путь с `$` возвращает массив всех совпадений,
+///старый синтаксис (`.a.b`, `a[0]`) - одно значение, как в RedisJSON
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    raw: String,
+    segments: Vec<Segment>,
+    legacy: bool,
+}
+
+impl Path {
+    pub fn root() -> Self {
+        Self { raw: "$".to_string(), segments: vec![], legacy: false }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, CashError> {
+        let invalid = || Error::CommandParse(format!("invalid JSONPath '{}'", raw));
+
+        let (legacy, rest) = match raw.strip_prefix('$') {
+            Some(rest) => (false, rest.to_string()),
+            None if raw.is_empty() || raw.starts_with('.') || raw.starts_with('[') => (true, raw.to_string()),
+            None => (true, format!(".{}", raw)),
+        };
+        let legacy_root = legacy && rest == ".";
+
+        let chars: Vec<char> = rest.chars().collect();
+        let mut segments = vec![];
+        let mut i = 0;
+
+        while i < chars.len() && !legacy_root {
+            match chars[i] {
+                '.' if chars.get(i + 1) == Some(&'.') => {
+                    segments.push(Segment::Descend);
+                    i += 2;
+                    if chars.get(i) == Some(&'[') {
+                        continue;
+                    }
+                    i = name(&chars, i, &mut segments).ok_or_else(invalid)?;
+                }
+                '.' => i = name(&chars, i + 1, &mut segments).ok_or_else(invalid)?,
+                '[' => {
+                    let close = chars[i..].iter().position(|&c| c == ']').ok_or_else(invalid)? + i;
+                    let inner: String = chars[i + 1..close].iter().collect();
+                    segments.push(bracket(inner.trim()).ok_or_else(invalid)?);
+                    i = close + 1;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Self { raw: raw.to_string(), segments, legacy })
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+}
+
+///Имя поля после точки до следующей точки или скобки
+fn name(chars: &[char], start: usize, segments: &mut Vec<Segment>) -> Option<usize> {
+    let end = chars[start..].iter().position(|&c| c == '.' || c == '[').map_or(chars.len(), |p| p + start);
+    let name: String = chars[start..end].iter().collect();
+
+    match name.as_str() {
+        "" => return None,
+        "*" => segments.push(Segment::Wildcard),
+        _ => segments.push(Segment::Key(name)),
+    }
+    Some(end)
+}
+
+///Содержимое скобок: `*`, индекс или имя в кавычках
+fn bracket(inner: &str) -> Option<Segment> {
+    if inner == "*" {
+        return Some(Segment::Wildcard);
+    }
+    if let Ok(index) = inner.parse::<i64>() {
+        return Some(Segment::Index(index));
+    }
+
+    let quoted = inner.len() >= 2
+        && ((inner.starts_with('\'') && inner.ends_with('\'')) || (inner.starts_with('"') && inner.ends_with('"')));
+    quoted.then(|| Segment::Key(inner[1..inner.len() - 1].to_string()))
+}
+
+///Команды над JSON-документами
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonCmd {
+    Set(Bytes, Path, Json, Option<Condition>),
+    Get(Bytes, Vec<Path>),
+    Del(Bytes, Path),
+    GetDel(Bytes),
+    ArrAppend(Bytes, Path, Vec<Json>),
+    NumIncrBy(Bytes, Path, serde_json::Number),
+    Type(Bytes, Path),
+}
+
+fn next_path(parse: &mut Parse) -> Result<Path, CashError> {
+    Path::parse(&parse.next_string()?)
+}
+
+fn optional_path(parse: &mut Parse) -> Result<Path, CashError> {
+    if parse.remaining() > 0 {
+        next_path(parse)
+    } else {
+        Ok(Path::root())
+    }
+}
+
+fn next_json(parse: &mut Parse) -> Result<Json, CashError> {
+    let bytes = parse.next_bytes()?;
+    serde_json::from_slice(&bytes).map_err(|err| Error::CommandParse(format!("invalid JSON: {}", err)))
+}
+
+pub(crate) fn parse_set(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let path = next_path(parse)?;
+    let value = next_json(parse)?;
+
+    let condition = match parse.remaining() {
+        0 => None,
+        _ => match parse.next_string()?.to_lowercase().as_str() {
+            "nx" => Some(Condition::Nx),
+            "xx" => Some(Condition::Xx),
+            _ => return Err(Error::CommandParse("syntax error".to_string())),
+        },
+    };
+    parse.finish()?;
+
+    Ok(Command::Json(JsonCmd::Set(key, path, value, condition)))
+}
+
+pub(crate) fn parse_get(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let mut paths = vec![];
+    while parse.remaining() > 0 {
+        paths.push(next_path(parse)?);
+    }
+    if paths.is_empty() {
+        paths.push(Path::parse(".")?);
+    }
+
+    Ok(Command::Json(JsonCmd::Get(key, paths)))
+}
+
+pub(crate) fn parse_del(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    Ok(Command::Json(JsonCmd::Del(key, optional_path(parse)?)))
+}
+
+pub(crate) fn parse_getdel(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    parse.finish()?;
+    Ok(Command::Json(JsonCmd::GetDel(key)))
+}
+
+pub(crate) fn parse_arrappend(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let path = next_path(parse)?;
+    let mut values = vec![];
+    while parse.remaining() > 0 {
+        values.push(next_json(parse)?);
+    }
+
+    Ok(Command::Json(JsonCmd::ArrAppend(key, path, values)))
+}
+
+pub(crate) fn parse_numincrby(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let path = next_path(parse)?;
+    let Json::Number(number) = next_json(parse)? else {
+        return Err(Error::CommandParse("increment is not a number".to_string()));
+    };
+
+    Ok(Command::Json(JsonCmd::NumIncrBy(key, path, number)))
+}
+
+pub(crate) fn parse_type(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    Ok(Command::Json(JsonCmd::Type(key, optional_path(parse)?)))
+}
+
+impl JsonCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JsonCmd::Set(..) => "json.set",
+            JsonCmd::Get(..) => "json.get",
+            JsonCmd::Del(..) => "json.del",
+            JsonCmd::GetDel(..) => "json.getdel",
+            JsonCmd::ArrAppend(..) => "json.arrappend",
+            JsonCmd::NumIncrBy(..) => "json.numincrby",
+            JsonCmd::Type(..) => "json.type",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let path = |path: &Path| Bytes::from(path.as_str().to_string());
+        let json = |value: &Json| Bytes::from(value.to_string());
+        let mut args = vec![Bytes::from(self.name())];
+
+        match self {
+            JsonCmd::Set(key, p, value, condition) => {
+                args.extend([key.clone(), path(p), json(value)]);
+                match condition {
+                    Some(Condition::Nx) => args.push(Bytes::from("nx")),
+                    Some(Condition::Xx) => args.push(Bytes::from("xx")),
+                    None => {}
+                }
+            }
+            JsonCmd::Get(key, paths) => {
+                args.push(key.clone());
+                args.extend(paths.iter().map(path));
+            }
+            JsonCmd::Del(key, p) | JsonCmd::Type(key, p) => args.extend([key.clone(), path(p)]),
+            JsonCmd::GetDel(key) => args.push(key.clone()),
+            JsonCmd::ArrAppend(key, p, values) => {
+                args.extend([key.clone(), path(p)]);
+                args.extend(values.iter().map(json));
+            }
+            JsonCmd::NumIncrBy(key, p, number) => {
+                args.extend([key.clone(), path(p), Bytes::from(number.to_string())]);
+            }
+        }
+
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
+
+#[cfg(test)]
+mod json_tests {
+    use super::*;
+
+    #[test]
+    fn parse_paths() {
+        let path = Path::parse("$.tasks[-1]['name']").unwrap();
+        assert!(!path.is_legacy());
+        assert_eq!(
+            &[Segment::Key("tasks".to_string()), Segment::Index(-1), Segment::Key("name".to_string())],
+            path.segments()
+        );
+
+        let path = Path::parse("$..status").unwrap();
+        assert_eq!(&[Segment::Descend, Segment::Key("status".to_string())], path.segments());
+        assert_eq!(&[Segment::Wildcard], Path::parse("$[*]").unwrap().segments());
+
+        assert!(Path::parse(".").unwrap().is_root());
+        assert!(Path::parse("status").unwrap().is_legacy());
+        assert!(Path::parse("$.").is_err());
+        assert!(Path::parse("$[abc]").is_err());
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let cmd = JsonCmd::Set(
+            Bytes::from("todo:1"),
+            Path::parse("$.status").unwrap(),
+            Json::String("done".to_string()),
+            Some(Condition::Xx),
+        );
+        assert!(matches!(Command::from_frame(cmd.frame()), Ok(Command::Json(parsed)) if parsed == cmd));
+    }
+}
@@ -0,0 +1,252 @@
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::command::acl::Auth;
+use crate::core::command::string::Expiry;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Обход ключей и перенос между серверами: `SCAN`, `TYPE`, `TTL`, `PTTL`, `DUMP`, `RESTORE` и `MIGRATE`
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyspaceCmd {
+    Scan(Scan),
+    Type(Bytes),
+    Ttl(Bytes),
+    Pttl(Bytes),
+    Dump(Bytes),
+    Restore(Restore),
+    Migrate(Migrate),
+}
+
+///Число ключей за шаг `SCAN` по умолчанию
+pub const SCAN_COUNT: u64 = 10;
+
+///`SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]`.
+///`MATCH` и `TYPE` отбирают ключи после шага, поэтому ответ может быть пустым при ненулевом курсоре
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    pub cursor: u64,
+    pub pattern: Option<Bytes>,
+    pub count: u64,
+    pub kind: Option<String>,
+}
+
+///`RESTORE key ttl payload [REPLACE] [ABSTTL]`.
+///`ttl` в миллисекундах, `0` - без срока, с `ABSTTL` - unix-время в миллисекундах
+#[derive(Debug, Clone, PartialEq)]
+pub struct Restore {
+    pub key: Bytes,
+    pub ttl: u64,
+    pub payload: Bytes,
+    pub replace: bool,
+    pub absttl: bool,
+}
+
+///`MIGRATE host port key|"" db timeout [COPY] [REPLACE] [AUTH password | AUTH2 user password] [KEYS key ...]`.
+///База у сервера одна, поэтому `db` может быть только `0`. `timeout` в миллисекундах
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migrate {
+    pub host: String,
+    pub port: u16,
+    pub keys: Vec<Bytes>,
+    pub timeout: u64,
+    pub copy: bool,
+    pub replace: bool,
+    pub auth: Option<Auth>,
+}
+
+pub(crate) fn parse_scan(parse: &mut Parse) -> Result<Command, CashError> {
+    let mut scan = Scan { cursor: parse.next_int()?, pattern: None, count: SCAN_COUNT, kind: None };
+
+    while parse.remaining() > 0 {
+        match parse.next_string()?.to_lowercase().as_str() {
+            "match" => scan.pattern = Some(parse.next_bytes()?),
+            "count" => scan.count = parse.next_int()?,
+            "type" => scan.kind = Some(parse.next_string()?),
+            option => return Err(Error::CommandParse(format!("unknown scan option '{}'", option))),
+        }
+    }
+    if scan.count == 0 {
+        return Err(Error::CommandParse("count must be positive".to_string()));
+    }
+
+    Ok(Command::Keyspace(KeyspaceCmd::Scan(scan)))
+}
+
+pub(crate) fn parse_type(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::Keyspace(KeyspaceCmd::Type(parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_ttl(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::Keyspace(KeyspaceCmd::Ttl(parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_pttl(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::Keyspace(KeyspaceCmd::Pttl(parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_dump(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::Keyspace(KeyspaceCmd::Dump(parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_restore(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let ttl = parse.next_int()?;
+    let payload = parse.next_bytes()?;
+    let mut restore = Restore { key, ttl, payload, replace: false, absttl: false };
+
+    while parse.remaining() > 0 {
+        match parse.next_string()?.to_lowercase().as_str() {
+            "replace" => restore.replace = true,
+            "absttl" => restore.absttl = true,
+            option => return Err(Error::CommandParse(format!("unknown restore option '{}'", option))),
+        }
+    }
+
+    let expiry = match restore.absttl {
+        true => Expiry::PxAt(restore.ttl),
+        false => Expiry::Px(restore.ttl),
+    };
+    if !expiry.in_range() {
+        return Err(Error::CommandParse("invalid expire time in 'restore' command".to_string()));
+    }
+
+    Ok(Command::Keyspace(KeyspaceCmd::Restore(restore)))
+}
+
+pub(crate) fn parse_migrate(parse: &mut Parse) -> Result<Command, CashError> {
+    let host = parse.next_string()?;
+    let port = u16::try_from(parse.next_int()?).map_err(|_| Error::CommandParse("invalid port".to_string()))?;
+    let key = parse.next_bytes()?;
+    if parse.next_int()? != 0 {
+        return Err(Error::CommandParse("only db 0 is supported".to_string()));
+    }
+    let timeout = parse.next_int()?;
+
+    let mut migrate = Migrate { host, port, keys: vec![], timeout, copy: false, replace: false, auth: None };
+    while parse.remaining() > 0 {
+        match parse.next_string()?.to_lowercase().as_str() {
+            "copy" => migrate.copy = true,
+            "replace" => migrate.replace = true,
+            "auth" => migrate.auth = Some(Auth::new(None, parse.next_string()?)),
+            "auth2" => migrate.auth = Some(Auth::new(Some(parse.next_string()?), parse.next_string()?)),
+            "keys" => {
+                while parse.remaining() > 0 {
+                    migrate.keys.push(parse.next_bytes()?);
+                }
+            }
+            option => return Err(Error::CommandParse(format!("unknown migrate option '{}'", option))),
+        }
+    }
+
+    match (key.is_empty(), migrate.keys.is_empty()) {
+        (false, true) => migrate.keys.push(key),
+        (true, false) => {}
+        _ => return Err(Error::CommandParse("use either key or KEYS with an empty key".to_string())),
+    }
+
+    Ok(Command::Keyspace(KeyspaceCmd::Migrate(migrate)))
+}
+
+impl KeyspaceCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyspaceCmd::Scan(_) => "scan",
+            KeyspaceCmd::Type(_) => "type",
+            KeyspaceCmd::Ttl(_) => "ttl",
+            KeyspaceCmd::Pttl(_) => "pttl",
+            KeyspaceCmd::Dump(_) => "dump",
+            KeyspaceCmd::Restore(_) => "restore",
+            KeyspaceCmd::Migrate(_) => "migrate",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec![Bytes::from(self.name())];
+        match self {
+            KeyspaceCmd::Scan(scan) => {
+                args.extend([Bytes::from(scan.cursor.to_string()), Bytes::from("count"), Bytes::from(scan.count.to_string())]);
+                if let Some(pattern) = &scan.pattern {
+                    args.extend([Bytes::from("match"), pattern.clone()]);
+                }
+                if let Some(kind) = &scan.kind {
+                    args.extend([Bytes::from("type"), Bytes::from(kind.clone())]);
+                }
+            }
+            KeyspaceCmd::Type(key) | KeyspaceCmd::Ttl(key) | KeyspaceCmd::Pttl(key) | KeyspaceCmd::Dump(key) => {
+                args.push(key.clone())
+            }
+            KeyspaceCmd::Restore(restore) => {
+                args.extend([restore.key.clone(), Bytes::from(restore.ttl.to_string()), restore.payload.clone()]);
+                if restore.replace {
+                    args.push(Bytes::from("replace"));
+                }
+                if restore.absttl {
+                    args.push(Bytes::from("absttl"));
+                }
+            }
+            KeyspaceCmd::Migrate(migrate) => {
+                args.extend([
+                    Bytes::from(migrate.host.clone()),
+                    Bytes::from(migrate.port.to_string()),
+                    Bytes::new(),
+                    Bytes::from("0"),
+                    Bytes::from(migrate.timeout.to_string()),
+                ]);
+                if migrate.copy {
+                    args.push(Bytes::from("copy"));
+                }
+                if migrate.replace {
+                    args.push(Bytes::from("replace"));
+                }
+                if let Some(auth) = &migrate.auth {
+                    match auth.user() {
+                        "default" => args.push(Bytes::from("auth")),
+                        user => args.extend([Bytes::from("auth2"), Bytes::from(user.to_string())]),
+                    }
+                    args.push(Bytes::from(auth.password().to_string()));
+                }
+                args.push(Bytes::from("keys"));
+                args.extend(migrate.keys.iter().cloned());
+            }
+        }
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
+
+#[cfg(test)]
+mod keyspace_tests {
+    use super::*;
+
+    fn round_trip(input: &str) -> KeyspaceCmd {
+        let Ok(Command::Keyspace(cmd)) = Command::from_cmd(input.to_string()) else { panic!("{}", input) };
+        assert!(matches!(Command::from_frame(cmd.frame()), Ok(Command::Keyspace(parsed)) if parsed == cmd));
+        cmd
+    }
+
+    #[test]
+    fn parse_and_frame() {
+        round_trip("dump todo:1");
+        round_trip("pttl todo:1");
+        let KeyspaceCmd::Scan(scan) = round_trip("scan 0 match todo:* type ReJSON-RL") else { panic!() };
+        assert_eq!((SCAN_COUNT, Some(Bytes::from("todo:*"))), (scan.count, scan.pattern));
+        assert!(Command::from_cmd("scan 0 count 0".to_string()).is_err());
+
+        let KeyspaceCmd::Restore(restore) = round_trip("restore todo:1 5000 payload replace") else { panic!() };
+        assert!(restore.replace && !restore.absttl);
+
+        let KeyspaceCmd::Migrate(migrate) = round_trip("migrate 127.0.0.1 6380 todo:1 0 1000 copy auth2 u p") else { panic!() };
+        assert_eq!(vec![Bytes::from("todo:1")], migrate.keys);
+        assert_eq!(Some("u"), migrate.auth.as_ref().map(Auth::user));
+
+        let args = ["migrate", "127.0.0.1", "6380", "", "0", "1000", "keys", "a", "b"];
+        let frame = Frame::Array(args.into_iter().map(|a| Frame::BulkString(Bytes::from(a))).collect());
+        let Ok(Command::Keyspace(KeyspaceCmd::Migrate(migrate))) = Command::from_frame(frame) else { panic!() };
+        assert_eq!(2, migrate.keys.len());
+
+        assert!(Command::from_cmd("migrate 127.0.0.1 6380 a 1 1000".to_string()).is_err());
+        assert!(Command::from_cmd("migrate 127.0.0.1 6380 a 0 1000 keys b".to_string()).is_err());
+        assert!(Command::from_cmd("restore k 0 payload absttl now".to_string()).is_err());
+        assert!(Command::from_cmd(format!("restore k {} payload", u64::MAX)).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Команды блокировок с токенами ограждения.
+///`Acquire` без `wait` не ждет: если блокировка занята, сразу возвращается `Null`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LockCmd {
+    Acquire { name: Bytes, ttl: Duration, wait: Option<Duration> },
+    Release { name: Bytes, token: u64 },
+    Extend { name: Bytes, token: u64, ttl: Duration },
+}
+
+///Срок и ожидание не больше `i64::MAX` миллисекунд, как сроки жизни ключей
+fn millis(parse: &mut Parse, what: &str, zero: bool) -> Result<Duration, CashError> {
+    match parse.next_int()? {
+        ms if (ms > 0 || zero) && ms <= i64::MAX as u64 => Ok(Duration::from_millis(ms)),
+        _ => Err(Error::CommandParse(format!("invalid {} in lock command", what))),
+    }
+}
+
+///`LOCK.ACQUIRE name ttl-ms [WAIT timeout-ms]`
+pub(crate) fn parse_acquire(parse: &mut Parse) -> Result<Command, CashError> {
+    let name = parse.next_bytes()?;
+    let ttl = millis(parse, "ttl", false)?;
+
+    let wait = match parse.remaining() {
+        0 => None,
+        _ if parse.next_string()?.eq_ignore_ascii_case("wait") => Some(millis(parse, "wait", true)?),
+        _ => return Err(Error::CommandParse("syntax error".to_string())),
+    };
+    parse.finish()?;
+
+    Ok(Command::Lock(LockCmd::Acquire { name, ttl, wait }))
+}
+
+pub(crate) fn parse_release(parse: &mut Parse) -> Result<Command, CashError> {
+    let name = parse.next_bytes()?;
+    Ok(Command::Lock(LockCmd::Release { name, token: parse.next_int()? }))
+}
+
+///`LOCK.EXTEND name token ttl-ms`
+pub(crate) fn parse_extend(parse: &mut Parse) -> Result<Command, CashError> {
+    let name = parse.next_bytes()?;
+    let token = parse.next_int()?;
+    Ok(Command::Lock(LockCmd::Extend { name, token, ttl: millis(parse, "ttl", false)? }))
+}
+
+impl LockCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LockCmd::Acquire { .. } => "lock.acquire",
+            LockCmd::Release { .. } => "lock.release",
+            LockCmd::Extend { .. } => "lock.extend",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let ms = |d: &Duration| Bytes::from(d.as_millis().to_string());
+        let mut args = vec![Bytes::from(self.name())];
+
+        match self {
+            LockCmd::Acquire { name, ttl, wait } => {
+                args.extend([name.clone(), ms(ttl)]);
+                if let Some(wait) = wait {
+                    args.extend([Bytes::from("wait"), ms(wait)]);
+                }
+            }
+            LockCmd::Release { name, token } => args.extend([name.clone(), Bytes::from(token.to_string())]),
+            LockCmd::Extend { name, token, ttl } => {
+                args.extend([name.clone(), Bytes::from(token.to_string()), ms(ttl)]);
+            }
+        }
+
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
+
+#[cfg(test)]
+mod lock_tests {
+    use super::*;
+
+    #[test]
+    fn parse_acquire_options() {
+        let Ok(Command::Lock(cmd)) = Command::from_cmd("lock.acquire jobs 3000 wait 500".to_string()) else { panic!() };
+        assert_eq!(
+            LockCmd::Acquire {
+                name: Bytes::from("jobs"),
+                ttl: Duration::from_secs(3),
+                wait: Some(Duration::from_millis(500))
+            },
+            cmd
+        );
+        assert!(matches!(Command::from_frame(cmd.frame()), Ok(Command::Lock(parsed)) if parsed == cmd));
+
+        assert!(Command::from_cmd("lock.acquire jobs 0".to_string()).is_err());
+        assert!(Command::from_cmd("lock.acquire jobs 100 block 5".to_string()).is_err());
+        assert!(Command::from_cmd(format!("lock.acquire jobs {}", u64::MAX)).is_err());
+        assert!(Command::from_cmd(format!("lock.acquire jobs 100 wait {}", u64::MAX)).is_err());
+        assert!(Command::from_cmd(format!("lock.extend jobs 1 {}", u64::MAX)).is_err());
+    }
+}
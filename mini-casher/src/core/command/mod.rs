@@ -0,0 +1,291 @@
+use bytes::Bytes;
+use crate::core::command::acl::{AclCmd, Auth};
+use crate::core::command::bitmap::BitmapCmd;
+use crate::core::command::client::ClientCmd;
+use crate::core::command::config::ConfigCmd;
+use crate::core::command::filter::FilterCmd;
+use crate::core::command::hyperloglog::HllCmd;
+use crate::core::command::introspection::CommandCmd;
+use crate::core::command::json::JsonCmd;
+use crate::core::command::keyspace::KeyspaceCmd;
+use crate::core::command::lock::LockCmd;
+use crate::core::command::persistence::PersistenceCmd;
+use crate::core::command::search::SearchCmd;
+use crate::core::command::slowlog::SlowlogCmd;
+use crate::core::command::string::{Condition, Expiry, StringCmd};
+use crate::core::command::table::{CommandSpec, Flag};
+use crate::core::command::throttle::Throttle;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+pub mod client;
+pub mod config;
+pub mod filter;
+pub mod hyperloglog;
+pub mod acl;
+pub mod bitmap;
+pub mod introspection;
+pub mod json;
+pub mod keyspace;
+pub mod lock;
+pub mod persistence;
+pub mod search;
+pub mod slowlog;
+pub mod string;
+pub mod table;
+pub mod throttle;
+
+#[derive(Debug)]
+pub enum Command {
+    Get(Get),
+    Set(Set),
+    Delete(Get),
+    Len,
+    All,
+    Ping,
+    Client(ClientCmd),
+    Auth(Auth),
+    Acl(AclCmd),
+    Command(CommandCmd),
+    Slowlog(SlowlogCmd),
+    Monitor,
+    Config(ConfigCmd),
+    String(StringCmd),
+    Bitmap(BitmapCmd),
+    Hll(HllCmd),
+    Json(JsonCmd),
+    Search(SearchCmd),
+    Filter(FilterCmd),
+    Lock(LockCmd),
+    Throttle(Throttle),
+    Persistence(PersistenceCmd),
+    Keyspace(KeyspaceCmd),
+}
+
+///Ключи, как и значения, хранятся и передаются байтами:
+///они не обязаны быть строками UTF-8
+#[derive(Debug)]
+pub struct Get {
+    key: Bytes,
+}
+
+impl Get {
+    pub fn new(key: Bytes) -> Self {
+        Self { key }
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+}
+
+///`SET` с опциями: условие `NX`/`XX`, `GET` - вернуть старое значение,
+///и срок жизни ключа
+#[derive(Debug)]
+pub struct Set {
+    key: Bytes,
+    value: Bytes,
+    condition: Option<Condition>,
+    get: bool,
+    expiry: Option<Expiry>,
+}
+
+impl Set {
+    pub fn new(key: Bytes, value: Bytes) -> Self {
+        Self { key, value, condition: None, get: false, expiry: None }
+    }
+
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn with_get(mut self) -> Self {
+        self.get = true;
+        self
+    }
+
+    pub fn with_expiry(mut self, expiry: Expiry) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    pub fn condition(&self) -> Option<Condition> {
+        self.condition
+    }
+
+    pub fn get(&self) -> bool {
+        self.get
+    }
+
+    pub fn expiry(&self) -> Option<Expiry> {
+        self.expiry
+    }
+
+    pub fn key(&self) -> &Bytes {
+        &self.key
+    }
+
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+}
+
+
+impl Command {
+    pub fn get_frame(key: impl AsRef<[u8]>) -> Frame {
+        Frame::Array(vec![
+            Frame::BulkString(Bytes::from("get")),
+            Frame::BulkString(Bytes::copy_from_slice(key.as_ref())),
+        ])
+    }
+
+    pub fn set_frame(key: impl AsRef<[u8]>, value: Bytes) -> Frame {
+        Frame::Array(vec![
+            Frame::BulkString(Bytes::from("set")),
+            Frame::BulkString(Bytes::copy_from_slice(key.as_ref())),
+            Frame::BulkString(value),
+        ])
+    }
+
+    pub fn set_with_frame(set: &Set) -> Frame {
+        let mut frames = vec![
+            Frame::BulkString(Bytes::from("set")),
+            Frame::BulkString(set.key.clone()),
+            Frame::BulkString(set.value.clone()),
+        ];
+        frames.extend(set.option_args().into_iter().map(|a| Frame::BulkString(Bytes::from(a))));
+        Frame::Array(frames)
+    }
+
+    pub fn delete_frame(key: impl AsRef<[u8]>) -> Frame {
+        Frame::Array(vec![
+            Frame::BulkString(Bytes::from("delete")),
+            Frame::BulkString(Bytes::copy_from_slice(key.as_ref())),
+        ])
+    }
+
+    pub fn ping_frame() -> Frame {
+        Frame::Array(vec![Frame::BulkString(Bytes::from("ping"))])
+    }
+
+    pub fn len_frame() -> Frame {
+        Frame::Array(vec![Frame::BulkString(Bytes::from("len"))])
+    }
+
+    pub fn all_frame() -> Frame {
+        Frame::Array(vec![Frame::BulkString(Bytes::from("all"))])
+    }
+
+    pub fn monitor_frame() -> Frame {
+        Frame::Array(vec![Frame::BulkString(Bytes::from("monitor"))])
+    }
+
+    ///Имя команды, которое показывается в `CLIENT LIST`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Get(_) => "get",
+            Command::Set(_) => "set",
+            Command::Delete(_) => "delete",
+            Command::Len => "len",
+            Command::All => "all",
+            Command::Ping => "ping",
+            Command::Client(cmd) => cmd.name(),
+            Command::Auth(_) => "auth",
+            Command::Acl(cmd) => cmd.name(),
+            Command::Command(cmd) => cmd.name(),
+            Command::Slowlog(cmd) => cmd.name(),
+            Command::Monitor => "monitor",
+            Command::Config(cmd) => cmd.name(),
+            Command::String(cmd) => cmd.name(),
+            Command::Bitmap(cmd) => cmd.name(),
+            Command::Hll(cmd) => cmd.name(),
+            Command::Json(cmd) => cmd.name(),
+            Command::Search(cmd) => cmd.name(),
+            Command::Filter(cmd) => cmd.name(),
+            Command::Lock(cmd) => cmd.name(),
+            Command::Throttle(_) => "throttle",
+            Command::Persistence(cmd) => cmd.name(),
+            Command::Keyspace(cmd) => cmd.name(),
+        }
+    }
+
+    ///Описание команды в таблице команд
+    pub fn spec(&self) -> &'static CommandSpec {
+        table::lookup_full(self.name()).expect("every command is described in the command table")
+    }
+
+    ///Команда изменяет данные, учитывается в `CLIENT PAUSE WRITE`
+    pub fn is_write(&self) -> bool {
+        self.spec().has_flag(Flag::Write)
+    }
+
+    ///Разбирает строку, введенную в консоли
+    pub fn from_cmd(input: String) -> Result<Command, CashError> {
+        let words = input.split_whitespace();
+        Command::parse(Parse::from_words(words))
+    }
+
+    pub fn from_frame(frame: Frame) -> Result<Command, CashError> {
+        Command::parse(Parse::new(frame)?)
+    }
+
+    ///Находит команду в таблице по имени (и имени подкоманды),
+    ///проверяет количество аргументов и разбирает их функцией из таблицы
+    fn parse(mut parse: Parse) -> Result<Command, CashError> {
+        let argc = parse.remaining();
+        let name = parse.next_string()?.to_lowercase();
+
+        let mut spec = table::lookup(&name).ok_or_else(|| {
+            log::error!("unsupported command");
+            Error::CommandParse("unsupported command".to_string())
+        })?;
+
+        if !spec.subcommands.is_empty() && parse.remaining() > 0 {
+            let sub = parse.next_string()?.to_lowercase();
+            spec = spec.subcommand(&sub)
+                .ok_or_else(|| Error::CommandParse(format!("unknown subcommand `{} {}`", name, sub)))?;
+        }
+
+        spec.check_arity(argc)?;
+
+        let parse_fn = spec.parse
+            .ok_or_else(|| Error::CommandParse(format!("wrong number of arguments for '{}' command", name)))?;
+        let command = parse_fn(&mut parse)?;
+        parse.finish()?;
+
+        Ok(command)
+    }
+
+    pub(crate) fn parse_get(parse: &mut Parse) -> Result<Command, CashError> {
+        let key = parse.next_bytes()?;
+        Ok(Command::Get(Get { key }))
+    }
+
+    pub(crate) fn parse_set(parse: &mut Parse) -> Result<Command, CashError> {
+        let mut set = Set::new(parse.next_bytes()?, parse.next_bytes()?);
+        string::parse_set_options(parse, &mut set)?;
+        Ok(Command::Set(set))
+    }
+
+    pub(crate) fn parse_delete(parse: &mut Parse) -> Result<Command, CashError> {
+        let key = parse.next_bytes()?;
+        Ok(Command::Delete(Get { key }))
+    }
+}
+
+#[cfg(test)]
+mod command_tests {
+    use super::*;
+
+    #[test]
+    fn binary_key_round_trip() {
+        let key = [0xffu8, 0x00, b't', 0xfe];
+        let frame = Command::set_frame(key, Bytes::from("value"));
+
+        let Ok(Command::Set(set)) = Command::from_frame(frame) else { panic!() };
+        assert_eq!(&Bytes::copy_from_slice(&key), set.key());
+        assert_eq!(&Bytes::from("value"), set.value());
+    }
+}
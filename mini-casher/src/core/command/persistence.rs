@@ -0,0 +1,26 @@
+use bytes::Bytes;
+use crate::core::frames::Frame;
+
+///Команды сохранения данных на диск
+#[derive(Debug, Clone, PartialEq)]
+pub enum PersistenceCmd {
+    Save,
+    BgSave,
+    LastSave,
+    BgRewriteAof,
+}
+
+impl PersistenceCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PersistenceCmd::Save => "save",
+            PersistenceCmd::BgSave => "bgsave",
+            PersistenceCmd::LastSave => "lastsave",
+            PersistenceCmd::BgRewriteAof => "bgrewriteaof",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        Frame::Array(vec![Frame::BulkString(Bytes::from(self.name()))])
+    }
+}
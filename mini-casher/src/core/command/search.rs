@@ -0,0 +1,378 @@
+use bytes::Bytes;
+use crate::core::command::json::Path;
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Тип индексируемого поля. `Tag` - точные значения через разделитель,
+///`Text` - слова для полнотекстового поиска, `Numeric` - числа для диапазонов
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    Tag(char),
+    Text,
+    Numeric,
+}
+
+///Поле схемы индекса: путь в документе и имя, по которому к нему обращается запрос
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub path: Path,
+    pub name: String,
+    pub kind: FieldKind,
+}
+
+///Описание индекса из `FT.CREATE`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexDef {
+    pub name: String,
+    pub prefixes: Vec<Bytes>,
+    pub fields: Vec<Field>,
+}
+
+///Граница числового диапазона: значение и включается ли оно
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bound {
+    pub value: f64,
+    pub inclusive: bool,
+}
+
+impl Bound {
+    fn parse(raw: &str) -> Option<Self> {
+        let (inclusive, raw) = match raw.strip_prefix('(') {
+            Some(rest) => (false, rest),
+            None => (true, raw),
+        };
+        let value = match raw {
+            "-inf" => f64::NEG_INFINITY,
+            "+inf" | "inf" => f64::INFINITY,
+            _ => raw.parse().ok()?,
+        };
+        Some(Self { value, inclusive })
+    }
+}
+
+///Условие запроса. Слово с `*` на конце ищется как префикс
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clause {
+    All,
+    Tag(String, Vec<String>),
+    Numeric(String, Bound, Bound),
+    Text(Option<String>, Vec<String>),
+}
+
+///Запрос `FT.SEARCH`: условия через пробел объединяются по И, `-` перед условием - отрицание
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    raw: String,
+    clauses: Vec<(bool, Clause)>,
+}
+
+impl Query {
+    pub fn parse(raw: &str) -> Result<Self, CashError> {
+        let invalid = || Error::CommandParse(format!("syntax error in query '{}'", raw));
+        let chars: Vec<char> = raw.chars().collect();
+        let mut clauses = vec![];
+        let mut i = 0;
+
+        let until = |from: usize, stop: &dyn Fn(char) -> bool| {
+            chars[from..].iter().position(|&c| stop(c)).map_or(chars.len(), |p| p + from)
+        };
+
+        while i < chars.len() {
+            if chars[i].is_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            let negated = chars[i] == '-';
+            if negated {
+                i += 1;
+            }
+
+            if chars.get(i) != Some(&'@') {
+                let end = until(i, &|c: char| c.is_whitespace());
+                let word: String = chars[i..end].iter().collect();
+                let clause = match word.as_str() {
+                    "" => return Err(invalid()),
+                    "*" => Clause::All,
+                    _ => Clause::Text(None, vec![word.to_lowercase()]),
+                };
+                clauses.push((negated, clause));
+                i = end;
+                continue;
+            }
+
+            let colon = until(i, &|c: char| c == ':');
+            let field: String = chars[i + 1..colon].iter().collect();
+            if field.is_empty() || colon + 1 >= chars.len() {
+                return Err(invalid());
+            }
+            i = colon + 1;
+
+            let close = match chars[i] {
+                '{' => Some('}'),
+                '[' => Some(']'),
+                '(' => Some(')'),
+                _ => None,
+            };
+
+            let (inner, end) = match close {
+                Some(close) => {
+                    let end = until(i, &|c: char| c == close);
+                    if end == chars.len() {
+                        return Err(invalid());
+                    }
+                    (chars[i + 1..end].iter().collect::<String>(), end + 1)
+                }
+                None => {
+                    let end = until(i, &|c: char| c.is_whitespace());
+                    (chars[i..end].iter().collect::<String>(), end)
+                }
+            };
+
+            let words = |s: &str| s.split_whitespace().map(str::to_lowercase).collect::<Vec<_>>();
+            let clause = match chars[i] {
+                '{' => Clause::Tag(field, inner.split('|').map(|t| t.trim().to_lowercase()).collect()),
+                '[' => match inner.split_whitespace().collect::<Vec<_>>()[..] {
+                    [min, max] => Clause::Numeric(
+                        field,
+                        Bound::parse(min).ok_or_else(invalid)?,
+                        Bound::parse(max).ok_or_else(invalid)?,
+                    ),
+                    _ => return Err(invalid()),
+                },
+                _ => Clause::Text(Some(field), words(&inner)),
+            };
+            clauses.push((negated, clause));
+            i = end;
+        }
+
+        if clauses.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { raw: raw.to_string(), clauses })
+    }
+
+    pub fn clauses(&self) -> &[(bool, Clause)] {
+        &self.clauses
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+}
+
+///Команды вторичных индексов над JSON-документами
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchCmd {
+    Create(IndexDef),
+    Search(String, Query, SearchOptions),
+    DropIndex(String),
+}
+
+///Опции `FT.SEARCH`: только ключи (`NOCONTENT`) и страница `LIMIT offset num`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchOptions {
+    pub no_content: bool,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self { no_content: false, offset: 0, limit: 10 }
+    }
+}
+
+fn syntax_error() -> Error {
+    Error::CommandParse("syntax error".to_string())
+}
+
+///`FT.CREATE index [ON JSON] [PREFIX [count] prefix...] SCHEMA path [AS name] TAG [SEPARATOR c]|TEXT|NUMERIC ...`
+pub(crate) fn parse_create(parse: &mut Parse) -> Result<Command, CashError> {
+    let name = parse.next_string()?;
+    let mut prefixes = vec![];
+
+    loop {
+        match parse.next_string()?.to_lowercase().as_str() {
+            "on" => {
+                if parse.next_string()?.to_lowercase() != "json" {
+                    return Err(Error::CommandParse("only JSON indexes are supported".to_string()));
+                }
+            }
+            "prefix" => {
+                let first = parse.next_string()?;
+                match first.parse::<usize>() {
+                    Ok(count) => {
+                        for _ in 0..count {
+                            prefixes.push(parse.next_bytes()?);
+                        }
+                    }
+                    Err(_) => prefixes.push(Bytes::from(first)),
+                }
+            }
+            "schema" => break,
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    let mut fields = vec![];
+    while parse.remaining() > 0 {
+        let path = Path::parse(&parse.next_string()?)?;
+        let mut name = path.as_str().trim_start_matches('$').trim_start_matches('.').to_string();
+
+        let mut kind = parse.next_string()?.to_lowercase();
+        if kind == "as" {
+            name = parse.next_string()?;
+            kind = parse.next_string()?.to_lowercase();
+        }
+
+        let kind = match kind.as_str() {
+            "text" => FieldKind::Text,
+            "numeric" => FieldKind::Numeric,
+            "tag" => {
+                let mut separator = ',';
+                if parse.peek_string().is_some_and(|s| s.eq_ignore_ascii_case("separator")) {
+                    parse.next_string()?;
+                    separator = parse.next_string()?.chars().next().ok_or_else(syntax_error)?;
+                }
+                FieldKind::Tag(separator)
+            }
+            _ => return Err(Error::CommandParse(format!("unknown field type '{}'", kind))),
+        };
+
+        fields.push(Field { path, name, kind });
+    }
+
+    if fields.is_empty() {
+        return Err(Error::CommandParse("the schema has no fields".to_string()));
+    }
+
+    Ok(Command::Search(SearchCmd::Create(IndexDef { name, prefixes, fields })))
+}
+
+///`FT.SEARCH index query [NOCONTENT] [LIMIT offset num]`
+pub(crate) fn parse_search(parse: &mut Parse) -> Result<Command, CashError> {
+    let name = parse.next_string()?;
+    let query = Query::parse(&parse.next_string()?)?;
+    let mut options = SearchOptions::default();
+
+    while parse.remaining() > 0 {
+        match parse.next_string()?.to_lowercase().as_str() {
+            "nocontent" => options.no_content = true,
+            "limit" => {
+                options.offset = parse.next_int()? as usize;
+                options.limit = parse.next_int()? as usize;
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    Ok(Command::Search(SearchCmd::Search(name, query, options)))
+}
+
+pub(crate) fn parse_dropindex(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::Search(SearchCmd::DropIndex(parse.next_string()?)))
+}
+
+impl SearchCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchCmd::Create(_) => "ft.create",
+            SearchCmd::Search(..) => "ft.search",
+            SearchCmd::DropIndex(_) => "ft.dropindex",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec![Bytes::from(self.name())];
+
+        match self {
+            SearchCmd::Create(def) => {
+                args.extend([Bytes::from(def.name.clone()), Bytes::from("on"), Bytes::from("json")]);
+                if !def.prefixes.is_empty() {
+                    args.extend([Bytes::from("prefix"), Bytes::from(def.prefixes.len().to_string())]);
+                    args.extend(def.prefixes.iter().cloned());
+                }
+                args.push(Bytes::from("schema"));
+                for field in &def.fields {
+                    args.extend([
+                        Bytes::from(field.path.as_str().to_string()),
+                        Bytes::from("as"),
+                        Bytes::from(field.name.clone()),
+                    ]);
+                    match field.kind {
+                        FieldKind::Text => args.push(Bytes::from("text")),
+                        FieldKind::Numeric => args.push(Bytes::from("numeric")),
+                        FieldKind::Tag(separator) => {
+                            args.extend([Bytes::from("tag"), Bytes::from("separator"), Bytes::from(separator.to_string())]);
+                        }
+                    }
+                }
+            }
+            SearchCmd::Search(name, query, options) => {
+                args.extend([Bytes::from(name.clone()), Bytes::from(query.as_str().to_string())]);
+                if options.no_content {
+                    args.push(Bytes::from("nocontent"));
+                }
+                args.extend([
+                    Bytes::from("limit"),
+                    Bytes::from(options.offset.to_string()),
+                    Bytes::from(options.limit.to_string()),
+                ]);
+            }
+            SearchCmd::DropIndex(name) => args.push(Bytes::from(name.clone())),
+        }
+
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use super::*;
+
+    #[test]
+    fn parse_query() {
+        let query = Query::parse("@status:{new | In Progress} -@estimate:[(1 +inf] @name:(write doc*) urgent").unwrap();
+
+        assert_eq!(
+            &[
+                (false, Clause::Tag("status".to_string(), vec!["new".to_string(), "in progress".to_string()])),
+                (
+                    true,
+                    Clause::Numeric(
+                        "estimate".to_string(),
+                        Bound { value: 1.0, inclusive: false },
+                        Bound { value: f64::INFINITY, inclusive: true }
+                    )
+                ),
+                (false, Clause::Text(Some("name".to_string()), vec!["write".to_string(), "doc*".to_string()])),
+                (false, Clause::Text(None, vec!["urgent".to_string()])),
+            ],
+            query.clauses()
+        );
+
+        assert!(Query::parse("@status:{new").is_err());
+        assert!(Query::parse("@estimate:[1]").is_err());
+        assert!(Query::parse("  ").is_err());
+    }
+
+    #[test]
+    fn create_round_trip() {
+        let Ok(Command::Search(cmd)) =
+            Command::from_cmd("ft.create idx on json prefix todo: schema $.status tag $.name as title text".to_string())
+        else {
+            panic!()
+        };
+
+        let SearchCmd::Create(def) = &cmd else { panic!() };
+        assert_eq!(vec![Bytes::from("todo:")], def.prefixes);
+        assert_eq!("status", def.fields[0].name);
+        assert_eq!(FieldKind::Tag(','), def.fields[0].kind);
+        assert_eq!("title", def.fields[1].name);
+
+        assert!(matches!(Command::from_frame(cmd.frame()), Ok(Command::Search(parsed)) if parsed == cmd));
+    }
+}
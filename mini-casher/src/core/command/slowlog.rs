@@ -0,0 +1,44 @@
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::CashError;
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Подкоманды `SLOWLOG`.
+///`Get(None)` возвращает 10 последних записей, отрицательное значение - все записи
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlowlogCmd {
+    Get(Option<i64>),
+    Len,
+    Reset,
+}
+
+pub(crate) fn parse_get(parse: &mut Parse) -> Result<Command, CashError> {
+    let count = if parse.remaining() > 0 { Some(parse.next_signed()?) } else { None };
+    Ok(Command::Slowlog(SlowlogCmd::Get(count)))
+}
+
+impl SlowlogCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SlowlogCmd::Get(_) => "slowlog|get",
+            SlowlogCmd::Len => "slowlog|len",
+            SlowlogCmd::Reset => "slowlog|reset",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec!["slowlog".to_string()];
+
+        match self {
+            SlowlogCmd::Get(count) => {
+                args.push("get".to_string());
+                args.extend(count.map(|c| c.to_string()));
+            }
+            SlowlogCmd::Len => args.push("len".to_string()),
+            SlowlogCmd::Reset => args.push("reset".to_string()),
+        }
+
+        Frame::Array(args.into_iter().map(|a| Frame::BulkString(Bytes::from(a))).collect())
+    }
+}
@@ -0,0 +1,290 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use bytes::Bytes;
+use crate::core::command::{Command, Set};
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Срок жизни ключа в `SET` и `GETEX`.
+///`Ex`, `Px` - через секунды и миллисекунды, `ExAt`, `PxAt` - unix-время,
+///`KeepTtl` - оставить текущий срок (`SET`), `Persist` - снять срок (`GETEX`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expiry {
+    Ex(u64),
+    Px(u64),
+    ExAt(u64),
+    PxAt(u64),
+    KeepTtl,
+    Persist,
+}
+
+fn unix_now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+impl Expiry {
+    ///Момент истечения. Время в прошлом дает ключ, который уже истек.
+    ///`None` - у ключа не будет срока жизни. Сроки проверяются `in_range` при разборе,
+    ///а срок, который всё же не помещается в `Instant`, тоже дает `None`
+    pub fn deadline(&self) -> Option<Instant> {
+        let now = Instant::now();
+
+        let after = match *self {
+            Expiry::Ex(secs) => Duration::from_secs(secs),
+            Expiry::Px(millis) => Duration::from_millis(millis),
+            Expiry::ExAt(secs) => Duration::from_millis(secs.saturating_mul(1000).saturating_sub(unix_now_ms())),
+            Expiry::PxAt(millis) => Duration::from_millis(millis.saturating_sub(unix_now_ms())),
+            Expiry::KeepTtl | Expiry::Persist => return None,
+        };
+        now.checked_add(after)
+    }
+
+    ///Момент истечения в unix-времени в миллисекундах помещается в `i64`, как требует redis
+    pub(crate) fn in_range(&self) -> bool {
+        let now = unix_now_ms();
+        let at = match *self {
+            Expiry::Ex(secs) => secs.checked_mul(1000).and_then(|millis| millis.checked_add(now)),
+            Expiry::Px(millis) => millis.checked_add(now),
+            Expiry::ExAt(secs) => secs.checked_mul(1000),
+            Expiry::PxAt(millis) => Some(millis),
+            Expiry::KeepTtl | Expiry::Persist => Some(0),
+        };
+        at.is_some_and(|at| at <= i64::MAX as u64)
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self {
+            Expiry::Ex(secs) => vec!["ex".to_string(), secs.to_string()],
+            Expiry::Px(millis) => vec!["px".to_string(), millis.to_string()],
+            Expiry::ExAt(secs) => vec!["exat".to_string(), secs.to_string()],
+            Expiry::PxAt(millis) => vec!["pxat".to_string(), millis.to_string()],
+            Expiry::KeepTtl => vec!["keepttl".to_string()],
+            Expiry::Persist => vec!["persist".to_string()],
+        }
+    }
+}
+
+///Условие записи в `SET`: `NX` - только если ключа нет, `XX` - только если есть
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    Nx,
+    Xx,
+}
+
+///Команды над строковыми значениями
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringCmd {
+    Append(Bytes, Bytes),
+    StrLen(Bytes),
+    GetRange(Bytes, i64, i64),
+    SetRange(Bytes, u64, Bytes),
+    GetDel(Bytes),
+    GetEx(Bytes, Option<Expiry>),
+    GetSet(Bytes, Bytes),
+    SetNx(Bytes, Bytes),
+}
+
+///Разбирает `EX`, `PX`, `EXAT`, `PXAT` и вариант без значения (`KEEPTTL` или `PERSIST`).
+///`None` - опция не относится к сроку жизни
+fn parse_expiry(option: &str, parse: &mut Parse, command: &str) -> Result<Option<Expiry>, CashError> {
+    let constructor: fn(u64) -> Expiry = match option {
+        "ex" => Expiry::Ex,
+        "px" => Expiry::Px,
+        "exat" => Expiry::ExAt,
+        "pxat" => Expiry::PxAt,
+        "keepttl" if command == "set" => return Ok(Some(Expiry::KeepTtl)),
+        "persist" if command == "getex" => return Ok(Some(Expiry::Persist)),
+        _ => return Ok(None),
+    };
+
+    let invalid = || Error::CommandParse(format!("invalid expire time in '{}' command", command));
+    let value = parse.next_signed().map_err(|_| invalid())?;
+    if value <= 0 {
+        return Err(invalid());
+    }
+
+    let expiry = constructor(value as u64);
+    match expiry.in_range() {
+        true => Ok(Some(expiry)),
+        false => Err(invalid()),
+    }
+}
+
+fn syntax_error() -> Error {
+    Error::CommandParse("syntax error".to_string())
+}
+
+///Опции `SET key value [NX|XX] [GET] [EX s|PX ms|EXAT ts|PXAT ts|KEEPTTL]`
+pub(crate) fn parse_set_options(parse: &mut Parse, set: &mut Set) -> Result<(), CashError> {
+    while parse.remaining() > 0 {
+        let option = parse.next_string()?.to_lowercase();
+
+        match option.as_str() {
+            "nx" | "xx" if set.condition.is_none() => {
+                set.condition = Some(if option == "nx" { Condition::Nx } else { Condition::Xx });
+            }
+            "get" if !set.get => set.get = true,
+            _ => match parse_expiry(&option, parse, "set")? {
+                Some(expiry) if set.expiry.is_none() => set.expiry = Some(expiry),
+                _ => return Err(syntax_error()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn parse_append(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::String(StringCmd::Append(parse.next_bytes()?, parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_strlen(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::String(StringCmd::StrLen(parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_getrange(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    Ok(Command::String(StringCmd::GetRange(key, parse.next_signed()?, parse.next_signed()?)))
+}
+
+pub(crate) fn parse_setrange(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let offset = parse.next_signed()?;
+    if offset < 0 {
+        return Err(Error::CommandParse("offset is out of range".to_string()));
+    }
+    Ok(Command::String(StringCmd::SetRange(key, offset as u64, parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_getdel(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::String(StringCmd::GetDel(parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_getex(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let mut expiry = None;
+
+    while parse.remaining() > 0 {
+        let option = parse.next_string()?.to_lowercase();
+        match parse_expiry(&option, parse, "getex")? {
+            Some(value) if expiry.is_none() => expiry = Some(value),
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    Ok(Command::String(StringCmd::GetEx(key, expiry)))
+}
+
+pub(crate) fn parse_getset(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::String(StringCmd::GetSet(parse.next_bytes()?, parse.next_bytes()?)))
+}
+
+pub(crate) fn parse_setnx(parse: &mut Parse) -> Result<Command, CashError> {
+    Ok(Command::String(StringCmd::SetNx(parse.next_bytes()?, parse.next_bytes()?)))
+}
+
+impl Set {
+    ///Аргументы `SET` после значения
+    pub(crate) fn option_args(&self) -> Vec<String> {
+        let mut args = vec![];
+
+        match self.condition {
+            Some(Condition::Nx) => args.push("nx".to_string()),
+            Some(Condition::Xx) => args.push("xx".to_string()),
+            None => {}
+        }
+        if self.get {
+            args.push("get".to_string());
+        }
+        if let Some(expiry) = &self.expiry {
+            args.extend(expiry.args());
+        }
+
+        args
+    }
+}
+
+impl StringCmd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StringCmd::Append(..) => "append",
+            StringCmd::StrLen(_) => "strlen",
+            StringCmd::GetRange(..) => "getrange",
+            StringCmd::SetRange(..) => "setrange",
+            StringCmd::GetDel(_) => "getdel",
+            StringCmd::GetEx(..) => "getex",
+            StringCmd::GetSet(..) => "getset",
+            StringCmd::SetNx(..) => "setnx",
+        }
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut args = vec![Bytes::from(self.name())];
+
+        match self {
+            StringCmd::Append(key, value) | StringCmd::GetSet(key, value) | StringCmd::SetNx(key, value) => {
+                args.extend([key.clone(), value.clone()]);
+            }
+            StringCmd::StrLen(key) | StringCmd::GetDel(key) => args.push(key.clone()),
+            StringCmd::GetRange(key, start, end) => {
+                args.extend([key.clone(), Bytes::from(start.to_string()), Bytes::from(end.to_string())]);
+            }
+            StringCmd::SetRange(key, offset, value) => {
+                args.extend([key.clone(), Bytes::from(offset.to_string()), value.clone()]);
+            }
+            StringCmd::GetEx(key, expiry) => {
+                args.push(key.clone());
+                args.extend(expiry.iter().flat_map(|e| e.args()).map(Bytes::from));
+            }
+        }
+
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
+
+#[cfg(test)]
+mod string_tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Command, CashError> {
+        Command::from_cmd(input.to_string())
+    }
+
+    #[test]
+    fn parse_set_options() {
+        let Ok(Command::Set(set)) = parse("set k v nx get px 500") else { panic!() };
+        assert_eq!(Some(Condition::Nx), set.condition());
+        assert!(set.get());
+        assert_eq!(Some(Expiry::Px(500)), set.expiry());
+
+        assert!(parse("set k v nx xx").is_err());
+        assert!(parse("set k v ex 0").is_err());
+        assert!(parse("set k v persist").is_err());
+    }
+
+    #[test]
+    fn huge_expiry_is_rejected() {
+        let max = i64::MAX;
+        for option in ["ex", "px", "exat"] {
+            assert!(parse(&format!("set k v {} {}", option, max)).is_err());
+            assert!(parse(&format!("getex k {} {}", option, max)).is_err());
+        }
+        assert!(parse(&format!("set k v pxat {}", max)).is_ok());
+        assert!(parse(&format!("set k v px {}", max / 2)).is_ok());
+        assert_eq!(None, Expiry::Ex(u64::MAX).deadline());
+    }
+
+    #[test]
+    fn parse_getex_options() {
+        assert!(matches!(parse("getex k persist"), Ok(Command::String(StringCmd::GetEx(_, Some(Expiry::Persist))))));
+        assert!(matches!(parse("getex k"), Ok(Command::String(StringCmd::GetEx(_, None)))));
+        assert!(parse("getex k keepttl").is_err());
+        assert!(parse("setrange k -1 v").is_err());
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let cmd = StringCmd::GetEx(Bytes::from("k"), Some(Expiry::ExAt(1_700_000_000)));
+        assert!(matches!(Command::from_frame(cmd.frame()), Ok(Command::String(parsed)) if parsed == cmd));
+    }
+}
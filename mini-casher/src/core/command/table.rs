@@ -0,0 +1,753 @@
+use bytes::Bytes;
+use crate::core::command::{acl, bitmap, client, config, filter, hyperloglog, introspection, json, keyspace, lock, persistence, search, slowlog, string, throttle, Command};
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///Разбирает аргументы команды после её имени (и имени подкоманды)
+pub type ParseFn = fn(&mut Parse) -> Result<Command, CashError>;
+
+///Флаги команды, которые показываются в `COMMAND INFO`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flag {
+    Readonly,
+    Write,
+    Blocking,
+    Admin,
+    NoAuth,
+    Fast,
+    MovableKeys,
+}
+
+impl Flag {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Flag::Readonly => "readonly",
+            Flag::Write => "write",
+            Flag::Blocking => "blocking",
+            Flag::Admin => "admin",
+            Flag::NoAuth => "no_auth",
+            Flag::Fast => "fast",
+            Flag::MovableKeys => "movablekeys",
+        }
+    }
+}
+
+///Описание команды.
+///`arity` считается как в redis: положительное значение - точное количество аргументов
+///вместе с именем команды, отрицательное - минимальное.
+///`first_key`, `last_key`, `step` - позиции ключей в аргументах, `last_key = -1` - до конца.
+///У команд-контейнеров (`client`, `acl`) аргументы разбирают подкоманды
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub arity: i64,
+    pub flags: &'static [Flag],
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+    pub categories: &'static [&'static str],
+    pub summary: &'static str,
+    pub arguments: &'static str,
+    pub parse: Option<ParseFn>,
+    pub subcommands: &'static [CommandSpec],
+    pub sensitive: bool,
+}
+
+impl CommandSpec {
+    const fn new(name: &'static str, arity: i64, summary: &'static str) -> Self {
+        Self {
+            name,
+            arity,
+            flags: &[],
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            categories: &[],
+            summary,
+            arguments: "",
+            parse: None,
+            subcommands: &[],
+            sensitive: false,
+        }
+    }
+
+    const fn flags(mut self, flags: &'static [Flag]) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    const fn keys(mut self, first_key: i64, last_key: i64, step: i64) -> Self {
+        self.first_key = first_key;
+        self.last_key = last_key;
+        self.step = step;
+        self
+    }
+
+    const fn categories(mut self, categories: &'static [&'static str]) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    const fn arguments(mut self, arguments: &'static str) -> Self {
+        self.arguments = arguments;
+        self
+    }
+
+    const fn parse(mut self, parse: ParseFn) -> Self {
+        self.parse = Some(parse);
+        self
+    }
+
+    const fn subcommands(mut self, subcommands: &'static [CommandSpec]) -> Self {
+        self.subcommands = subcommands;
+        self
+    }
+
+    ///Аргументы команды содержат пароли и не попадают в журналы
+    const fn sensitive(mut self) -> Self {
+        self.sensitive = true;
+        self
+    }
+
+    pub fn has_flag(&self, flag: Flag) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    ///Подкоманда по имени без префикса, например `kill` для `client`
+    pub fn subcommand(&self, name: &str) -> Option<&'static CommandSpec> {
+        self.subcommands
+            .iter()
+            .find(|s| s.name.split_once('|').is_some_and(|(_, sub)| sub == name))
+    }
+
+    ///Проверяет количество аргументов вместе с именем команды
+    pub fn check_arity(&self, argc: usize) -> Result<(), CashError> {
+        let argc = argc as i64;
+        let valid = if self.arity >= 0 { argc == self.arity } else { argc >= -self.arity };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::CommandParse(format!("wrong number of arguments for '{}' command", self.name)))
+        }
+    }
+
+    ///Ключи команды по позициям `first_key`, `last_key`, `step`.
+    ///У команд с `movablekeys` ключи могут идти списком после аргумента `KEYS`.
+    ///`args` - все аргументы, включая имя команды
+    pub fn key_args<'a>(&self, args: &'a [Bytes]) -> Vec<&'a Bytes> {
+        if self.has_flag(Flag::MovableKeys) {
+            let after = (self.last_key.max(0) as usize + 1).min(args.len());
+            if let Some(at) = args[after..].iter().position(|arg| arg.eq_ignore_ascii_case(b"keys")) {
+                return args[after + at + 1..].iter().collect();
+            }
+        }
+
+        if self.first_key <= 0 || self.step <= 0 {
+            return vec![];
+        }
+
+        let last = if self.last_key < 0 {
+            args.len() as i64 + self.last_key
+        } else {
+            self.last_key.min(args.len() as i64 - 1)
+        };
+
+        (self.first_key..=last)
+            .step_by(self.step as usize)
+            .filter_map(|i| args.get(i as usize))
+            .collect()
+    }
+
+    ///Аргументы для журналов: у команд с паролями остается только имя команды
+    pub fn loggable_args(&self, args: &[Bytes]) -> Vec<Bytes> {
+        if !self.sensitive {
+            return args.to_vec();
+        }
+
+        let words = self.name.split('|').count();
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| if i < words { arg.clone() } else { Bytes::from("(redacted)") })
+            .collect()
+    }
+
+    ///Строка использования для подсказки консоли, например `get key`
+    pub fn usage(&self) -> String {
+        let name = self.name.replace('|', " ");
+        if self.arguments.is_empty() {
+            name
+        } else {
+            format!("{} {}", name, self.arguments)
+        }
+    }
+
+    ///Ответ `COMMAND INFO`:
+    ///имя, arity, флаги, позиции ключей, категории ACL и подкоманды
+    pub fn info_frame(&self) -> Frame {
+        Frame::Array(vec![
+            bulk(self.name),
+            Frame::Integer(self.arity),
+            Frame::Array(self.flags.iter().map(|f| Frame::Simple(f.name().to_string())).collect()),
+            Frame::Integer(self.first_key),
+            Frame::Integer(self.last_key),
+            Frame::Integer(self.step),
+            Frame::Array(self.categories.iter().map(|c| Frame::Simple(format!("@{}", c))).collect()),
+            Frame::Array(self.subcommands.iter().map(|s| s.info_frame()).collect()),
+        ])
+    }
+
+    ///Ответ `COMMAND DOCS` для одной команды: пары ключ-значение
+    pub fn docs_frame(&self) -> Frame {
+        let mut docs = vec![
+            bulk("summary"),
+            bulk(self.summary),
+            bulk("arguments"),
+            bulk(self.arguments),
+        ];
+
+        if !self.subcommands.is_empty() {
+            let mut subcommands = vec![];
+            for sub in self.subcommands {
+                subcommands.push(bulk(sub.name));
+                subcommands.push(sub.docs_frame());
+            }
+            docs.push(bulk("subcommands"));
+            docs.push(Frame::Array(subcommands));
+        }
+
+        Frame::Array(docs)
+    }
+}
+
+///Таблица всех команд сервера
+pub static COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("get", 2, "get value by key")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "string"])
+        .arguments("key")
+        .parse(Command::parse_get),
+    CommandSpec::new("set", -3, "set a new value")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "string"])
+        .arguments("key value [nx|xx] [get] [ex seconds|px ms|exat unix-s|pxat unix-ms|keepttl]")
+        .parse(Command::parse_set),
+    CommandSpec::new("delete", 2, "delete by key")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "keyspace"])
+        .arguments("key")
+        .parse(Command::parse_delete),
+    CommandSpec::new("append", 3, "append to the value, returns the new length")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "string"])
+        .arguments("key value")
+        .parse(string::parse_append),
+    CommandSpec::new("strlen", 2, "length of the value")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "string"])
+        .arguments("key")
+        .parse(string::parse_strlen),
+    CommandSpec::new("getrange", 4, "substring of the value, negative offsets count from the end")
+        .flags(&[Flag::Readonly])
+        .keys(1, 1, 1)
+        .categories(&["read", "string"])
+        .arguments("key start end")
+        .parse(string::parse_getrange),
+    CommandSpec::new("setrange", 4, "overwrite part of the value, padding with zero bytes")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "string"])
+        .arguments("key offset value")
+        .parse(string::parse_setrange),
+    CommandSpec::new("getdel", 2, "get the value and delete the key")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "string"])
+        .arguments("key")
+        .parse(string::parse_getdel),
+    CommandSpec::new("getex", -2, "get the value and change its expiration")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "string"])
+        .arguments("key [ex seconds|px ms|exat unix-s|pxat unix-ms|persist]")
+        .parse(string::parse_getex),
+    CommandSpec::new("getset", 3, "set a new value and return the old one")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "string"])
+        .arguments("key value")
+        .parse(string::parse_getset),
+    CommandSpec::new("setnx", 3, "set the value if the key doesn't exist")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "string"])
+        .arguments("key value")
+        .parse(string::parse_setnx),
+    CommandSpec::new("setbit", 4, "set or clear a bit, returns the old bit")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "bitmap"])
+        .arguments("key offset 0|1")
+        .parse(bitmap::parse_setbit),
+    CommandSpec::new("getbit", 3, "bit at the offset")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "bitmap"])
+        .arguments("key offset")
+        .parse(bitmap::parse_getbit),
+    CommandSpec::new("bitcount", -2, "number of set bits")
+        .flags(&[Flag::Readonly])
+        .keys(1, 1, 1)
+        .categories(&["read", "bitmap"])
+        .arguments("key [start end [byte|bit]]")
+        .parse(bitmap::parse_bitcount),
+    CommandSpec::new("bitpos", -3, "position of the first set or clear bit")
+        .flags(&[Flag::Readonly])
+        .keys(1, 1, 1)
+        .categories(&["read", "bitmap"])
+        .arguments("key 0|1 [start [end [byte|bit]]]")
+        .parse(bitmap::parse_bitpos),
+    CommandSpec::new("bitop", -4, "bitwise operation between keys, stored in destkey")
+        .flags(&[Flag::Write])
+        .keys(2, -1, 1)
+        .categories(&["write", "bitmap"])
+        .arguments("and|or|xor|not destkey key [key ...]")
+        .parse(bitmap::parse_bitop),
+    CommandSpec::new("bitfield", -2, "get, set and increment packed integer fields")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "bitmap"])
+        .arguments("key [get type offset] [set type offset value] [incrby type offset increment] [overflow wrap|sat|fail]")
+        .parse(bitmap::parse_bitfield),
+    CommandSpec::new("pfadd", -2, "add elements to a HyperLogLog")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "hyperloglog"])
+        .arguments("key [element ...]")
+        .parse(hyperloglog::parse_pfadd),
+    CommandSpec::new("pfcount", -2, "approximate number of unique elements, union of several keys")
+        .flags(&[Flag::Readonly])
+        .keys(1, -1, 1)
+        .categories(&["read", "hyperloglog"])
+        .arguments("key [key ...]")
+        .parse(hyperloglog::parse_pfcount),
+    CommandSpec::new("pfmerge", -2, "merge HyperLogLogs into destkey")
+        .flags(&[Flag::Write])
+        .keys(1, -1, 1)
+        .categories(&["write", "hyperloglog"])
+        .arguments("destkey [sourcekey ...]")
+        .parse(hyperloglog::parse_pfmerge),
+    CommandSpec::new("json.set", -4, "set the JSON value at path, creating documents at the root")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "json"])
+        .arguments("key path value [nx|xx]")
+        .parse(json::parse_set),
+    CommandSpec::new("json.get", -2, "JSON values at the given paths")
+        .flags(&[Flag::Readonly])
+        .keys(1, 1, 1)
+        .categories(&["read", "json"])
+        .arguments("key [path ...]")
+        .parse(json::parse_get),
+    CommandSpec::new("json.del", -2, "delete the values at path")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "json"])
+        .arguments("key [path]")
+        .parse(json::parse_del),
+    CommandSpec::new("json.getdel", 2, "get the whole JSON document and delete the key")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "json"])
+        .arguments("key")
+        .parse(json::parse_getdel),
+    CommandSpec::new("json.arrappend", -4, "append values to the arrays at path")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "json"])
+        .arguments("key path value [value ...]")
+        .parse(json::parse_arrappend),
+    CommandSpec::new("json.numincrby", 4, "increment the numbers at path")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "json"])
+        .arguments("key path number")
+        .parse(json::parse_numincrby),
+    CommandSpec::new("json.type", -2, "type of the values at path")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "json"])
+        .arguments("key [path]")
+        .parse(json::parse_type),
+    CommandSpec::new("bf.reserve", -4, "create an empty Bloom filter")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "bloom"])
+        .arguments("key error_rate capacity [expansion n] [nonscaling]")
+        .parse(filter::parse_bf_reserve),
+    CommandSpec::new("bf.add", 3, "add an item to a Bloom filter, creating it if needed")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "bloom"])
+        .arguments("key item")
+        .parse(filter::parse_bf_add),
+    CommandSpec::new("bf.madd", -3, "add items to a Bloom filter, creating it if needed")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "bloom"])
+        .arguments("key item [item ...]")
+        .parse(filter::parse_bf_madd),
+    CommandSpec::new("bf.exists", 3, "whether an item may have been added to a Bloom filter")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "bloom"])
+        .arguments("key item")
+        .parse(filter::parse_bf_exists),
+    CommandSpec::new("bf.mexists", -3, "whether items may have been added to a Bloom filter")
+        .flags(&[Flag::Readonly])
+        .keys(1, 1, 1)
+        .categories(&["read", "bloom"])
+        .arguments("key item [item ...]")
+        .parse(filter::parse_bf_mexists),
+    CommandSpec::new("cf.reserve", -3, "create an empty cuckoo filter")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "cuckoo"])
+        .arguments("key capacity [bucketsize n] [maxiterations n] [expansion n]")
+        .parse(filter::parse_cf_reserve),
+    CommandSpec::new("cf.add", 3, "add an item to a cuckoo filter, creating it if needed")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "cuckoo"])
+        .arguments("key item")
+        .parse(filter::parse_cf_add),
+    CommandSpec::new("cf.addnx", 3, "add an item to a cuckoo filter if it is not there yet")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "cuckoo"])
+        .arguments("key item")
+        .parse(filter::parse_cf_addnx),
+    CommandSpec::new("cf.exists", 3, "whether an item may be in a cuckoo filter")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "cuckoo"])
+        .arguments("key item")
+        .parse(filter::parse_cf_exists),
+    CommandSpec::new("cf.del", 3, "delete one occurrence of an item from a cuckoo filter")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "cuckoo"])
+        .arguments("key item")
+        .parse(filter::parse_cf_del),
+    CommandSpec::new("ft.create", -5, "create a secondary index over JSON documents with a key prefix")
+        .flags(&[Flag::Write])
+        .categories(&["write", "search"])
+        .arguments("index [on json] [prefix count prefix ...] schema path [as name] tag [separator c]|text|numeric ...")
+        .parse(search::parse_create),
+    CommandSpec::new("ft.search", -3, "keys and documents matching a query, by pages")
+        .flags(&[Flag::Readonly])
+        .categories(&["read", "search"])
+        .arguments("index query [nocontent] [limit offset num]")
+        .parse(search::parse_search),
+    CommandSpec::new("ft.dropindex", 2, "delete an index, documents are kept")
+        .flags(&[Flag::Write])
+        .categories(&["write", "search"])
+        .arguments("index")
+        .parse(search::parse_dropindex),
+    CommandSpec::new("lock.acquire", -3, "take a lock for ttl milliseconds, returns a fencing token")
+        .flags(&[Flag::Write, Flag::Blocking])
+        .keys(1, 1, 1)
+        .categories(&["write", "lock"])
+        .arguments("name ttl [wait timeout]")
+        .parse(lock::parse_acquire),
+    CommandSpec::new("lock.release", 3, "release a lock held with the token")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "lock"])
+        .arguments("name token")
+        .parse(lock::parse_release),
+    CommandSpec::new("lock.extend", 4, "prolong a lock held with the token by ttl milliseconds")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "lock"])
+        .arguments("name token ttl")
+        .parse(lock::parse_extend),
+    CommandSpec::new("throttle", -5, "rate limit with the generic cell rate algorithm")
+        .flags(&[Flag::Write, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["write", "throttle"])
+        .arguments("key max_burst count_per_period period [quantity]")
+        .parse(throttle::parse_throttle),
+    CommandSpec::new("scan", -2, "iterate over keys, returns the next cursor and a batch of keys")
+        .flags(&[Flag::Readonly])
+        .categories(&["read", "keyspace"])
+        .arguments("cursor [match pattern] [count count] [type type]")
+        .parse(keyspace::parse_scan),
+    CommandSpec::new("type", 2, "type of the value, none if the key doesn't exist")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "keyspace"])
+        .arguments("key")
+        .parse(keyspace::parse_type),
+    CommandSpec::new("ttl", 2, "seconds to live, -1 without expiry, -2 if the key doesn't exist")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "keyspace"])
+        .arguments("key")
+        .parse(keyspace::parse_ttl),
+    CommandSpec::new("pttl", 2, "milliseconds to live, -1 without expiry, -2 if the key doesn't exist")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .keys(1, 1, 1)
+        .categories(&["read", "keyspace"])
+        .arguments("key")
+        .parse(keyspace::parse_pttl),
+    CommandSpec::new("dump", 2, "serialize the value of a key with its type, format version and checksum")
+        .flags(&[Flag::Readonly])
+        .keys(1, 1, 1)
+        .categories(&["read", "keyspace"])
+        .arguments("key")
+        .parse(keyspace::parse_dump),
+    CommandSpec::new("restore", -4, "create a key from a DUMP payload, ttl in milliseconds, 0 for none")
+        .flags(&[Flag::Write])
+        .keys(1, 1, 1)
+        .categories(&["write", "keyspace", "dangerous"])
+        .arguments("key ttl payload [replace] [absttl]")
+        .parse(keyspace::parse_restore),
+    CommandSpec::new("migrate", -6, "move keys to another server with DUMP and RESTORE")
+        .flags(&[Flag::Write, Flag::MovableKeys])
+        .keys(3, 3, 1)
+        .categories(&["write", "keyspace", "dangerous"])
+        .arguments("host port key|\"\" db timeout [copy] [replace] [auth password|auth2 user password] [keys key ...]")
+        .parse(keyspace::parse_migrate)
+        .sensitive(),
+    CommandSpec::new("len", 1, "map length")
+        .flags(&[Flag::Readonly, Flag::Fast])
+        .categories(&["read", "keyspace"])
+        .parse(|_| Ok(Command::Len)),
+    CommandSpec::new("all", 1, "load all entity")
+        .flags(&[Flag::Readonly])
+        .categories(&["read", "keyspace"])
+        .parse(|_| Ok(Command::All)),
+    CommandSpec::new("ping", 1, "check the connection")
+        .flags(&[Flag::Fast])
+        .categories(&["connection"])
+        .parse(|_| Ok(Command::Ping)),
+    CommandSpec::new("auth", -2, "authenticate the connection")
+        .flags(&[Flag::NoAuth, Flag::Fast])
+        .categories(&["connection"])
+        .arguments("[user] password")
+        .parse(acl::parse_auth)
+        .sensitive(),
+    CommandSpec::new("client", -2, "connection management")
+        .categories(&["connection"])
+        .subcommands(&[
+            CommandSpec::new("client|setname", 3, "name the current connection")
+                .categories(&["connection"])
+                .arguments("name")
+                .parse(client::parse_setname),
+            CommandSpec::new("client|getname", 2, "name of the current connection")
+                .categories(&["connection"])
+                .parse(|_| Ok(Command::Client(client::ClientCmd::GetName))),
+            CommandSpec::new("client|id", 2, "id of the current connection")
+                .flags(&[Flag::Fast])
+                .categories(&["connection"])
+                .parse(|_| Ok(Command::Client(client::ClientCmd::Id))),
+            CommandSpec::new("client|list", 2, "list connected clients")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "connection", "dangerous"])
+                .parse(|_| Ok(Command::Client(client::ClientCmd::List))),
+            CommandSpec::new("client|kill", -3, "close connections")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "connection", "dangerous"])
+                .arguments("addr | [id id] [addr ip:port] [user user]")
+                .parse(client::parse_kill),
+            CommandSpec::new("client|pause", -3, "suspend command processing")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "connection", "dangerous"])
+                .arguments("timeout [write|all]")
+                .parse(client::parse_pause),
+            CommandSpec::new("client|unpause", 2, "resume command processing")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "connection", "dangerous"])
+                .parse(|_| Ok(Command::Client(client::ClientCmd::Unpause))),
+        ]),
+    CommandSpec::new("acl", -2, "users and permissions")
+        .subcommands(&[
+            CommandSpec::new("acl|setuser", -3, "create or modify a user")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .arguments("user [rule ...]")
+                .parse(acl::parse_setuser)
+                .sensitive(),
+            CommandSpec::new("acl|list", 2, "list users and their rules")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .parse(|_| Ok(Command::Acl(acl::AclCmd::List))),
+            CommandSpec::new("acl|whoami", 2, "user of the current connection")
+                .categories(&["connection"])
+                .parse(|_| Ok(Command::Acl(acl::AclCmd::WhoAmI))),
+            CommandSpec::new("acl|deluser", -3, "remove users")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .arguments("user [user ...]")
+                .parse(acl::parse_deluser),
+        ]),
+    CommandSpec::new("slowlog", -2, "commands slower than the threshold")
+        .subcommands(&[
+            CommandSpec::new("slowlog|get", -2, "latest slow commands")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .arguments("[count]")
+                .parse(slowlog::parse_get),
+            CommandSpec::new("slowlog|len", 2, "number of slow commands")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .parse(|_| Ok(Command::Slowlog(slowlog::SlowlogCmd::Len))),
+            CommandSpec::new("slowlog|reset", 2, "clear the slow log")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .parse(|_| Ok(Command::Slowlog(slowlog::SlowlogCmd::Reset))),
+        ]),
+    CommandSpec::new("config", -2, "runtime configuration")
+        .subcommands(&[
+            CommandSpec::new("config|get", -3, "values of matching parameters")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .arguments("pattern [pattern ...]")
+                .parse(config::parse_get),
+            CommandSpec::new("config|set", -4, "change parameters at runtime")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .arguments("parameter value [parameter value ...]")
+                .parse(config::parse_set)
+                .sensitive(),
+            CommandSpec::new("config|rewrite", 2, "write the running configuration to the config file")
+                .flags(&[Flag::Admin])
+                .categories(&["admin", "dangerous"])
+                .parse(|_| Ok(Command::Config(config::ConfigCmd::Rewrite))),
+        ]),
+    CommandSpec::new("save", 1, "write a snapshot of the data to disk, blocking the server")
+        .flags(&[Flag::Admin])
+        .categories(&["admin", "dangerous"])
+        .parse(|_| Ok(Command::Persistence(persistence::PersistenceCmd::Save))),
+    CommandSpec::new("bgsave", 1, "write a snapshot of the data to disk in the background")
+        .flags(&[Flag::Admin])
+        .categories(&["admin", "dangerous"])
+        .parse(|_| Ok(Command::Persistence(persistence::PersistenceCmd::BgSave))),
+    CommandSpec::new("lastsave", 1, "unix time of the last successful snapshot")
+        .flags(&[Flag::Fast])
+        .categories(&["admin", "dangerous"])
+        .parse(|_| Ok(Command::Persistence(persistence::PersistenceCmd::LastSave))),
+    CommandSpec::new("bgrewriteaof", 1, "compact the append-only file in the background")
+        .flags(&[Flag::Admin])
+        .categories(&["admin", "dangerous"])
+        .parse(|_| Ok(Command::Persistence(persistence::PersistenceCmd::BgRewriteAof))),
+    CommandSpec::new("monitor", 1, "stream every command processed by the server")
+        .flags(&[Flag::Admin])
+        .categories(&["admin", "dangerous"])
+        .parse(|_| Ok(Command::Monitor)),
+    CommandSpec::new("command", -1, "describe server commands")
+        .categories(&["connection"])
+        .parse(|_| Ok(Command::Command(introspection::CommandCmd::List)))
+        .subcommands(&[
+            CommandSpec::new("command|count", 2, "number of commands")
+                .categories(&["connection"])
+                .parse(|_| Ok(Command::Command(introspection::CommandCmd::Count))),
+            CommandSpec::new("command|info", -2, "details about commands")
+                .categories(&["connection"])
+                .arguments("[command ...]")
+                .parse(introspection::parse_info),
+            CommandSpec::new("command|docs", -2, "documentation of commands")
+                .categories(&["connection"])
+                .arguments("[command ...]")
+                .parse(introspection::parse_docs),
+        ]),
+];
+
+///Команда верхнего уровня по имени
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+///Команда или подкоманда по полному имени, например `client|kill`
+pub fn lookup_full(name: &str) -> Option<&'static CommandSpec> {
+    match name.split_once('|') {
+        Some((container, sub)) => lookup(container)?.subcommand(sub),
+        None => lookup(name),
+    }
+}
+
+///Все исполняемые команды и подкоманды.
+///Используется ACL для правил `+@category` и `+command`
+pub fn executable() -> impl Iterator<Item = &'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .flat_map(|c| std::iter::once(c).chain(c.subcommands.iter()))
+        .filter(|c| c.parse.is_some())
+}
+
+///Подсказка консоли со списком команд
+pub fn help() -> String {
+    let mut help = String::from("Enter the command:\r\n");
+    for spec in executable() {
+        help.push_str(&format!("- {} - `{}`\r\n", spec.summary, spec.usage()));
+    }
+    help
+}
+
+fn bulk(value: &str) -> Frame {
+    Frame::BulkString(Bytes::from(value.to_string()))
+}
+
+#[cfg(test)]
+mod table_tests {
+    use super::*;
+
+    #[test]
+    fn key_args_positions() {
+        let spec = lookup("set").unwrap();
+        let args = vec![Bytes::from("set"), Bytes::from("todo:1"), Bytes::from("value")];
+
+        assert_eq!(vec![&Bytes::from("todo:1")], spec.key_args(&args));
+        assert!(lookup("ping").unwrap().key_args(&args[..1]).is_empty());
+    }
+
+    #[test]
+    fn movable_key_args() {
+        let spec = lookup("migrate").unwrap();
+        let args: Vec<Bytes> = ["migrate", "host", "6380", "", "0", "100", "keys", "a", "b"].map(Bytes::from).to_vec();
+        assert_eq!(vec![&args[7], &args[8]], spec.key_args(&args));
+
+        let args: Vec<Bytes> = ["migrate", "host", "6380", "keys", "0", "100"].map(Bytes::from).to_vec();
+        assert_eq!(vec![&args[3]], spec.key_args(&args));
+    }
+
+    #[test]
+    fn check_arity() {
+        assert!(lookup("get").unwrap().check_arity(2).is_ok());
+        assert!(lookup("get").unwrap().check_arity(3).is_err());
+        assert!(lookup_full("client|kill").unwrap().check_arity(4).is_ok());
+        assert!(lookup_full("client|kill").unwrap().check_arity(2).is_err());
+    }
+
+    #[test]
+    fn loggable_args_redacts_passwords() {
+        let args = vec![Bytes::from("acl"), Bytes::from("setuser"), Bytes::from("board"), Bytes::from(">secret")];
+
+        let logged = lookup_full("acl|setuser").unwrap().loggable_args(&args);
+        assert_eq!(vec![Bytes::from("acl"), Bytes::from("setuser"), Bytes::from("(redacted)"), Bytes::from("(redacted)")], logged);
+    }
+
+    #[test]
+    fn every_command_is_reachable() {
+        for spec in executable() {
+            assert_eq!(spec.name, lookup_full(spec.name).unwrap().name);
+        }
+    }
+}
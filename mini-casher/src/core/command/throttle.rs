@@ -0,0 +1,69 @@
+use std::time::Duration;
+use bytes::Bytes;
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::parse::Parse;
+
+///`THROTTLE key max_burst count_per_period period [quantity]`: ограничение частоты по GCRA.
+///В ключе хранится только теоретическое время прихода следующего запроса (TAT)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throttle {
+    pub key: Bytes,
+    pub max_burst: u64,
+    pub count: u64,
+    pub period: Duration,
+    pub quantity: u64,
+}
+
+pub(crate) fn parse_throttle(parse: &mut Parse) -> Result<Command, CashError> {
+    let key = parse.next_bytes()?;
+    let max_burst = parse.next_int()?;
+    let count = parse.next_int()?;
+    let period = parse.next_int()?;
+    let quantity = match parse.remaining() {
+        0 => 1,
+        _ => parse.next_int()?,
+    };
+    parse.finish()?;
+
+    if count == 0 || period == 0 {
+        return Err(Error::CommandParse("count and period must be positive".to_string()));
+    }
+    //расчеты ведутся в наносекундах
+    if period > i64::MAX as u64 / 1_000_000_000 {
+        return Err(Error::CommandParse("period is too large".to_string()));
+    }
+
+    Ok(Command::Throttle(Throttle { key, max_burst, count, period: Duration::from_secs(period), quantity }))
+}
+
+impl Throttle {
+    pub fn frame(&self) -> Frame {
+        let args = [
+            Bytes::from("throttle"),
+            self.key.clone(),
+            Bytes::from(self.max_burst.to_string()),
+            Bytes::from(self.count.to_string()),
+            Bytes::from(self.period.as_secs().to_string()),
+            Bytes::from(self.quantity.to_string()),
+        ];
+        Frame::Array(args.into_iter().map(Frame::BulkString).collect())
+    }
+}
+
+#[cfg(test)]
+mod throttle_tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_frame() {
+        let Ok(Command::Throttle(cmd)) = Command::from_cmd("throttle api:1 15 30 60".to_string()) else { panic!() };
+        assert_eq!(1, cmd.quantity);
+        assert_eq!(Duration::from_secs(60), cmd.period);
+        assert!(matches!(Command::from_frame(cmd.frame()), Ok(Command::Throttle(parsed)) if parsed == cmd));
+
+        assert!(Command::from_cmd("throttle api:1 15 0 60".to_string()).is_err());
+        assert!(Command::from_cmd("throttle api:1 15 30 60 1 2".to_string()).is_err());
+    }
+}
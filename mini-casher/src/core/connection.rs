@@ -25,7 +25,7 @@ Redis принимает команды, состоящие из разных а
 При отправке кадров кадр сначала кодируется в буфер записи,затем содержимое буфера записи
 записывается в сокет.
 
-                        ***
+***
 Полное описание протокола в документации к redis:
 - https://redis.io/docs/reference/protocol-spec/
 
@@ -68,24 +68,8 @@ impl Connection {
     }
 
     ///Записывает одно значение `Frame` в сокет.
-    ///Вложенные массивы не поддерживаются
     pub async fn write_frame(&mut self, frame: &Frame) -> Result<(), CashError> {
-        match frame {
-            Frame::Array(val) => {
-                let len = val.len() as u64;
-
-                self.socket.write_u8(b'*').await?;
-                self.write_u64(&len).await?;
-                self.write_crlf().await?;
-                for f in val {
-                    self.write_value(f).await?;
-                }
-            }
-            _ => {
-                self.write_value(frame).await?;
-            }
-        }
-
+        self.write_value(frame).await?;
         self.socket.flush().await?;
         Ok(())
     }
@@ -96,16 +80,15 @@ impl Connection {
     ///При остальных возможных ошибках процесс преобразования прерывается
     async fn parse_frame(&mut self) -> Result<Option<Frame>, CashError> {
         let mut buff = Cursor::new(self.buffer.to_owned());
-        let len = buff.position() as usize;
-        buff.set_position(0);
 
         match Frame::try_frame(&mut buff) {
             Ok(frame) => {
-                buff.advance(len);
+                let len = buff.position() as usize;
+                self.buffer.advance(len);
                 Ok(Some(frame))
             }
             Err(CashError::Incomplete) => Ok(None),
-            Err(e) => Err(e.into())
+            Err(e) => Err(e)
         }
     }
 
@@ -123,7 +106,7 @@ impl Connection {
             }
             Frame::Integer(val) => {
                 self.socket.write_u8(b':').await?;
-                self.write_u64(val).await?;
+                self.socket.write_all(val.to_string().as_bytes()).await?;
                 self.write_crlf().await?;
             }
             Frame::Null => {
@@ -137,8 +120,15 @@ impl Connection {
                 self.socket.write_all(val).await?;
                 self.write_crlf().await?;
             }
-            Frame::Array(_) => {
-                return Err(CashError::Protocol("nested arrays are not supported".to_string()));
+            Frame::Array(val) => {
+                let len = val.len() as u64;
+
+                self.socket.write_u8(b'*').await?;
+                self.write_u64(&len).await?;
+                self.write_crlf().await?;
+                for f in val {
+                    Box::pin(self.write_value(f)).await?;
+                }
             }
         }
 
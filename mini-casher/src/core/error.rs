@@ -1,9 +1,7 @@
-use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
 use std::string::FromUtf8Error;
-use std::sync::{MutexGuard, PoisonError};
-use bytes::Bytes;
+use std::sync::PoisonError;
 use thiserror::Error;
 
 
@@ -18,6 +16,12 @@ pub enum Error {
     Protocol(String),
     CommandParse(String),
     Storage(String),
+    NoAuth,
+    NoPerm(String),
+    WrongPass,
+    Config(String),
+    Oom,
+    WrongType(String),
 }
 
 
@@ -39,8 +43,8 @@ impl From<std::num::ParseIntError> for Error {
     }
 }
 
-impl From<PoisonError<std::sync::MutexGuard<'_, HashMap<String, bytes::Bytes>>>> for Error {
-    fn from(value: PoisonError<MutexGuard<'_, HashMap<String, Bytes>>>) -> Self {
+impl<T> From<PoisonError<T>> for Error {
+    fn from(value: PoisonError<T>) -> Self {
         Error::Storage(value.to_string())
     }
 }
@@ -54,6 +58,12 @@ impl Display for Error {
             Error::Protocol(value) => value,
             Error::CommandParse(value) => value,
             Error::Storage(value) => value,
+            Error::NoAuth => return write!(f, "NOAUTH Authentication required."),
+            Error::NoPerm(value) => return write!(f, "NOPERM {value}"),
+            Error::Config(value) => value,
+            Error::WrongType(value) => return write!(f, "WRONGTYPE {value}"),
+            Error::Oom => return write!(f, "OOM command not allowed when used memory > 'maxmemory'."),
+            Error::WrongPass => return write!(f, "WRONGPASS invalid username-password pair or user is disabled."),
         };
 
         write!(f, "{message}")
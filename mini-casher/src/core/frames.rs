@@ -1,5 +1,5 @@
 use std::io::Cursor;
-use bytes::{Buf, BufMut, Bytes, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use crate::core::error::CashError;
 
 /**
@@ -24,7 +24,7 @@ RESP может представлять значение Null, использу
 Кадрирование — это процесс получения потока байтов и преобразования его в поток кадров.
 Фрейм — это единица данных, передаваемая между двумя одноранговыми узлами.
 
-                        ***
+***
 Полное описание протокола в документации к redis:
 
 - https://redis.io/docs/reference/protocol-spec/
@@ -37,7 +37,7 @@ RESP может представлять значение Null, использу
 pub enum Frame {
     Simple(String),
     Error(String),
-    Integer(u64),
+    Integer(i64),
     BulkString(Bytes),
     Null,
     Array(Vec<Frame>),
@@ -55,7 +55,7 @@ impl Frame {
             b'$' => bulk_string_frame(buff),
             b'*' => array_frame(buff),
             unknown => Err(CashError::Protocol(
-                format!("protocol error; invalid frame type byte `{}`", unknown.to_string())))
+                format!("protocol error; invalid frame type byte `{}`", unknown)))
         }
     }
 }
@@ -83,11 +83,14 @@ fn error_frame(buff: &mut Cursor<BytesMut>) -> Result<Frame, CashError> {
 ///При успешном преобразовании возвращает - `Frame::Integer`
 ///Возможные ошибки:
 /// `ERROR:Incomplete` при неполных данных
-/// `ERORR::Protocol` при неудачном преобразовании байтов в u64
+/// `ERORR::Protocol` при неудачном преобразовании байтов в i64
 fn decimal_frame(buff: &mut Cursor<BytesMut>) -> Result<Frame, CashError> {
-    let line = line(buff)?.to_vec();
-    let decimal = decimal(line)?;
-    Ok(Frame::Integer(decimal))
+    use atoi::atoi;
+    let line = line(buff)?;
+    match atoi::<i64>(line) {
+        Some(decimal) => Ok(Frame::Integer(decimal)),
+        None => Err(CashError::Protocol("protocol error; invalid decimal frame format".to_string()))
+    }
 }
 
 ///При успешном преобразовании возвращает:
@@ -97,7 +100,7 @@ fn decimal_frame(buff: &mut Cursor<BytesMut>) -> Result<Frame, CashError> {
 /// `ERROR:Incomplete` при неполных данных
 /// `ERORR::Protocol` при нарешении формы протокола
 fn bulk_string_frame(buff: &mut Cursor<BytesMut>) -> Result<Frame, CashError> {
-    return match peek(buff)? {
+    match peek(buff)? {
         b'-' => {
             let line = line(buff)?;
 
@@ -149,7 +152,7 @@ fn pop(buff: &mut Cursor<BytesMut>) -> Result<u8, CashError> {
         return Err(CashError::Incomplete);
     }
 
-    return Ok(buff.get_u8());
+    Ok(buff.get_u8())
 }
 
 ///Возвращает первый байт как u8, позиция не изменяется.
@@ -159,7 +162,7 @@ fn peek(buff: &mut Cursor<BytesMut>) -> Result<u8, CashError> {
         return Err(CashError::Incomplete);
     }
 
-    return Ok(buff.chunk()[0] as u8);
+    Ok(buff.chunk()[0])
 }
 
 ///Вовращает последовательность байтов от текущей позиции до CRLF.
@@ -205,7 +208,7 @@ fn skip_crlf(buff: &mut Cursor<BytesMut>) -> Result<bool, CashError> {
         }
     }
 
-    return Ok(false);
+    Ok(false)
 }
 
 
@@ -214,7 +217,9 @@ fn next_crlf(current: u8, next: u8) -> bool {
 }
 
 
+#[cfg(test)]
 mod frames_tests {
+    use bytes::BufMut;
     use super::*;
 
     #[tokio::test]
@@ -241,6 +246,14 @@ mod frames_tests {
         assert_eq!(Ok(Frame::Integer(1984)), decimal)
     }
 
+    #[tokio::test]
+    async fn try_frame_negative_decimal_ok() {
+        let mut buff = test_data(&b":-2\r\n"[..]);
+
+        let decimal = Frame::try_frame(&mut buff);
+        assert_eq!(Ok(Frame::Integer(-2)), decimal)
+    }
+
     #[tokio::test]
     async fn try_frame_decimal_err() {
         let mut buff = test_data(&b":19"[..]);
@@ -1,4 +1,6 @@
 pub mod connection;
 pub mod frames;
 pub mod error;
-pub mod command;
\ No newline at end of file
+pub mod command;
+pub mod parse;
+pub mod glob;
@@ -0,0 +1,95 @@
+use std::vec::IntoIter;
+use bytes::Bytes;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+
+///Курсор по аргументам команды.
+///Команда приходит как `Frame::Array`, `Parse` последовательно
+///отдает её элементы в нужном виде: строка, байты или число.
+#[derive(Debug)]
+pub struct Parse {
+    parts: IntoIter<Frame>,
+}
+
+impl Parse {
+    ///Возвращает ошибку протокола, если кадр не является массивом
+    pub fn new(frame: Frame) -> Result<Parse, CashError> {
+        match frame {
+            Frame::Array(array) => Ok(Parse { parts: array.into_iter() }),
+            frame => Err(Error::Protocol(format!("protocol error; expected array, got {:?}", frame)))
+        }
+    }
+
+    ///Создает курсор из слов строки ввода консоли
+    pub fn from_words<'a>(words: impl Iterator<Item = &'a str>) -> Parse {
+        let parts: Vec<Frame> = words
+            .map(|w| Frame::BulkString(Bytes::from(w.to_string())))
+            .collect();
+
+        Parse { parts: parts.into_iter() }
+    }
+
+    pub fn next_frame(&mut self) -> Result<Frame, CashError> {
+        self.parts
+            .next()
+            .ok_or_else(|| Error::CommandParse("wrong number of arguments".to_string()))
+    }
+
+    pub fn next_bytes(&mut self) -> Result<Bytes, CashError> {
+        match self.next_frame()? {
+            Frame::BulkString(bytes) => Ok(bytes),
+            Frame::Simple(string) => Ok(Bytes::from(string)),
+            frame => Err(Error::Protocol(format!("protocol error; expected bulk string, got {:?}", frame)))
+        }
+    }
+
+    pub fn next_string(&mut self) -> Result<String, CashError> {
+        let bytes = self.next_bytes()?;
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    pub fn next_int(&mut self) -> Result<u64, CashError> {
+        match self.next_frame()? {
+            Frame::Integer(int) => u64::try_from(int)
+                .map_err(|_| Error::CommandParse("value is out of range, must be positive".to_string())),
+            Frame::BulkString(bytes) => std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| Error::CommandParse("value is not an integer or out of range".to_string())),
+            frame => Err(Error::Protocol(format!("protocol error; expected integer, got {:?}", frame)))
+        }
+    }
+
+    pub fn next_signed(&mut self) -> Result<i64, CashError> {
+        match self.next_frame()? {
+            Frame::Integer(int) => Ok(int),
+            Frame::BulkString(bytes) => std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| Error::CommandParse("value is not an integer or out of range".to_string())),
+            frame => Err(Error::Protocol(format!("protocol error; expected integer, got {:?}", frame)))
+        }
+    }
+
+    ///Следующий аргумент строкой без сдвига курсора, для необязательных опций
+    pub fn peek_string(&self) -> Option<String> {
+        match self.parts.as_slice().first()? {
+            Frame::BulkString(bytes) => String::from_utf8(bytes.to_vec()).ok(),
+            Frame::Simple(string) => Some(string.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.parts.len()
+    }
+
+    ///Проверяет, что все аргументы прочитаны
+    pub fn finish(&mut self) -> Result<(), CashError> {
+        if self.parts.next().is_none() {
+            Ok(())
+        } else {
+            Err(Error::CommandParse("wrong number of arguments".to_string()))
+        }
+    }
+}
@@ -1,11 +1,22 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use bytes::Bytes;
+use std::sync::Arc;
+use crate::storage::sharded::Sharded;
+use crate::storage::Store;
 
 pub mod core;
 pub mod server;
 pub mod client;
+pub mod storage;
 
-pub type Storage = Arc<Mutex<HashMap<String, Bytes>>>;
+///Хранилище, общее для всех соединений. Движок выбирается параметром `storage-engine`
+pub type Storage<E = Store> = Arc<Sharded<E>>;
 
-pub const SOCKET_ADDR: &str = "127.0.0.1:6379";
\ No newline at end of file
+pub const SOCKET_ADDR: &str = "127.0.0.1:6379";
+
+///Переменная окружения с адресом сервера для клиентов,
+///когда сервер запущен не на порту по умолчанию
+pub const ADDR_ENV: &str = "CASH_ADDR";
+
+///Адрес сервера из `CASH_ADDR` или адрес по умолчанию
+pub fn socket_addr() -> String {
+    std::env::var(ADDR_ENV).unwrap_or_else(|_| SOCKET_ADDR.to_string())
+}
@@ -0,0 +1,338 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::Mutex;
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use crate::core::command::acl::{AclCmd, Auth};
+use crate::core::command::table::{self, CommandSpec};
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::glob::glob_match;
+
+///Пользователь ACL.
+///Пароли хранятся в виде sha256, правила команд - в порядке применения,
+///чтобы `ACL LIST` показывал их так же, как они были заданы
+#[derive(Debug, Clone, Default)]
+struct User {
+    enabled: bool,
+    nopass: bool,
+    passwords: BTreeSet<String>,
+    key_patterns: Vec<String>,
+    command_rules: Vec<String>,
+    allowed: HashSet<&'static str>,
+}
+
+impl User {
+    fn new() -> Self {
+        let mut user = User::default();
+        user.command_rules.push("-@all".to_string());
+        user
+    }
+
+    ///Правило `ACL SETUSER`
+    fn apply(&mut self, rule: &str) -> Result<(), CashError> {
+        match rule.to_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.key_patterns = vec!["*".to_string()],
+            "resetkeys" => self.key_patterns.clear(),
+            "allcommands" => self.command_rule("+@all")?,
+            "nocommands" => self.command_rule("-@all")?,
+            "reset" => *self = User::new(),
+            _ => match rule.as_bytes().first() {
+                Some(b'>') => {
+                    self.passwords.insert(hash(&rule[1..]));
+                    self.nopass = false;
+                }
+                Some(b'<') => {
+                    self.passwords.remove(&hash(&rule[1..]));
+                }
+                Some(b'#') if rule.len() == 65 => {
+                    self.passwords.insert(rule[1..].to_lowercase());
+                    self.nopass = false;
+                }
+                Some(b'!') => {
+                    self.passwords.remove(&rule[1..].to_lowercase());
+                }
+                Some(b'~') => {
+                    if !self.key_patterns.iter().any(|p| p == "*") {
+                        self.key_patterns.push(rule[1..].to_string());
+                    }
+                }
+                Some(b'+') | Some(b'-') => self.command_rule(rule)?,
+                _ => return Err(syntax_error(rule))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn command_rule(&mut self, rule: &str) -> Result<(), CashError> {
+        let rule = rule.to_lowercase();
+        let (add, target) = rule.split_at(1);
+        let add = add == "+";
+
+        let names: Vec<&'static str> = match target.strip_prefix('@') {
+            Some("all") => table::executable().map(|c| c.name).collect(),
+            Some(category) => table::executable()
+                .filter(|c| c.categories.contains(&category))
+                .map(|c| c.name)
+                .collect(),
+            None => table::executable()
+                .map(|c| c.name)
+                .filter(|name| *name == target || name.strip_prefix(target).is_some_and(|sub| sub.starts_with('|')))
+                .collect(),
+        };
+
+        if names.is_empty() {
+            return Err(syntax_error(&rule));
+        }
+
+        for name in names {
+            if add {
+                self.allowed.insert(name);
+            } else {
+                self.allowed.remove(name);
+            }
+        }
+
+        if target == "@all" {
+            self.command_rules.clear();
+        }
+        self.command_rules.push(rule);
+
+        Ok(())
+    }
+
+    fn key_allowed(&self, key: &[u8]) -> bool {
+        self.key_patterns.iter().any(|p| glob_match(p.as_bytes(), key))
+    }
+
+    ///Описание пользователя в формате `ACL LIST`
+    fn describe(&self, name: &str) -> String {
+        let mut parts = vec![
+            "user".to_string(),
+            name.to_string(),
+            if self.enabled { "on" } else { "off" }.to_string(),
+        ];
+
+        if self.nopass {
+            parts.push("nopass".to_string());
+        }
+        parts.extend(self.passwords.iter().map(|p| format!("#{}", p)));
+        parts.extend(self.key_patterns.iter().map(|p| format!("~{}", p)));
+        parts.extend(self.command_rules.iter().cloned());
+
+        parts.join(" ")
+    }
+}
+
+///Список пользователей и проверка прав на выполнение команд
+#[derive(Debug)]
+pub struct Acl {
+    users: Mutex<BTreeMap<String, User>>,
+}
+
+impl Acl {
+    ///Создает пользователя `default` со всеми правами.
+    ///Если задан `requirepass`, пользователь `default` должен пройти `AUTH`
+    pub fn new(requirepass: Option<String>) -> Self {
+        let mut default = User::new();
+        default.enabled = true;
+        default.key_patterns.push("*".to_string());
+        default.command_rule("+@all").expect("`+@all` is a valid rule");
+
+        match requirepass {
+            Some(password) => {
+                default.passwords.insert(hash(&password));
+            }
+            None => default.nopass = true,
+        }
+
+        let mut users = BTreeMap::new();
+        users.insert("default".to_string(), default);
+
+        Self { users: Mutex::new(users) }
+    }
+
+    ///Меняет пароль пользователя `default` по `CONFIG SET requirepass`.
+    ///Пустой пароль отключает проверку
+    pub fn set_requirepass(&self, requirepass: Option<String>) -> Result<(), CashError> {
+        let mut users = self.users.lock()?;
+        let Some(default) = users.get_mut("default") else {
+            return Ok(());
+        };
+
+        default.passwords.clear();
+        match requirepass {
+            Some(password) => {
+                default.passwords.insert(hash(&password));
+                default.nopass = false;
+            }
+            None => default.nopass = true,
+        }
+
+        Ok(())
+    }
+
+    ///Новое соединение считается аутентифицированным,
+    ///если пользователь `default` включен и не требует пароль
+    pub fn default_authenticated(&self) -> Result<bool, CashError> {
+        let users = self.users.lock()?;
+        Ok(users.get("default").is_some_and(|u| u.enabled && u.nopass))
+    }
+
+    pub fn authenticate(&self, auth: &Auth) -> Result<(), CashError> {
+        let users = self.users.lock()?;
+
+        if auth.user() == "default" && users.get("default").is_some_and(|u| u.nopass) {
+            return Err(Error::CommandParse(
+                "AUTH called without any password configured for the default user".to_string()));
+        }
+
+        match users.get(auth.user()) {
+            Some(user) if user.enabled && (user.nopass || user.passwords.contains(&hash(auth.password()))) => Ok(()),
+            _ => Err(Error::WrongPass)
+        }
+    }
+
+    ///Проверяет, что пользователю доступна команда и все ключи, которые она затрагивает.
+    ///Ключи берутся из аргументов по позициям из таблицы команд
+    pub fn check(&self, user: &str, spec: &CommandSpec, args: &[Bytes]) -> Result<(), CashError> {
+        let users = self.users.lock()?;
+        let name = spec.name;
+
+        let Some(acl_user) = users.get(user) else {
+            return Err(Error::NoAuth);
+        };
+
+        if !acl_user.allowed.contains(name) {
+            return Err(Error::NoPerm(format!("this user has no permissions to run the '{}' command", name)));
+        }
+
+        if spec.key_args(args).iter().any(|key| !acl_user.key_allowed(key)) {
+            return Err(Error::NoPerm("no permissions to access a key".to_string()));
+        }
+
+        Ok(())
+    }
+
+    ///Отбирает ключи, доступные пользователю, для команд, которые проходят по всему хранилищу
+    pub fn key_filter(&self, user: &str) -> Result<impl Fn(&[u8]) -> bool, CashError> {
+        let users = self.users.lock()?;
+        let patterns = users.get(user).map(|u| u.key_patterns.clone()).unwrap_or_default();
+
+        Ok(move |key: &[u8]| patterns.iter().any(|p| glob_match(p.as_bytes(), key)))
+    }
+
+    ///Удаляет пользователей и возвращает имена удаленных.
+    ///Пользователя `default` удалить нельзя
+    pub fn del_users(&self, names: &[String]) -> Result<Vec<String>, CashError> {
+        if names.iter().any(|n| n == "default") {
+            return Err(Error::CommandParse("the 'default' user cannot be removed".to_string()));
+        }
+
+        let mut users = self.users.lock()?;
+        Ok(names.iter().filter(|n| users.remove(*n).is_some()).cloned().collect())
+    }
+
+    pub fn execute(&self, user: &str, cmd: AclCmd) -> Result<Frame, CashError> {
+        match cmd {
+            AclCmd::SetUser(name, rules) => {
+                let mut users = self.users.lock()?;
+                let mut acl_user = users.get(&name).cloned().unwrap_or_else(User::new);
+
+                for rule in &rules {
+                    acl_user.apply(rule)?;
+                }
+
+                users.insert(name, acl_user);
+                Ok(Frame::Simple("Ok".to_string()))
+            }
+            AclCmd::List => {
+                let users = self.users.lock()?;
+                let list = users
+                    .iter()
+                    .map(|(name, u)| Frame::BulkString(Bytes::from(u.describe(name))))
+                    .collect();
+
+                Ok(Frame::Array(list))
+            }
+            AclCmd::WhoAmI => Ok(Frame::BulkString(Bytes::from(user.to_string()))),
+            AclCmd::DelUser(names) => Ok(Frame::Integer(self.del_users(&names)?.len() as i64)),
+        }
+    }
+}
+
+fn hash(password: &str) -> String {
+    Sha256::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn syntax_error(rule: &str) -> Error {
+    Error::CommandParse(format!("error in ACL SETUSER modifier '{}': syntax error", rule))
+}
+
+#[cfg(test)]
+mod acl_tests {
+    use super::*;
+
+    fn check(acl: &Acl, user: &str, args: &[&str]) -> Result<(), CashError> {
+        let spec = table::lookup(args[0]).unwrap();
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        acl.check(user, spec, &args)
+    }
+
+    fn acl_with(user: &str, rules: &str) -> Acl {
+        let acl = Acl::new(None);
+        let rules = rules.split(' ').map(String::from).collect();
+        acl.execute("default", AclCmd::SetUser(user.to_string(), rules)).unwrap();
+        acl
+    }
+
+    #[test]
+    fn check_categories_and_keys() {
+        let acl = acl_with("board", "on >secret ~todo:* +@read");
+
+        assert_eq!(Ok(()), check(&acl, "board", &["get", "todo:1"]));
+        assert!(matches!(check(&acl, "board", &["get", "user:1"]), Err(Error::NoPerm(_))));
+        assert!(matches!(check(&acl, "board", &["set", "todo:1", "v"]), Err(Error::NoPerm(_))));
+    }
+
+    #[test]
+    fn authenticate_password() {
+        let acl = acl_with("board", "on >secret");
+
+        assert_eq!(Ok(()), acl.authenticate(&Auth::new(Some("board".to_string()), "secret".to_string())));
+        assert_eq!(Err(Error::WrongPass), acl.authenticate(&Auth::new(Some("board".to_string()), "nope".to_string())));
+    }
+
+    #[test]
+    fn requirepass_disables_default_login() {
+        let acl = Acl::new(Some("pass".to_string()));
+
+        assert_eq!(Ok(false), acl.default_authenticated());
+        assert_eq!(Ok(()), acl.authenticate(&Auth::new(None, "pass".to_string())));
+
+        acl.set_requirepass(None).unwrap();
+        assert_eq!(Ok(true), acl.default_authenticated());
+    }
+
+    #[test]
+    fn describe_user() {
+        let acl = acl_with("board", "on nopass ~todo:* -@all +@read -len");
+
+        let list = acl.execute("default", AclCmd::List).unwrap();
+        let expected = Frame::BulkString(Bytes::from("user board on nopass ~todo:* -@all +@read -len"));
+        assert!(matches!(list, Frame::Array(users) if users.contains(&expected)));
+    }
+}
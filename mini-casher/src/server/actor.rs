@@ -0,0 +1,378 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use bytes::Bytes;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use crate::Storage;
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::server::aof::Aof;
+use crate::server::dispatch;
+use crate::storage::engine::StorageEngine;
+
+///Сколько команд может ждать поток хранилища, прежде чем соединения начнут ждать места в очереди
+const QUEUE_CAPACITY: usize = 1024;
+
+///Фильтр ключей пользователя ACL, который уходит вместе с командой в поток хранилища
+pub type KeyFilter = Box<dyn Fn(&[u8]) -> bool + Send>;
+
+///Как выполняются команды хранилища, параметр `execution`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Execution {
+    ///Каждое соединение выполняет команды само под блокировками частей
+    Locks,
+    ///Команды выполняет один поток хранилища по очереди
+    Actor,
+}
+
+impl Execution {
+    pub const NAMES: &'static [&'static str] = &["locks", "actor"];
+}
+
+impl FromStr for Execution {
+    type Err = CashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "locks" => Ok(Execution::Locks),
+            "actor" => Ok(Execution::Actor),
+            _ => Err(Error::Config(format!("unknown execution model '{}'", s))),
+        }
+    }
+}
+
+///Команда для потока хранилища с каналом для ответа
+struct Job {
+    command: Command,
+    args: Vec<Bytes>,
+    allowed: KeyFilter,
+    reply: oneshot::Sender<Result<Frame, CashError>>,
+}
+
+///Очередь команд к потоку хранилища. Поток выполняет команды по одной в порядке очереди
+///и сам пишет их в AOF. Как и соединение, он берет журнал до выполнения команды записи,
+///поэтому порядок в журнале совпадает с порядком выполнения, перезапись AOF не теряет команд,
+///а `MIGRATE` останавливает и его записи
+#[derive(Debug, Clone)]
+pub struct Keyspace {
+    sender: mpsc::Sender<Job>,
+}
+
+impl Keyspace {
+    ///Запускает поток хранилища. Он работает, пока есть хотя бы одна копия `Keyspace`.
+    ///Команды ждут блокировки частей и файлы `LogStore` синхронно, поэтому это отдельный поток,
+    ///а не задача tokio. `appendonly` и `writes` - те же, что у соединений
+    pub fn spawn<E: StorageEngine + 'static>(
+        storage: Storage<E>,
+        aof: Arc<tokio::sync::Mutex<Aof>>,
+        appendonly: Arc<AtomicBool>,
+        writes: Arc<RwLock<()>>,
+    ) -> Result<Self, CashError> {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        std::thread::Builder::new()
+            .name("keyspace".to_string())
+            .spawn(move || run(storage, aof, appendonly, writes, receiver))
+            .map_err(|e| Error::Storage(format!("can't start the keyspace thread: {}", e)))?;
+        Ok(Keyspace { sender })
+    }
+
+    ///Ставит команду в очередь и ждет ответа
+    pub async fn execute(&self, command: Command, args: &[Bytes], allowed: KeyFilter) -> Result<Frame, CashError> {
+        let (reply, response) = oneshot::channel();
+        let job = Job { command, args: args.to_vec(), allowed, reply };
+
+        self.sender
+            .send(job)
+            .await
+            .map_err(|_| Error::Storage("the keyspace thread has stopped".to_string()))?;
+        response
+            .await
+            .map_err(|_| Error::Storage("the keyspace thread dropped the command".to_string()))?
+    }
+}
+
+fn run<E: StorageEngine>(
+    storage: Storage<E>,
+    aof: Arc<tokio::sync::Mutex<Aof>>,
+    appendonly: Arc<AtomicBool>,
+    writes: Arc<RwLock<()>>,
+    mut receiver: mpsc::Receiver<Job>,
+) {
+    while let Some(job) = receiver.blocking_recv() {
+        let write = job.command.is_write();
+        let writing = match write {
+            true => Some(writes.blocking_read()),
+            false => None,
+        };
+        let mut aof = match write && appendonly.load(Ordering::SeqCst) {
+            true => Some(aof.blocking_lock()).filter(|aof| aof.is_enabled()),
+            false => None,
+        };
+
+        //паника в команде не должна останавливать поток: иначе ни один клиент не получит ответа
+        let result = catch_unwind(AssertUnwindSafe(|| dispatch(&storage, job.command, &job.args, job.allowed)))
+            .unwrap_or_else(|_| {
+                log::error!("a storage command panicked, the keyspace thread keeps running");
+                storage.clear_poison();
+                Err(Error::Storage("internal error while executing the command".to_string()))
+            });
+
+        if let (Some(aof), Ok(frame)) = (&mut aof, &result) {
+            if let Err(err) = aof.log(&job.args, frame, &storage) {
+                log::error!("{}", err);
+            }
+        }
+        drop(aof);
+        drop(writing);
+
+        //соединение могло закрыться, пока команда ждала в очереди
+        let _ = job.reply.send(result);
+    }
+}
+
+#[cfg(test)]
+mod actor_tests {
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use crate::core::command::keyspace::Migrate;
+    use crate::core::connection::Connection;
+    use crate::server::{aof, keyspace};
+    use std::time::Instant;
+    use crate::core::command::search::{IndexDef, Query};
+    use crate::storage::engine::EngineOptions;
+    use crate::storage::eviction::Policy;
+    use crate::storage::sharded::Sharded;
+    use crate::storage::{Store, Value};
+    use super::*;
+
+    fn command(args: &[&str]) -> (Command, Vec<Bytes>) {
+        let frame = Frame::Array(args.iter().map(|a| Frame::BulkString(Bytes::from(a.to_string()))).collect());
+        let args = crate::server::frame_args(&frame);
+        (Command::from_frame(frame).unwrap(), args)
+    }
+
+    fn spawn(storage: &Storage, aof: &Arc<tokio::sync::Mutex<Aof>>, writes: &Arc<RwLock<()>>) -> Keyspace {
+        let appendonly = aof.try_lock().unwrap().enabled();
+        Keyspace::spawn(storage.clone(), aof.clone(), appendonly, writes.clone()).unwrap()
+    }
+
+    async fn append(keyspace: &Keyspace, key: &str) -> Result<Frame, CashError> {
+        let (cmd, args) = command(&["append", key, "x"]);
+        keyspace.execute(cmd, &args, Box::new(|_: &[u8]| true)).await
+    }
+
+    ///Движок в памяти, который паникует при чтении ключа `boom`
+    #[derive(Debug)]
+    struct Fragile(Store);
+
+    impl StorageEngine for Fragile {
+        fn open(options: &EngineOptions, shard: usize) -> Result<Self, CashError> {
+            Ok(Fragile(Store::open(options, shard)?))
+        }
+
+        fn get_value(&mut self, key: &[u8]) -> Result<Option<Value>, CashError> {
+            assert_ne!(b"boom", key);
+            self.0.get_value(key)
+        }
+
+        fn set_value(&mut self, key: Bytes, value: Value) -> Result<(), CashError> {
+            self.0.set_value(key, value)
+        }
+
+        fn delete(&mut self, key: &[u8]) -> Option<Value> {
+            self.0.delete(key)
+        }
+
+        fn expire(&mut self, key: &[u8], at: Option<Instant>) -> bool {
+            self.0.expire(key, at)
+        }
+
+        fn deadline(&self, key: &[u8]) -> Option<Instant> {
+            self.0.deadline(key)
+        }
+
+        fn type_name(&self, key: &[u8]) -> Option<&'static str> {
+            self.0.type_name(key)
+        }
+
+        fn scan(&self, cursor: usize, count: usize) -> (usize, Vec<Bytes>) {
+            self.0.scan(cursor, count)
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn entries(&mut self) -> Result<Vec<(Bytes, Value, Option<Instant>)>, CashError> {
+            self.0.entries()
+        }
+
+        fn used_memory(&self) -> usize {
+            self.0.used_memory()
+        }
+
+        fn reserve(&mut self, incoming: &[(&[u8], usize)]) -> Result<(), CashError> {
+            self.0.reserve(incoming)
+        }
+
+        fn configure(&mut self, maxmemory: usize, policy: Policy, samples: usize) {
+            self.0.configure(maxmemory, policy, samples)
+        }
+
+        fn dirty(&self) -> u64 {
+            self.0.dirty()
+        }
+
+        fn saved(&self, dirty: u64) {
+            self.0.saved(dirty)
+        }
+
+        fn create_index(&mut self, def: IndexDef) -> Result<(), CashError> {
+            self.0.create_index(def)
+        }
+
+        fn drop_index(&mut self, name: &str) -> bool {
+            self.0.drop_index(name)
+        }
+
+        fn index_defs(&self) -> Vec<IndexDef> {
+            self.0.index_defs()
+        }
+
+        fn search(&self, name: &str, query: &Query) -> Result<Vec<Bytes>, CashError> {
+            self.0.search(name, query)
+        }
+    }
+
+    #[tokio::test]
+    async fn survives_a_panicking_command() {
+        let storage: Storage<Fragile> = Arc::new(Sharded::open(&EngineOptions { shards: 1, ..Default::default() }).unwrap());
+        let aof = Arc::new(tokio::sync::Mutex::new(Aof::default()));
+        let keyspace = Keyspace::spawn(storage.clone(), aof, Arc::default(), Arc::default()).unwrap();
+
+        let (cmd, args) = command(&["get", "boom"]);
+        assert!(keyspace.execute(cmd, &args, Box::new(|_: &[u8]| true)).await.is_err());
+
+        //поток жив, а часть, на которой случилась паника, снова доступна
+        assert_eq!(Ok(Frame::Integer(1)), append(&keyspace, "log").await);
+        assert_eq!(Ok(Some(Bytes::from("x"))), storage.shard(b"log").unwrap().get(b"log"));
+    }
+
+    #[tokio::test]
+    async fn serializes_commands_from_many_tasks() {
+        let storage: Storage = Arc::new(Sharded::default());
+        let keyspace = spawn(&storage, &Arc::new(tokio::sync::Mutex::new(Aof::default())), &Arc::default());
+
+        let mut tasks = vec![];
+        for _ in 0..8 {
+            let keyspace = keyspace.clone();
+            tasks.push(tokio::spawn(async move {
+                for _ in 0..100 {
+                    append(&keyspace, "log").await.unwrap();
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let (cmd, args) = command(&["get", "log"]);
+        let frame = keyspace.execute(cmd, &args, Box::new(|_: &[u8]| true)).await.unwrap();
+        assert_eq!(Frame::BulkString(Bytes::from("x".repeat(800))), frame);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rewrite_keeps_queued_writes() {
+        let path = std::env::temp_dir().join(format!("actor-rewrite-{}.aof", std::process::id()));
+        let storage: Storage = Arc::new(Sharded::default());
+        let aof = Arc::new(tokio::sync::Mutex::new(Aof::default()));
+        aof.lock().await.create(&path, &storage).unwrap();
+        let keyspace = spawn(&storage, &aof, &Arc::default());
+
+        //пока журнал занят, например снимком перезаписи, запись не выполняется
+        let journal = aof.lock().await;
+        let first = {
+            let keyspace = keyspace.clone();
+            tokio::spawn(async move { append(&keyspace, "log").await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(Ok(None), storage.shard(b"log").unwrap().get(b"log"));
+        drop(journal);
+        first.await.unwrap().unwrap();
+
+        let writer = {
+            let keyspace = keyspace.clone();
+            tokio::spawn(async move {
+                for _ in 0..499 {
+                    append(&keyspace, "log").await.unwrap();
+                }
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        aof::bgrewrite(aof.clone(), storage.clone()).await.unwrap();
+        writer.await.unwrap();
+        while aof.lock().await.is_rewriting() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        //каждая команда записана ровно один раз: или в снимке перезаписи, или после него
+        let restored = Sharded::default();
+        aof::replay(&path, &restored).unwrap();
+        assert_eq!(Ok(Some(Bytes::from("x".repeat(500)))), restored.shard(b"log").unwrap().get(b"log"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn migrate_holds_queued_writes() {
+        let storage: Storage = Arc::new(Sharded::default());
+        storage.shard(b"a").unwrap().set(Bytes::from("a"), Bytes::from("1")).unwrap();
+        let aof = Arc::new(tokio::sync::Mutex::new(Aof::default()));
+        let writes = Arc::default();
+        let keyspace = spawn(&storage, &aof, &writes);
+
+        //целевой сервер отвечает, только когда тест его отпустит
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (release, released) = oneshot::channel::<()>();
+        let target = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(socket);
+            let frame = connection.read_frame().await.unwrap();
+            released.await.unwrap();
+            connection.write_frame(&Frame::Simple("Ok".to_string())).await.unwrap();
+            frame
+        });
+
+        let migrate = Migrate {
+            host: "127.0.0.1".to_string(),
+            port,
+            keys: vec![Bytes::from("a")],
+            timeout: 5000,
+            copy: false,
+            replace: false,
+            auth: None,
+        };
+        let migration = {
+            let (storage, aof, writes) = (storage.clone(), aof.clone(), writes.clone());
+            tokio::spawn(async move { keyspace::migrate(&storage, &aof, &writes, migrate).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let write = {
+            let keyspace = keyspace.clone();
+            tokio::spawn(async move { append(&keyspace, "a").await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!write.is_finished());
+
+        release.send(()).unwrap();
+        assert_eq!(Ok(Frame::Simple("Ok".to_string())), migration.await.unwrap());
+        assert!(target.await.unwrap().is_some());
+
+        //запись выполнена после переноса: ключ уже удален и создается заново
+        assert_eq!(Ok(Frame::Integer(1)), write.await.unwrap());
+        assert_eq!(Ok(Some(Bytes::from("x"))), storage.shard(b"a").unwrap().get(b"a"));
+    }
+}
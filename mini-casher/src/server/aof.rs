@@ -0,0 +1,560 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use bytes::{Bytes, BytesMut};
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::server::{dispatch, frame_args};
+use crate::server::persistence::write_atomic;
+use crate::storage::engine::StorageEngine;
+use crate::storage::sharded::Sharded;
+use crate::storage::snapshot::{unix_ms, Snapshot, MAGIC};
+use crate::Storage;
+
+///Когда данные AOF сбрасываются на диск: после каждой команды,
+///раз в секунду или когда решит операционная система
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fsync {
+    Always,
+    EverySec,
+    No,
+}
+
+impl Fsync {
+    pub const NAMES: &'static [&'static str] = &["always", "everysec", "no"];
+}
+
+impl FromStr for Fsync {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "always" => Ok(Fsync::Always),
+            "everysec" => Ok(Fsync::EverySec),
+            "no" => Ok(Fsync::No),
+            _ => Err(Error::Config(format!("invalid appendfsync '{}'", value))),
+        }
+    }
+}
+
+///Команда в формате RESP, как её отправляет клиент
+fn encode(args: &[Bytes]) -> Vec<u8> {
+    let mut buf = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buf.extend_from_slice(arg);
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf
+}
+
+///Аргументы команды для AOF. Относительный срок жизни `EX`/`PX` заменяется на `PXAT`,
+///а у `RESTORE` - на `ABSTTL`, иначе после перезапуска ключ прожил бы дольше
+pub fn record(args: &[Bytes]) -> Vec<Bytes> {
+    let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let options_from = match args.first().map(|name| name.to_ascii_lowercase()) {
+        Some(name) if name == b"set" => 3,
+        Some(name) if name == b"getex" => 2,
+        Some(name) if name == b"restore" => return record_restore(args, unix_ms),
+        _ => return args.to_vec(),
+    };
+
+    let mut record = args[..options_from.min(args.len())].to_vec();
+    let mut options = args.iter().skip(options_from);
+
+    while let Some(option) = options.next() {
+        let millis = match option.to_ascii_lowercase().as_slice() {
+            b"ex" => 1000,
+            b"px" => 1,
+            _ => {
+                record.push(option.clone());
+                continue;
+            }
+        };
+        let Some(ttl) = options.next() else { break };
+        let ttl: u64 = std::str::from_utf8(ttl).ok().and_then(|t| t.parse().ok()).unwrap_or_default();
+        record.push(Bytes::from("pxat"));
+        record.push(Bytes::from((unix_ms + ttl.saturating_mul(millis)).to_string()));
+    }
+
+    record
+}
+
+fn record_restore(args: &[Bytes], unix_ms: u64) -> Vec<Bytes> {
+    let absttl = args.iter().skip(4).any(|arg| arg.eq_ignore_ascii_case(b"absttl"));
+    let ttl: u64 = args.get(2).and_then(|ttl| std::str::from_utf8(ttl).ok()?.parse().ok()).unwrap_or_default();
+    if absttl || ttl == 0 {
+        return args.to_vec();
+    }
+
+    let mut record = args.to_vec();
+    record[2] = Bytes::from((unix_ms + ttl).to_string());
+    record.push(Bytes::from("absttl"));
+    record
+}
+
+///Журнал команд записи. Файл начинается со снимка данных на момент включения
+///или последней перезаписи, за ним идут команды в формате RESP.
+///Пока идет перезапись, новые команды копятся ещё и в `rewrite`
+#[derive(Debug)]
+pub struct Aof {
+    file: Option<File>,
+    path: PathBuf,
+    fsync: Fsync,
+    size: u64,
+    base_size: u64,
+    rewrite: Option<Vec<u8>>,
+    ///Включен ли журнал. Читается без блокировки журнала
+    enabled: Arc<AtomicBool>,
+}
+
+impl Default for Aof {
+    fn default() -> Self {
+        Self {
+            file: None,
+            path: PathBuf::new(),
+            fsync: Fsync::EverySec,
+            size: 0,
+            base_size: 0,
+            rewrite: None,
+            enabled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Aof {
+    pub fn is_enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    ///Флаг включения журнала, по которому команды решают, нужно ли ждать журнал
+    pub fn enabled(&self) -> Arc<AtomicBool> {
+        self.enabled.clone()
+    }
+
+    pub fn set_fsync(&mut self, fsync: Fsync) {
+        self.fsync = fsync;
+    }
+
+    ///Продолжает запись в существующий файл после его чтения при запуске
+    pub fn open(&mut self, path: &Path) -> Result<(), CashError> {
+        let file = OpenOptions::new().append(true).open(path).map_err(|e| io_error(path, e))?;
+        self.size = file.metadata().map_err(|e| io_error(path, e))?.len();
+        self.base_size = self.size;
+        self.file = Some(file);
+        self.path = path.to_path_buf();
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    ///Начинает новый журнал со снимка текущих данных
+    pub fn create<E: StorageEngine>(&mut self, path: &Path, storage: &Sharded<E>) -> Result<(), CashError> {
+        write_atomic(path, &storage.snapshot()?.encode()).map_err(|e| io_error(path, e))?;
+        self.open(path)
+    }
+
+    ///Закрывает журнал. Незаконченная перезапись отменяется
+    pub fn close(&mut self) {
+        self.sync();
+        self.file = None;
+        self.rewrite = None;
+        self.enabled.store(false, Ordering::SeqCst);
+    }
+
+    pub fn append(&mut self, args: &[Bytes]) -> Result<(), CashError> {
+        let Some(file) = &mut self.file else { return Ok(()) };
+        let record = encode(args);
+
+        if let Some(buffer) = &mut self.rewrite {
+            buffer.extend_from_slice(&record);
+        }
+        file.write_all(&record).map_err(|e| io_error(&self.path, e))?;
+        self.size += record.len() as u64;
+        if self.fsync == Fsync::Always {
+            file.sync_data().map_err(|e| io_error(&self.path, e))?;
+        }
+        Ok(())
+    }
+
+    ///Записывает выполненную команду записи по её ответу. Ошибки не записываются.
+    ///`THROTTLE` записывается своим результатом, `SET` состояния ограничителя с `PXAT`:
+    ///при чтении журнала сама команда пересчитала бы лимит по текущему времени.
+    ///Отклоненный запрос состояние не меняет и не записывается
+    pub fn log<E: StorageEngine>(&mut self, args: &[Bytes], reply: &Frame, storage: &Sharded<E>) -> Result<(), CashError> {
+        if matches!(reply, Frame::Error(_)) {
+            return Ok(());
+        }
+        if !args.first().is_some_and(|name| name.eq_ignore_ascii_case(b"throttle")) {
+            return self.append(&record(args));
+        }
+
+        let limited = matches!(reply, Frame::Array(frames) if frames.first() == Some(&Frame::Integer(1)));
+        let Some(key) = args.get(1).filter(|_| !limited) else { return Ok(()) };
+        let mut store = storage.shard(key)?;
+        let Some(tat) = store.get(key)? else { return Ok(()) };
+
+        let mut record = vec![Bytes::from("set"), key.clone(), tat];
+        if let Some(at) = store.deadline(key) {
+            record.push(Bytes::from("pxat"));
+            record.push(Bytes::from(unix_ms(at, Instant::now()).to_string()));
+        }
+        drop(store);
+        self.append(&record)
+    }
+
+    ///Сброс на диск для `appendfsync everysec`
+    pub fn sync(&mut self) {
+        if let Some(file) = &self.file {
+            if let Err(err) = file.sync_data() {
+                log::error!("{}", io_error(&self.path, err));
+            }
+        }
+    }
+
+    pub fn fsync(&self) -> Fsync {
+        self.fsync
+    }
+
+    pub fn is_rewriting(&self) -> bool {
+        self.rewrite.is_some()
+    }
+
+    ///Пора ли перезаписать журнал: файл не меньше `min_size` и вырос на `percentage` процентов
+    ///с последней перезаписи. `percentage = 0` отключает автоматическую перезапись
+    pub fn needs_rewrite(&self, percentage: u64, min_size: u64) -> bool {
+        let growth = self.size.saturating_sub(self.base_size) * 100 / self.base_size.max(1);
+        self.is_enabled() && !self.is_rewriting() && percentage > 0 && self.size >= min_size && growth >= percentage
+    }
+}
+
+///Запускает перезапись AOF в фоне. Снимок данных берется под блокировками журнала и хранилища,
+///поэтому каждая команда попадает либо в снимок, либо в буфер перезаписи.
+///Новый файл пишется без блокировок, затем в него дописывается буфер и он заменяет старый
+pub async fn bgrewrite<E: StorageEngine>(aof: Arc<tokio::sync::Mutex<Aof>>, storage: Storage<E>) -> Result<(), CashError> {
+    let (snapshot, path) = {
+        let mut log = aof.lock().await;
+        if !log.is_enabled() {
+            return Err(Error::Storage("append only file is turned off".to_string()));
+        }
+        if log.is_rewriting() {
+            return Err(Error::Storage("Background append only file rewriting already in progress".to_string()));
+        }
+
+        let snapshot = storage.snapshot()?;
+        log.rewrite = Some(vec![]);
+        (snapshot, log.path.clone())
+    };
+
+    tokio::spawn(async move {
+        let tmp = path.with_extension("rewrite");
+        let written = {
+            let tmp = tmp.clone();
+            tokio::task::spawn_blocking(move || write_atomic(&tmp, &snapshot.encode())).await
+        };
+
+        let mut log = aof.lock().await;
+        let result = match written {
+            Ok(Ok(())) => swap(&mut log, &tmp, &path),
+            Ok(Err(err)) => Err(io_error(&tmp, err)),
+            Err(err) => Err(Error::Storage(err.to_string())),
+        };
+        log.rewrite = None;
+
+        match result {
+            Ok(()) => log::info!("Background AOF rewrite finished successfully"),
+            Err(err) => {
+                log::error!("Background AOF rewrite failed: {}", err);
+                let _ = std::fs::remove_file(&tmp);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+///Дописывает команды, пришедшие во время перезаписи, и заменяет журнал новым файлом
+fn swap(log: &mut Aof, tmp: &Path, path: &Path) -> Result<(), CashError> {
+    let Some(buffer) = log.rewrite.take() else {
+        return Err(Error::Storage("append only file was turned off during the rewrite".to_string()));
+    };
+
+    let mut file = OpenOptions::new().append(true).open(tmp).map_err(|e| io_error(tmp, e))?;
+    file.write_all(&buffer).map_err(|e| io_error(tmp, e))?;
+    file.sync_all().map_err(|e| io_error(tmp, e))?;
+    std::fs::rename(tmp, path).map_err(|e| io_error(path, e))?;
+
+    log.open(path)
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> Error {
+    Error::Storage(format!("AOF '{}': {}", path.display(), err))
+}
+
+///Запись AOF, найденная при разборе файла
+pub enum Entry {
+    Snapshot(Snapshot),
+    ///Команда и её смещение в файле
+    Command(usize, Frame),
+}
+
+///Итог разбора AOF
+#[derive(Debug, PartialEq)]
+pub struct Scan {
+    pub commands: usize,
+    ///Длина начала файла, состоящего из целых команд
+    pub valid: usize,
+    ///Повреждение после `valid`. Если его нет, а `valid` меньше длины файла,
+    ///последняя команда недописана
+    pub corruption: Option<CashError>,
+}
+
+///Разбирает AOF и передает записи в `visit` по порядку.
+///Поврежденный снимок в начале - ошибка: после него нечего сохранить
+pub fn scan(bytes: &[u8], mut visit: impl FnMut(Entry) -> Result<(), CashError>) -> Result<Scan, CashError> {
+    let mut offset = 0;
+    if bytes.starts_with(MAGIC) {
+        let (snapshot, len) = Snapshot::decode_prefix(bytes)?;
+        visit(Entry::Snapshot(snapshot))?;
+        offset = len;
+    }
+
+    let mut buff = Cursor::new(BytesMut::from(&bytes[offset..]));
+    let mut scan = Scan { commands: 0, valid: offset, corruption: None };
+
+    loop {
+        let start = buff.position() as usize;
+        if start == buff.get_ref().len() {
+            break;
+        }
+
+        match Frame::try_frame(&mut buff) {
+            Ok(frame) => visit(Entry::Command(offset + start, frame))?,
+            Err(CashError::Incomplete) => break,
+            Err(err) => {
+                scan.corruption = Some(err);
+                break;
+            }
+        };
+        scan.commands += 1;
+        scan.valid = offset + buff.position() as usize;
+    }
+
+    Ok(scan)
+}
+
+///Обрезает файл до `len` байт
+pub fn truncate(path: &Path, len: usize) -> Result<(), CashError> {
+    let file = OpenOptions::new().write(true).open(path).map_err(|e| io_error(path, e))?;
+    file.set_len(len as u64).map_err(|e| io_error(path, e))
+}
+
+///Восстанавливает хранилище из AOF: снимок в начале файла и команды после него.
+///Недописанная последняя команда (сбой во время записи) отбрасывается, и файл обрезается до неё.
+///Возвращает число выполненных команд, `None` - файла нет
+pub fn replay<E: StorageEngine>(path: &Path, storage: &Sharded<E>) -> Result<Option<usize>, CashError> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(io_error(path, err)),
+    };
+
+    let scan = scan(&bytes, |entry| {
+        match entry {
+            Entry::Snapshot(snapshot) => {
+                snapshot.restore(storage)?;
+            }
+            Entry::Command(at, frame) => {
+                let args = frame_args(&frame);
+                if let Err(err) = Command::from_frame(frame).and_then(|command| dispatch(storage, command, &args, |_: &[u8]| true)) {
+                    log::warn!("AOF command at byte {} failed: {}", at, err);
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    if let Some(err) = scan.corruption {
+        return Err(Error::Storage(format!("AOF is corrupted at byte {}: {}", scan.valid, err)));
+    }
+    if scan.valid < bytes.len() {
+        log::warn!(
+            "AOF '{}' ends with an incomplete command, truncating {} bytes",
+            path.display(),
+            bytes.len() - scan.valid
+        );
+        truncate(path, scan.valid)?;
+    }
+
+    storage.saved(storage.dirty()?)?;
+    Ok(Some(scan.commands))
+}
+
+#[cfg(test)]
+mod aof_tests {
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.aof", name, std::process::id()))
+    }
+
+    fn args(line: &str) -> Vec<Bytes> {
+        line.split_whitespace().map(|arg| Bytes::from(arg.to_string())).collect()
+    }
+
+    #[test]
+    fn relative_expiry_becomes_absolute() {
+        let set = record(&args("set k ex nx px 1000"));
+
+        assert_eq!(args("set k ex nx pxat")[..], set[..5]);
+        let at: u64 = std::str::from_utf8(&set[5]).unwrap().parse().unwrap();
+        let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        assert!(at > unix_ms && at <= unix_ms + 1000);
+
+        assert_eq!(args("append k v"), record(&args("append k v")));
+
+        let restore = record(&args("restore k 1000 payload replace"));
+        assert_eq!(args("restore k"), restore[..2]);
+        assert_eq!(args("payload replace absttl")[..], restore[3..]);
+        assert_eq!(args("restore k 0 payload"), record(&args("restore k 0 payload")));
+    }
+
+    #[test]
+    fn replay_after_snapshot_and_truncated_tail() {
+        let path = path("replay");
+        let storage = Sharded::default();
+        storage.shard(b"base").unwrap().set(Bytes::from("base"), Bytes::from("1")).unwrap();
+
+        let mut aof = Aof::default();
+        aof.create(&path, &storage).unwrap();
+        aof.append(&args("set a 1")).unwrap();
+        aof.append(&args("append a 2")).unwrap();
+        aof.close();
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"*3\r\n$3\r\nset\r\n$1\r\nb").unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        let storage = Sharded::default();
+        assert_eq!(Ok(Some(2)), replay(&path, &storage));
+        assert_eq!(Ok(Some(Bytes::from("1"))), storage.shard(b"base").unwrap().get(b"base"));
+        assert_eq!(Ok(Some(Bytes::from("12"))), storage.shard(b"a").unwrap().get(b"a"));
+        assert!(!storage.shard(b"b").unwrap().exists(b"b"));
+        assert_eq!(len - 18, std::fs::metadata(&path).unwrap().len());
+
+        std::fs::write(&path, b"*1\r\n:1\r\n").unwrap();
+        assert!(replay(&path, &Sharded::default()).is_ok());
+        std::fs::write(&path, b"?garbage").unwrap();
+        assert!(replay(&path, &Sharded::default()).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn throttle_is_logged_as_its_state() {
+        let path = path("throttle");
+        let storage = Sharded::default();
+        let mut aof = Aof::default();
+        aof.create(&path, &storage).unwrap();
+
+        let throttle = args("throttle api:1 1 1 60");
+        for _ in 0..3 {
+            let command = Command::from_frame(Frame::Array(throttle.iter().cloned().map(Frame::BulkString).collect())).unwrap();
+            let reply = dispatch(&storage, command, &throttle, |_: &[u8]| true).unwrap();
+            aof.log(&throttle, &reply, &storage).unwrap();
+        }
+        aof.close();
+
+        //два разрешенных запроса записаны как `SET`, отклоненный не записан
+        let mut commands = vec![];
+        scan(&std::fs::read(&path).unwrap(), |entry| {
+            if let Entry::Command(_, frame) = entry {
+                commands.push(frame_args(&frame));
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(2, commands.len());
+        assert!(commands.iter().all(|cmd| cmd[0] == "set" && cmd[3] == "pxat"));
+
+        let restored = Sharded::default();
+        replay(&path, &restored).unwrap();
+        let mut source = storage.shard(b"api:1").unwrap();
+        let mut target = restored.shard(b"api:1").unwrap();
+        assert_eq!(source.get(b"api:1"), target.get(b"api:1"));
+        let (at, restored_at) = (source.deadline(b"api:1").unwrap(), target.deadline(b"api:1").unwrap());
+        assert!(at.max(restored_at) - at.min(restored_at) < std::time::Duration::from_millis(50));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rewrite_keeps_concurrent_writes() {
+        let path = path("rewrite");
+        let storage: Storage = Arc::new(Sharded::default());
+        let aof = Arc::new(tokio::sync::Mutex::new(Aof::default()));
+        aof.lock().await.create(&path, &storage).unwrap();
+
+        for i in 0..100 {
+            let cmd = args(&format!("set k {}", i));
+            let command = Command::from_frame(Frame::Array(cmd.iter().cloned().map(Frame::BulkString).collect())).unwrap();
+            dispatch(&storage, command, &cmd, |_: &[u8]| true).unwrap();
+            aof.lock().await.append(&cmd).unwrap();
+        }
+        let before = std::fs::metadata(&path).unwrap().len();
+
+        bgrewrite(aof.clone(), storage.clone()).await.unwrap();
+        assert!(bgrewrite(aof.clone(), storage.clone()).await.is_err());
+        storage.shard(b"late").unwrap().set(Bytes::from("late"), Bytes::from("1")).unwrap();
+        aof.lock().await.append(&args("set late 1")).unwrap();
+
+        while aof.lock().await.is_rewriting() {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        assert!(std::fs::metadata(&path).unwrap().len() < before);
+
+        let restored = Sharded::default();
+        assert_eq!(Ok(Some(1)), replay(&path, &restored));
+        assert_eq!(Ok(Some(Bytes::from("99"))), restored.shard(b"k").unwrap().get(b"k"));
+        assert_eq!(Ok(Some(Bytes::from("1"))), restored.shard(b"late").unwrap().get(b"late"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn growth_triggers_rewrite() {
+        let aof = Aof { file: None, size: 250, base_size: 100, ..Default::default() };
+        assert!(!aof.needs_rewrite(100, 0));
+
+        let path = path("growth");
+        let mut aof = Aof::default();
+        aof.create(&path, &Sharded::default()).unwrap();
+        aof.base_size = 100;
+        aof.size = 250;
+        assert!(aof.needs_rewrite(100, 200));
+        assert!(!aof.needs_rewrite(200, 0));
+        assert!(!aof.needs_rewrite(100, 300));
+        assert!(!aof.needs_rewrite(0, 0));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn enabled_flag_follows_the_file() {
+        let path = path("enabled");
+        let mut aof = Aof::default();
+        let enabled = aof.enabled();
+        assert!(!enabled.load(Ordering::SeqCst));
+
+        aof.create(&path, &Sharded::default()).unwrap();
+        assert!(enabled.load(Ordering::SeqCst));
+        aof.close();
+        assert!(!enabled.load(Ordering::SeqCst));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,295 @@
+use bytes::Bytes;
+use crate::core::command::bitmap::{BitOp, BitType, BitUnit, BitmapCmd, FieldOp, Overflow};
+use crate::core::error::CashError;
+use crate::core::frames::Frame;
+use crate::storage::engine::StorageEngine;
+
+pub fn execute(store: &mut dyn StorageEngine, cmd: BitmapCmd) -> Result<Frame, CashError> {
+    match cmd {
+        BitmapCmd::SetBit(key, offset, bit) => {
+            let mut bytes = store.get(&key)?.unwrap_or_default().to_vec();
+            let old = get_bit(&bytes, offset);
+
+            let byte = (offset / 8) as usize;
+            if bytes.len() <= byte {
+                bytes.resize(byte + 1, 0);
+            }
+            let mask = 0x80 >> (offset % 8);
+            if bit {
+                bytes[byte] |= mask;
+            } else {
+                bytes[byte] &= !mask;
+            }
+
+            store.update(key, Bytes::from(bytes))?;
+            Ok(Frame::Integer(old as i64))
+        }
+        BitmapCmd::GetBit(key, offset) => {
+            let bytes = store.get(&key)?.unwrap_or_default();
+            Ok(Frame::Integer(get_bit(&bytes, offset) as i64))
+        }
+        BitmapCmd::BitCount(key, range) => {
+            let bytes = store.get(&key)?.unwrap_or_default();
+            Ok(Frame::Integer(bitcount(&bytes, range) as i64))
+        }
+        BitmapCmd::BitPos(key, bit, start, end, unit) => {
+            let bytes = store.get(&key)?.unwrap_or_default();
+            Ok(Frame::Integer(bitpos(&bytes, bit, start, end, unit)))
+        }
+        BitmapCmd::BitOp(op, dest, keys) => {
+            let sources = keys
+                .iter()
+                .map(|k| store.get(k).map(Option::unwrap_or_default))
+                .collect::<Result<Vec<Bytes>, _>>()?;
+            let result = bitop(op, &sources);
+            let len = result.len();
+
+            if result.is_empty() {
+                store.delete(&dest);
+            } else {
+                store.set(dest, Bytes::from(result))?;
+            }
+            Ok(Frame::Integer(len as i64))
+        }
+        BitmapCmd::BitField(key, ops) => {
+            let mut bytes = store.get(&key)?.unwrap_or_default().to_vec();
+            let mut overflow = Overflow::Wrap;
+            let mut results = vec![];
+            let mut changed = false;
+
+            for op in ops {
+                match op {
+                    FieldOp::Overflow(mode) => overflow = mode,
+                    FieldOp::Get(ty, offset) => results.push(Frame::Integer(get_field(&bytes, ty, offset))),
+                    FieldOp::Set(ty, offset, value) => {
+                        let old = get_field(&bytes, ty, offset);
+                        match fit(value as i128, ty, overflow) {
+                            Some(value) => {
+                                set_field(&mut bytes, ty, offset, value);
+                                changed = true;
+                                results.push(Frame::Integer(old));
+                            }
+                            None => results.push(Frame::Null),
+                        }
+                    }
+                    FieldOp::IncrBy(ty, offset, incr) => {
+                        let old = get_field(&bytes, ty, offset);
+                        match fit(old as i128 + incr as i128, ty, overflow) {
+                            Some(value) => {
+                                set_field(&mut bytes, ty, offset, value);
+                                changed = true;
+                                results.push(Frame::Integer(value));
+                            }
+                            None => results.push(Frame::Null),
+                        }
+                    }
+                }
+            }
+
+            if changed {
+                store.update(key, Bytes::from(bytes))?;
+            }
+            Ok(Frame::Array(results))
+        }
+    }
+}
+
+fn get_bit(bytes: &[u8], offset: u64) -> bool {
+    bytes
+        .get((offset / 8) as usize)
+        .is_some_and(|byte| byte & (0x80 >> (offset % 8)) != 0)
+}
+
+///Приводит `start` и `end` к границам `0..len` как `GETRANGE`.
+///`None` - диапазон пуст
+fn clamp(start: i64, end: i64, len: i64) -> Option<(i64, i64)> {
+    if len == 0 {
+        return None;
+    }
+
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let end = if end < 0 { len + end } else { end.min(len - 1) };
+
+    (start <= end && end >= 0).then_some((start, end))
+}
+
+///Количество установленных битов в диапазоне байт или битов
+fn bitcount(bytes: &[u8], range: Option<(i64, i64, BitUnit)>) -> u64 {
+    match range {
+        None => bytes.iter().map(|b| b.count_ones() as u64).sum(),
+        Some((start, end, BitUnit::Byte)) => match clamp(start, end, bytes.len() as i64) {
+            Some((start, end)) => bytes[start as usize..=end as usize].iter().map(|b| b.count_ones() as u64).sum(),
+            None => 0,
+        },
+        Some((start, end, BitUnit::Bit)) => match clamp(start, end, bytes.len() as i64 * 8) {
+            Some((start, end)) => (start as u64..=end as u64).filter(|&bit| get_bit(bytes, bit)).count() as u64,
+            None => 0,
+        },
+    }
+}
+
+///Позиция первого бита `bit` в диапазоне.
+///Если ищется 0, а конец диапазона не задан, справа от строки считаются нули,
+///поэтому для строки из единиц возвращается позиция сразу за ней
+fn bitpos(bytes: &[u8], bit: bool, start: Option<i64>, end: Option<i64>, unit: BitUnit) -> i64 {
+    let bits = bytes.len() as i64 * 8;
+    if bytes.is_empty() {
+        return if bit { -1 } else { 0 };
+    }
+
+    let (scale, len) = match unit {
+        BitUnit::Byte => (8, bytes.len() as i64),
+        BitUnit::Bit => (1, bits),
+    };
+
+    let Some((start, end_unit)) = clamp(start.unwrap_or(0), end.unwrap_or(-1), len) else {
+        return -1;
+    };
+    let first = start * scale;
+    let last = (end_unit + 1) * scale - 1;
+
+    match (first..=last).find(|&i| get_bit(bytes, i as u64) == bit) {
+        Some(position) => position,
+        None if !bit && end.is_none() => bits,
+        None => -1,
+    }
+}
+
+///Побитовая операция над строками. Короткие строки дополняются нулями
+fn bitop(op: BitOp, sources: &[Bytes]) -> Vec<u8> {
+    let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+    let byte = |source: &Bytes, i: usize| source.get(i).copied().unwrap_or(0);
+
+    (0..len)
+        .map(|i| match op {
+            BitOp::Not => !byte(&sources[0], i),
+            BitOp::And => sources.iter().fold(0xff, |acc, s| acc & byte(s, i)),
+            BitOp::Or => sources.iter().fold(0, |acc, s| acc | byte(s, i)),
+            BitOp::Xor => sources.iter().fold(0, |acc, s| acc ^ byte(s, i)),
+        })
+        .collect()
+}
+
+///Значение поля `BITFIELD`. Байты за концом строки считаются нулями
+fn get_field(bytes: &[u8], ty: BitType, offset: u64) -> i64 {
+    let mut raw: u64 = 0;
+    for i in 0..ty.bits as u64 {
+        raw = (raw << 1) | get_bit(bytes, offset + i) as u64;
+    }
+
+    if ty.signed && ty.bits < 64 && raw & (1 << (ty.bits - 1)) != 0 {
+        (raw | (u64::MAX << ty.bits)) as i64
+    } else {
+        raw as i64
+    }
+}
+
+fn set_field(bytes: &mut Vec<u8>, ty: BitType, offset: u64, value: i64) {
+    let last_byte = ((offset + ty.bits as u64 - 1) / 8) as usize;
+    if bytes.len() <= last_byte {
+        bytes.resize(last_byte + 1, 0);
+    }
+
+    let raw = value as u64;
+    for i in 0..ty.bits as u64 {
+        let bit = raw >> (ty.bits as u64 - 1 - i) & 1 == 1;
+        let position = offset + i;
+        let mask = 0x80 >> (position % 8);
+        if bit {
+            bytes[(position / 8) as usize] |= mask;
+        } else {
+            bytes[(position / 8) as usize] &= !mask;
+        }
+    }
+}
+
+///Приводит значение к диапазону типа по политике переполнения.
+///`None` - переполнение в режиме `FAIL`, поле не меняется
+fn fit(value: i128, ty: BitType, overflow: Overflow) -> Option<i64> {
+    let (min, max) = (ty.min(), ty.max());
+    if (min..=max).contains(&value) {
+        return Some(value as i64);
+    }
+
+    match overflow {
+        Overflow::Wrap => Some(((value - min).rem_euclid(1i128 << ty.bits) + min) as i64),
+        Overflow::Sat => Some(value.clamp(min, max) as i64),
+        Overflow::Fail => None,
+    }
+}
+
+#[cfg(test)]
+mod bitmap_tests {
+    use crate::storage::Store;
+    use super::*;
+
+    fn u8t() -> BitType {
+        BitType { signed: false, bits: 8 }
+    }
+
+    #[test]
+    fn bitcount_ranges() {
+        let bytes = b"foobar";
+
+        assert_eq!(26, bitcount(bytes, None));
+        assert_eq!(4, bitcount(bytes, Some((0, 0, BitUnit::Byte))));
+        assert_eq!(6, bitcount(bytes, Some((1, 1, BitUnit::Byte))));
+        assert_eq!(17, bitcount(bytes, Some((5, 30, BitUnit::Bit))));
+    }
+
+    #[test]
+    fn bitpos_redis_examples() {
+        let ones = [0xff, 0xf0, 0x00];
+        assert_eq!(12, bitpos(&ones, false, None, None, BitUnit::Byte));
+
+        let bytes = [0x00, 0xff, 0xf0];
+        assert_eq!(8, bitpos(&bytes, true, Some(0), None, BitUnit::Byte));
+        assert_eq!(16, bitpos(&bytes, true, Some(2), None, BitUnit::Byte));
+        assert_eq!(16, bitpos(&bytes, true, Some(2), Some(-1), BitUnit::Byte));
+        assert_eq!(-1, bitpos(&[0x00], true, None, None, BitUnit::Byte));
+        assert_eq!(24, bitpos(&[0xff, 0xff, 0xff], false, None, None, BitUnit::Byte));
+        assert_eq!(-1, bitpos(&[0xff, 0xff, 0xff], false, Some(0), Some(-1), BitUnit::Byte));
+    }
+
+    #[test]
+    fn bitop_pads_shorter_strings() {
+        let a = Bytes::from_static(&[0b1100_0000, 0xff]);
+        let b = Bytes::from_static(&[0b1010_0000]);
+
+        assert_eq!(vec![0b1000_0000, 0x00], bitop(BitOp::And, &[a.clone(), b.clone()]));
+        assert_eq!(vec![0b1110_0000, 0xff], bitop(BitOp::Or, &[a.clone(), b.clone()]));
+        assert_eq!(vec![0b0011_1111, 0x00], bitop(BitOp::Not, &[a]));
+    }
+
+    #[test]
+    fn bitfield_overflow() {
+        assert_eq!(Some(4), fit(260, u8t(), Overflow::Wrap));
+        assert_eq!(Some(255), fit(260, u8t(), Overflow::Sat));
+        assert_eq!(None, fit(260, u8t(), Overflow::Fail));
+
+        let i8t = BitType { signed: true, bits: 8 };
+        assert_eq!(Some(-128), fit(128, i8t, Overflow::Wrap));
+        assert_eq!(Some(-128), fit(-200, i8t, Overflow::Sat));
+    }
+
+    #[test]
+    fn bitfield_fields() {
+        let mut bytes = vec![];
+        set_field(&mut bytes, BitType { signed: true, bits: 5 }, 3, -3);
+        assert_eq!(-3, get_field(&bytes, BitType { signed: true, bits: 5 }, 3));
+        assert_eq!(29, get_field(&bytes, BitType { signed: false, bits: 5 }, 3));
+
+        set_field(&mut bytes, BitType { signed: true, bits: 64 }, 8, i64::MIN);
+        assert_eq!(i64::MIN, get_field(&bytes, BitType { signed: true, bits: 64 }, 8));
+    }
+
+    #[test]
+    fn setbit_grows_value() {
+        let mut store = Store::default();
+        let key = Bytes::from("k");
+
+        assert_eq!(Ok(Frame::Integer(0)), execute(&mut store, BitmapCmd::SetBit(key.clone(), 9, true)));
+        assert_eq!(Ok(Some(Bytes::from_static(&[0x00, 0x40]))), store.get(&key));
+        assert_eq!(Ok(Frame::Integer(1)), execute(&mut store, BitmapCmd::GetBit(key, 9)));
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::core::command::Command;
+use crate::core::error::CashError;
+use crate::server::aof::{scan, Entry, Scan};
+use crate::storage::snapshot::{Snapshot, MAGIC};
+
+///Результат проверки файла `casher-check`
+#[derive(Debug)]
+pub enum Check {
+    Snapshot(Snapshot),
+    Aof {
+        preamble: Option<Snapshot>,
+        scan: Scan,
+        ///Смещения команд, которые сервер не распознает. При загрузке они пропускаются
+        unknown: Vec<usize>,
+        len: usize,
+    },
+}
+
+impl Check {
+    ///Файл можно загрузить без потерь
+    pub fn is_ok(&self) -> bool {
+        match self {
+            Check::Snapshot(_) => true,
+            Check::Aof { scan, len, .. } => scan.corruption.is_none() && scan.valid == *len,
+        }
+    }
+
+    ///Снимок файла или преамбула AOF
+    pub fn snapshot(&self) -> Option<&Snapshot> {
+        match self {
+            Check::Snapshot(snapshot) => Some(snapshot),
+            Check::Aof { preamble, .. } => preamble.as_ref(),
+        }
+    }
+}
+
+///AOF узнается по расширению `.aof`: его преамбула начинается так же, как снимок
+pub fn is_aof(path: &Path, bytes: &[u8]) -> bool {
+    path.extension().is_some_and(|ext| ext == "aof") || !bytes.starts_with(MAGIC)
+}
+
+///Проверяет структуру и контрольные суммы. Поврежденный снимок (в том числе преамбула AOF) - ошибка
+///с местом повреждения, повреждение среди команд AOF возвращается в `Scan`
+pub fn check(path: &Path, bytes: &[u8]) -> Result<Check, CashError> {
+    if !is_aof(path, bytes) {
+        return Ok(Check::Snapshot(Snapshot::decode(bytes)?));
+    }
+
+    let mut preamble = None;
+    let mut unknown = vec![];
+    let scan = scan(bytes, |entry| {
+        match entry {
+            Entry::Snapshot(snapshot) => preamble = Some(snapshot),
+            Entry::Command(at, frame) => {
+                if Command::from_frame(frame).is_err() {
+                    unknown.push(at);
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(Check::Aof { preamble, scan, unknown, len: bytes.len() })
+}
+
+///Строки `ключ тип размер ttl`: размер значения в байтах, ttl в секундах,
+///`-1` - без срока, `expired` - срок истек, ключ не будет загружен
+pub fn dump(snapshot: &Snapshot) -> Vec<String> {
+    let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+
+    snapshot
+        .entries
+        .iter()
+        .map(|(key, value, expires_at)| {
+            let ttl = match expires_at {
+                None => "-1".to_string(),
+                Some(at) if *at <= unix_ms => "expired".to_string(),
+                Some(at) => (at - unix_ms).div_ceil(1000).to_string(),
+            };
+            format!("{} {} {} {}", String::from_utf8_lossy(key), value.type_name(), value.to_bytes().len(), ttl)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod check_tests {
+    use bytes::Bytes;
+    use crate::storage::engine::StorageEngine;
+    use crate::storage::{Store, Value};
+    use super::*;
+
+    fn aof(commands: &[u8]) -> Vec<u8> {
+        let mut bytes = Snapshot::default().encode();
+        bytes.extend_from_slice(commands);
+        bytes
+    }
+
+    #[test]
+    fn finds_damage_in_aof() {
+        let path = Path::new("appendonly.aof");
+
+        let ok = aof(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n");
+        let valid = check(path, &ok).unwrap();
+        assert!(valid.is_ok());
+        assert!(valid.snapshot().is_some());
+
+        let incomplete = aof(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n*2\r\n$3\r\nget");
+        let Check::Aof { scan, .. } = check(path, &incomplete).unwrap() else { panic!() };
+        assert_eq!(ok.len(), scan.valid);
+        assert_eq!(None, scan.corruption);
+
+        let damaged = aof(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n?\r\n*1\r\n$4\r\nping\r\n");
+        let Check::Aof { scan, .. } = check(path, &damaged).unwrap() else { panic!() };
+        assert_eq!((1, ok.len()), (scan.commands, scan.valid));
+        assert!(scan.corruption.is_some());
+
+        let Check::Aof { unknown, .. } = check(path, b"*1\r\n$4\r\nnope\r\n").unwrap() else { panic!() };
+        assert_eq!(vec![0], unknown);
+    }
+
+    #[test]
+    fn reports_snapshot_offset() {
+        let mut store = Store::default();
+        store.set(Bytes::from("k"), Bytes::from("v")).unwrap();
+        let mut bytes = Snapshot::capture(&mut store).unwrap().encode();
+        bytes[8] = 9;
+
+        let err = check(Path::new("dump.cdb"), &bytes).unwrap_err();
+        assert_eq!(CashError::Storage("snapshot is corrupted at byte 8".to_string()), err);
+    }
+
+    #[test]
+    fn dumps_keys() {
+        let snapshot = Snapshot {
+            entries: vec![
+                (Bytes::from("a"), Value::String(Bytes::from("xyz")), None),
+                (Bytes::from("b"), Value::String(Bytes::from("x")), Some(1)),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(vec!["a string 3 -1", "b string 1 expired"], dump(&snapshot));
+    }
+}
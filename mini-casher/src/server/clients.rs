@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use bytes::Bytes;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use crate::core::command::client::{ClientCmd, Kill, PauseMode};
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+
+///Реестр открытых соединений сервера.
+///Каждое соединение регистрируется в `handler` и получает уникальный id,
+///по которому его можно найти в `CLIENT LIST` и закрыть через `CLIENT KILL`.
+#[derive(Debug, Default)]
+pub struct Clients {
+    next_id: AtomicU64,
+    clients: Mutex<HashMap<u64, ClientInfo>>,
+    pause: Mutex<Option<(Instant, PauseMode)>>,
+    unpause: Notify,
+}
+
+#[derive(Debug)]
+pub struct ClientInfo {
+    id: u64,
+    addr: SocketAddr,
+    name: Option<String>,
+    user: String,
+    created: Instant,
+    last_interaction: Instant,
+    db: u64,
+    last_command: &'static str,
+    kill: Arc<Notify>,
+}
+
+impl ClientInfo {
+    ///Строка в формате `CLIENT LIST`
+    fn line(&self, now: Instant) -> String {
+        format!(
+            "id={} addr={} name={} age={} idle={} db={} user={} cmd={}",
+            self.id,
+            self.addr,
+            self.name.as_deref().unwrap_or(""),
+            now.duration_since(self.created).as_secs(),
+            now.duration_since(self.last_interaction).as_secs(),
+            self.db,
+            self.user,
+            self.last_command,
+        )
+    }
+}
+
+impl Clients {
+    ///Регистрирует соединение.
+    ///Возвращает id клиента и сигнал, который срабатывает при `CLIENT KILL`
+    pub fn register(&self, addr: SocketAddr) -> Result<(u64, Arc<Notify>), CashError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let kill = Arc::new(Notify::new());
+        let now = Instant::now();
+
+        self.clients.lock()?.insert(id, ClientInfo {
+            id,
+            addr,
+            name: None,
+            user: "default".to_string(),
+            created: now,
+            last_interaction: now,
+            db: 0,
+            last_command: "NULL",
+            kill: kill.clone(),
+        });
+
+        Ok((id, kill))
+    }
+
+    pub fn unregister(&self, id: u64) -> Result<(), CashError> {
+        self.clients.lock()?.remove(&id);
+        Ok(())
+    }
+
+    ///Количество подключенных клиентов, проверяется по `maxclients`
+    pub fn count(&self) -> Result<usize, CashError> {
+        Ok(self.clients.lock()?.len())
+    }
+
+    ///Запоминает пользователя после успешного `AUTH`
+    pub fn set_user(&self, id: u64, user: &str) -> Result<(), CashError> {
+        if let Some(client) = self.clients.lock()?.get_mut(&id) {
+            client.user = user.to_string();
+        }
+        Ok(())
+    }
+
+    pub fn name(&self, id: u64) -> Result<String, CashError> {
+        let clients = self.clients.lock()?;
+        Ok(clients.get(&id).and_then(|c| c.name.clone()).unwrap_or_default())
+    }
+
+    ///Обновляет время последней активности и имя последней команды
+    pub fn touch(&self, id: u64, command: &'static str) -> Result<(), CashError> {
+        if let Some(client) = self.clients.lock()?.get_mut(&id) {
+            client.last_interaction = Instant::now();
+            client.last_command = command;
+        }
+        Ok(())
+    }
+
+    ///Ожидает окончания `CLIENT PAUSE`.
+    ///В режиме `WRITE` задерживаются только изменяющие данные команды
+    pub async fn wait_unpaused(&self, write: bool) -> Result<(), CashError> {
+        loop {
+            let deadline = match *self.pause.lock()? {
+                Some((deadline, mode)) if deadline > Instant::now() => {
+                    if mode == PauseMode::Write && !write {
+                        return Ok(());
+                    }
+                    deadline
+                }
+                _ => return Ok(()),
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => {}
+                _ = self.unpause.notified() => {}
+            }
+        }
+    }
+
+    pub fn execute(&self, id: u64, cmd: ClientCmd) -> Result<Frame, CashError> {
+        match cmd {
+            ClientCmd::SetName(name) => {
+                if let Some(client) = self.clients.lock()?.get_mut(&id) {
+                    client.name = if name.is_empty() { None } else { Some(name) };
+                }
+                Ok(Frame::Simple("Ok".to_string()))
+            }
+            ClientCmd::GetName => {
+                let clients = self.clients.lock()?;
+                match clients.get(&id).and_then(|c| c.name.clone()) {
+                    Some(name) => Ok(Frame::BulkString(Bytes::from(name))),
+                    None => Ok(Frame::Null)
+                }
+            }
+            ClientCmd::Id => Ok(Frame::Integer(id as i64)),
+            ClientCmd::List => {
+                let now = Instant::now();
+                let clients = self.clients.lock()?;
+                let mut clients: Vec<&ClientInfo> = clients.values().collect();
+                clients.sort_by_key(|c| c.id);
+
+                let list: String = clients
+                    .iter()
+                    .map(|c| c.line(now) + "\n")
+                    .collect();
+
+                Ok(Frame::BulkString(Bytes::from(list)))
+            }
+            ClientCmd::Kill(kill) => {
+                let killed = self.kill(&kill)?;
+
+                if !kill.is_legacy() {
+                    Ok(Frame::Integer(killed as i64))
+                } else if killed > 0 {
+                    Ok(Frame::Simple("Ok".to_string()))
+                } else {
+                    Err(Error::CommandParse("no such client".to_string()))
+                }
+            }
+            ClientCmd::Pause(pause) => {
+                let deadline = Instant::now() + Duration::from_millis(pause.millis());
+                *self.pause.lock()? = Some((deadline, pause.mode()));
+                Ok(Frame::Simple("Ok".to_string()))
+            }
+            ClientCmd::Unpause => {
+                *self.pause.lock()? = None;
+                self.unpause.notify_waiters();
+                Ok(Frame::Simple("Ok".to_string()))
+            }
+        }
+    }
+
+    ///Отправляет сигнал закрытия всем соединениям, подходящим под фильтр.
+    ///Соединение закрывается после ответа на текущую команду.
+    pub fn kill(&self, kill: &Kill) -> Result<u64, CashError> {
+        let clients = self.clients.lock()?;
+        let mut killed = 0;
+
+        for client in clients.values() {
+            let id_match = kill.id().is_none_or(|id| id == client.id);
+            let addr_match = kill.addr().is_none_or(|addr| *addr == client.addr.to_string());
+            let user_match = kill.user().is_none_or(|user| *user == client.user);
+
+            if id_match && addr_match && user_match {
+                client.kill.notify_one();
+                killed += 1;
+            }
+        }
+
+        Ok(killed)
+    }
+}
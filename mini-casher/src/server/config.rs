@@ -0,0 +1,458 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use bytes::Bytes;
+use crate::core::command::config::ConfigCmd;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::glob::glob_match;
+use crate::server::actor::Execution;
+use crate::server::aof::Fsync;
+use crate::storage::engine::{Engine, EngineOptions};
+use crate::storage::eviction::Policy;
+
+///Допустимые значения параметра
+#[derive(Debug)]
+enum Kind {
+    Int { min: i64, max: i64 },
+    Enum(&'static [&'static str]),
+    Memory,
+    String,
+    Save,
+}
+
+///Описание параметра конфигурации.
+///Неизменяемые параметры задаются только в файле или в командной строке
+#[derive(Debug)]
+struct Param {
+    name: &'static str,
+    default: &'static str,
+    kind: Kind,
+    mutable: bool,
+}
+
+impl Param {
+    const fn new(name: &'static str, default: &'static str, kind: Kind) -> Self {
+        Self { name, default, kind, mutable: true }
+    }
+
+    const fn immutable(mut self) -> Self {
+        self.mutable = false;
+        self
+    }
+
+    fn validate(&self, value: &str) -> Result<(), CashError> {
+        let valid = match &self.kind {
+            Kind::Int { min, max } => value.parse::<i64>().is_ok_and(|v| v >= *min && v <= *max),
+            Kind::Enum(values) => values.contains(&value),
+            Kind::Memory => parse_memory(value).is_some(),
+            Kind::String => true,
+            Kind::Save => parse_save(value).is_some(),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::Config(format!("invalid argument '{}' for CONFIG SET '{}'", value, self.name)))
+        }
+    }
+}
+
+///Все параметры сервера
+static PARAMS: &[Param] = &[
+    Param::new("bind", "127.0.0.1", Kind::String).immutable(),
+    Param::new("port", "6379", Kind::Int { min: 0, max: 65535 }).immutable(),
+    Param::new("maxclients", "10000", Kind::Int { min: 1, max: i64::MAX }),
+    Param::new("timeout", "0", Kind::Int { min: 0, max: i64::MAX }),
+    Param::new("requirepass", "", Kind::String),
+    Param::new("loglevel", "info", Kind::Enum(&["error", "warn", "info", "debug", "trace"])),
+    Param::new("slowlog-log-slower-than", "10000", Kind::Int { min: -1, max: i64::MAX }),
+    Param::new("slowlog-max-len", "128", Kind::Int { min: 0, max: i64::MAX }),
+    Param::new("maxmemory", "0", Kind::Memory),
+    Param::new("maxmemory-policy", "noeviction", Kind::Enum(Policy::NAMES)),
+    Param::new("maxmemory-samples", "5", Kind::Int { min: 1, max: 64 }),
+    Param::new("shards", "16", Kind::Int { min: 1, max: 1024 }).immutable(),
+    Param::new("storage-engine", "memory", Kind::Enum(Engine::NAMES)).immutable(),
+    Param::new("execution", "locks", Kind::Enum(Execution::NAMES)).immutable(),
+    Param::new("save", "3600 1 300 100 60 10000", Kind::Save),
+    Param::new("dir", ".", Kind::String),
+    Param::new("dbfilename", "dump.cdb", Kind::String),
+    Param::new("appendonly", "no", Kind::Enum(&["yes", "no"])),
+    Param::new("appendfsync", "everysec", Kind::Enum(Fsync::NAMES)),
+    Param::new("appendfilename", "appendonly.aof", Kind::String).immutable(),
+    Param::new("auto-aof-rewrite-percentage", "100", Kind::Int { min: 0, max: i64::MAX }),
+    Param::new("auto-aof-rewrite-min-size", "64mb", Kind::Memory),
+];
+
+fn param(name: &str) -> Result<&'static Param, CashError> {
+    PARAMS
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| Error::Config(format!("unknown config parameter '{}'", name)))
+}
+
+///Конфигурация сервера.
+///Значения берутся из файла в формате redis.conf (`имя значение` на строку)
+///и переопределяются аргументами командной строки `--имя значение`
+#[derive(Debug)]
+pub struct Config {
+    path: Option<PathBuf>,
+    values: Mutex<BTreeMap<&'static str, String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let values = PARAMS.iter().map(|p| (p.name, p.default.to_string())).collect();
+        Self { path: None, values: Mutex::new(values) }
+    }
+}
+
+impl Config {
+    ///Разбирает аргументы `cash-server [файл] [--имя значение ...]`
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Config, CashError> {
+        let mut args = args.into_iter().peekable();
+        let path = args.next_if(|a| !a.starts_with("--")).map(PathBuf::from);
+
+        let mut overrides = vec![];
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                return Err(Error::Config(format!("unexpected argument '{}'", arg)));
+            };
+            let value = args
+                .next()
+                .ok_or_else(|| Error::Config(format!("missing value for '--{}'", name)))?;
+            overrides.push((name.to_lowercase(), value));
+        }
+
+        Config::load(path, &overrides)
+    }
+
+    ///Читает файл конфигурации, если он задан, и применяет переопределения
+    pub fn load(path: Option<PathBuf>, overrides: &[(String, String)]) -> Result<Config, CashError> {
+        let mut config = Config::default();
+
+        if let Some(path) = &path {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| Error::Config(format!("can't read '{}': {}", path.display(), e)))?;
+
+            for (number, line) in content.lines().enumerate() {
+                if let Some((name, value)) = parse_line(line) {
+                    config.init(&name, value)
+                        .map_err(|e| Error::Config(format!("{} at line {}", e, number + 1)))?;
+                }
+            }
+            config.path = Some(path.clone());
+        }
+
+        for (name, value) in overrides {
+            config.init(name, value.clone())?;
+        }
+
+        Ok(config)
+    }
+
+    ///Задает значение при запуске, когда неизменяемые параметры еще можно менять
+    fn init(&mut self, name: &str, value: String) -> Result<(), CashError> {
+        let param = param(name)?;
+        param.validate(&value)?;
+        self.values.get_mut()?.insert(param.name, value);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<String, CashError> {
+        let param = param(name)?;
+        Ok(self.values.lock()?.get(param.name).cloned().unwrap_or_default())
+    }
+
+    ///Значение целочисленного параметра. Значения проверяются при записи
+    pub fn get_int(&self, name: &str) -> Result<i64, CashError> {
+        Ok(self.get(name)?.parse()?)
+    }
+
+    ///Размер в байтах, допускает суффиксы `kb`, `mb`, `gb`
+    pub fn get_memory(&self, name: &str) -> Result<usize, CashError> {
+        let value = self.get(name)?;
+        parse_memory(&value).ok_or_else(|| Error::Config(format!("invalid memory value '{}'", value)))
+    }
+
+    ///Ограничения памяти хранилища: `maxmemory`, политика и размер выборки
+    pub fn memory_limits(&self) -> Result<(usize, Policy, usize), CashError> {
+        Ok((
+            self.get_memory("maxmemory")?,
+            self.get("maxmemory-policy")?.parse()?,
+            self.get_int("maxmemory-samples")? as usize,
+        ))
+    }
+
+    ///Движок хранилища и параметры его частей. Файлы движка лежат в `dir`
+    pub fn storage_engine(&self) -> Result<(Engine, EngineOptions), CashError> {
+        let (maxmemory, policy, samples) = self.memory_limits()?;
+        let options = EngineOptions {
+            maxmemory,
+            policy,
+            samples,
+            dir: PathBuf::from(self.get("dir")?),
+            shards: self.get_int("shards")? as usize,
+            ..Default::default()
+        };
+        Ok((self.get("storage-engine")?.parse()?, options))
+    }
+
+    ///Кто выполняет команды хранилища: сами соединения или поток хранилища
+    pub fn execution(&self) -> Result<Execution, CashError> {
+        self.get("execution")?.parse()
+    }
+
+    ///Адрес, на котором сервер принимает соединения
+    pub fn addr(&self) -> Result<String, CashError> {
+        Ok(format!("{}:{}", self.get("bind")?, self.get("port")?))
+    }
+
+    ///Правила `save`: снимок, если за столько секунд было не меньше стольких изменений
+    pub fn save_rules(&self) -> Result<Vec<(u64, u64)>, CashError> {
+        let value = self.get("save")?;
+        parse_save(&value).ok_or_else(|| Error::Config(format!("invalid save rules '{}'", value)))
+    }
+
+    ///Файл снимка: `dbfilename` в каталоге `dir`
+    pub fn snapshot_path(&self) -> Result<PathBuf, CashError> {
+        Ok(Path::new(&self.get("dir")?).join(self.get("dbfilename")?))
+    }
+
+    ///Включен ли AOF и политика `fsync`
+    pub fn append_only(&self) -> Result<(bool, Fsync), CashError> {
+        Ok((self.get("appendonly")? == "yes", self.get("appendfsync")?.parse()?))
+    }
+
+    ///Файл AOF: `appendfilename` в каталоге `dir`
+    pub fn aof_path(&self) -> Result<PathBuf, CashError> {
+        Ok(Path::new(&self.get("dir")?).join(self.get("appendfilename")?))
+    }
+
+    pub fn log_level(&self) -> Result<log::LevelFilter, CashError> {
+        self.get("loglevel")?
+            .parse()
+            .map_err(|_| Error::Config("invalid loglevel".to_string()))
+    }
+
+    ///Меняет параметры во время работы.
+    ///Сначала проверяются все пары, чтобы не применить команду частично
+    pub fn set(&self, pairs: &[(String, String)]) -> Result<(), CashError> {
+        for (name, value) in pairs {
+            let param = param(name)?;
+            if !param.mutable {
+                return Err(Error::Config(format!("can't set immutable config parameter '{}'", name)));
+            }
+            param.validate(value)?;
+        }
+
+        let mut values = self.values.lock()?;
+        for (name, value) in pairs {
+            values.insert(param(name)?.name, value.clone());
+        }
+
+        Ok(())
+    }
+
+    ///Перезаписывает файл конфигурации текущими значениями.
+    ///Комментарии и порядок строк сохраняются, новые параметры дописываются в конец
+    pub fn rewrite(&self) -> Result<(), CashError> {
+        let Some(path) = &self.path else {
+            return Err(Error::Config("the server is running without a config file".to_string()));
+        };
+
+        let content = std::fs::read_to_string(path).unwrap_or_default();
+        let content = rewrite_content(&content, &*self.values.lock()?);
+
+        write_atomic(path, &content)
+            .map_err(|e| Error::Config(format!("rewriting config file: {}", e)))
+    }
+
+    pub fn execute(&self, cmd: ConfigCmd) -> Result<Frame, CashError> {
+        match cmd {
+            ConfigCmd::Get(patterns) => {
+                let values = self.values.lock()?;
+                let mut frames = vec![];
+
+                for (name, value) in values.iter() {
+                    if patterns.iter().any(|p| glob_match(p.as_bytes(), name.as_bytes())) {
+                        frames.push(Frame::BulkString(Bytes::from(name.to_string())));
+                        frames.push(Frame::BulkString(Bytes::from(value.clone())));
+                    }
+                }
+
+                Ok(Frame::Array(frames))
+            }
+            ConfigCmd::Set(pairs) => {
+                self.set(&pairs)?;
+                Ok(Frame::Simple("Ok".to_string()))
+            }
+            ConfigCmd::Rewrite => {
+                self.rewrite()?;
+                Ok(Frame::Simple("Ok".to_string()))
+            }
+        }
+    }
+}
+
+///Строка файла конфигурации: имя в нижнем регистре и значение без кавычек.
+///Пустые строки и комментарии пропускаются
+fn parse_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+
+    Some((name.to_lowercase(), value.to_string()))
+}
+
+///Размер памяти как в redis.conf: `1024`, `100kb`, `64mb`, `1gb` (`k`, `m`, `g` - степени 1000)
+fn parse_memory(value: &str) -> Option<usize> {
+    let value = value.to_lowercase();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier = match unit {
+        "" | "b" => 1,
+        "k" => 1_000,
+        "kb" => 1 << 10,
+        "m" => 1_000_000,
+        "mb" => 1 << 20,
+        "g" => 1_000_000_000,
+        "gb" => 1 << 30,
+        _ => return None,
+    };
+
+    number.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+///Правила `save` парами `секунды изменения`. Пустая строка отключает автоматические снимки
+fn parse_save(value: &str) -> Option<Vec<(u64, u64)>> {
+    let numbers = value.split_whitespace().map(|n| n.parse::<u64>().ok()).collect::<Option<Vec<_>>>()?;
+    if numbers.len() % 2 != 0 || numbers.iter().step_by(2).any(|seconds| *seconds == 0) {
+        return None;
+    }
+    Some(numbers.chunks(2).map(|rule| (rule[0], rule[1])).collect())
+}
+
+fn format_line(name: &str, value: &str) -> String {
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        format!("{} \"{}\"", name, value)
+    } else {
+        format!("{} {}", name, value)
+    }
+}
+
+///Новое содержимое файла: известные параметры заменяются текущими значениями,
+///параметры со значением не по умолчанию, которых не было в файле, дописываются
+fn rewrite_content(content: &str, values: &BTreeMap<&'static str, String>) -> String {
+    let mut written = vec![];
+    let mut lines = vec![];
+
+    for line in content.lines() {
+        match parse_line(line) {
+            Some((name, _)) if values.contains_key(name.as_str()) => {
+                if !written.contains(&name) {
+                    lines.push(format_line(&name, &values[name.as_str()]));
+                    written.push(name);
+                }
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    for param in PARAMS {
+        let value = &values[param.name];
+        if value != param.default && !written.iter().any(|n| n == param.name) {
+            lines.push(format_line(param.name, value));
+        }
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+///Запись через временный файл, чтобы при сбое не остался наполовину записанный конфиг
+fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn overrides_from_args() {
+        let config = Config::from_args(args(&["--port", "6380", "--maxclients", "10"])).unwrap();
+
+        assert_eq!(Ok("127.0.0.1:6380".to_string()), config.addr());
+        assert_eq!(Ok(10), config.get_int("maxclients"));
+        assert!(Config::from_args(args(&["--port", "x"])).is_err());
+        assert!(Config::from_args(args(&["--unknown", "1"])).is_err());
+    }
+
+    #[test]
+    fn set_checks_all_pairs() {
+        let config = Config::default();
+        let pairs = vec![
+            ("timeout".to_string(), "30".to_string()),
+            ("port".to_string(), "7000".to_string()),
+        ];
+
+        assert!(config.set(&pairs).is_err());
+        assert_eq!(Ok(0), config.get_int("timeout"));
+        assert!(config.set(&pairs[..1]).is_ok());
+        assert_eq!(Ok(30), config.get_int("timeout"));
+    }
+
+    #[test]
+    fn get_by_pattern() {
+        let config = Config::default();
+        let frame = config.execute(ConfigCmd::Get(vec!["slowlog-*".to_string()])).unwrap();
+
+        let Frame::Array(frames) = frame else { panic!() };
+        assert_eq!(4, frames.len());
+        assert_eq!(Frame::BulkString(Bytes::from("slowlog-log-slower-than")), frames[0]);
+    }
+
+    #[test]
+    fn memory_units() {
+        assert_eq!(Some(1024), parse_memory("1024"));
+        assert_eq!(Some(100 << 20), parse_memory("100MB"));
+        assert_eq!(Some(2_000), parse_memory("2k"));
+        assert_eq!(None, parse_memory("mb"));
+        assert_eq!(None, parse_memory("10tb"));
+    }
+
+    #[test]
+    fn save_rules() {
+        assert_eq!(Some(vec![(3600, 1), (60, 10000)]), parse_save("3600 1  60 10000"));
+        assert_eq!(Some(vec![]), parse_save(""));
+        assert_eq!(None, parse_save("3600"));
+        assert_eq!(None, parse_save("0 1"));
+    }
+
+    #[test]
+    fn rewrite_keeps_comments() {
+        let mut values: BTreeMap<&'static str, String> = PARAMS.iter().map(|p| (p.name, p.default.to_string())).collect();
+        values.insert("port", "6380".to_string());
+        values.insert("requirepass", "secret word".to_string());
+
+        let content = rewrite_content("# board cache\nport 6379\n\nloglevel info\n", &values);
+        assert_eq!("# board cache\nport 6380\n\nloglevel info\nrequirepass \"secret word\"\n", content);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::core::command::filter::FilterCmd;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::storage::filter::{Bloom, Cuckoo, BLOOM_CAPACITY, BLOOM_ERROR_RATE, BLOOM_EXPANSION, CUCKOO_BUCKET_SIZE,
+    CUCKOO_CAPACITY, CUCKOO_EXPANSION, CUCKOO_MAX_ITERATIONS};
+use crate::storage::engine::StorageEngine;
+use crate::storage::{wrong_type, Value};
+
+fn item_exists() -> Error {
+    Error::Storage("item exists".to_string())
+}
+
+fn load_bloom(store: &mut dyn StorageEngine, key: &[u8]) -> Result<Option<Bloom>, CashError> {
+    match store.get_value(key)? {
+        Some(Value::Bloom(bloom)) => Ok(Some(bloom)),
+        Some(_) => Err(wrong_type()),
+        None => Ok(None),
+    }
+}
+
+fn load_cuckoo(store: &mut dyn StorageEngine, key: &[u8]) -> Result<Option<Cuckoo>, CashError> {
+    match store.get_value(key)? {
+        Some(Value::Cuckoo(cuckoo)) => Ok(Some(cuckoo)),
+        Some(_) => Err(wrong_type()),
+        None => Ok(None),
+    }
+}
+
+fn default_bloom() -> Bloom {
+    Bloom::new(BLOOM_ERROR_RATE, BLOOM_CAPACITY, BLOOM_EXPANSION).expect("default bloom filter fits")
+}
+
+fn default_cuckoo() -> Cuckoo {
+    Cuckoo::new(CUCKOO_CAPACITY, CUCKOO_BUCKET_SIZE, CUCKOO_MAX_ITERATIONS, CUCKOO_EXPANSION).expect("default cuckoo filter fits")
+}
+
+///Освобождает место под новый фильтр до того, как под него выделяется память
+fn reserve(store: &mut dyn StorageEngine, key: &[u8], size: Option<usize>) -> Result<(), CashError> {
+    let size = size.ok_or_else(|| Error::Storage("filter is too large".to_string()))?;
+    store.reserve(&[(key, key.len() + size)])
+}
+
+///`BF.ADD`, `BF.MADD` и `CF.ADD` без фильтра создают его с параметрами по умолчанию.
+///Фильтр меняется на месте, поэтому срок жизни ключа сохраняется.
+///`RESERVE` проверяет `maxmemory` по оценке размера до выделения памяти под фильтр
+pub fn execute(store: &mut dyn StorageEngine, cmd: FilterCmd) -> Result<Frame, CashError> {
+    match cmd {
+        FilterCmd::BfReserve { key, error_rate, capacity, expansion } => {
+            if store.exists(&key) {
+                return Err(item_exists());
+            }
+            reserve(store, &key, Bloom::estimate_size(error_rate, capacity))?;
+            store.set_value(key, Value::Bloom(Bloom::new(error_rate, capacity, expansion)?))?;
+            Ok(Frame::Simple("Ok".to_string()))
+        }
+        FilterCmd::BfAdd(key, item) => {
+            let mut bloom = load_bloom(store, &key)?.unwrap_or_else(default_bloom);
+            let added = bloom.add(&item)?;
+            store.update_value(key, Value::Bloom(bloom))?;
+            Ok(Frame::Integer(added as i64))
+        }
+        FilterCmd::BfMAdd(key, items) => {
+            let mut bloom = load_bloom(store, &key)?.unwrap_or_else(default_bloom);
+            let added = items
+                .iter()
+                .map(|item| match bloom.add(item) {
+                    Ok(added) => Frame::Integer(added as i64),
+                    Err(err) => Frame::Error(err.to_string()),
+                })
+                .collect();
+            store.update_value(key, Value::Bloom(bloom))?;
+            Ok(Frame::Array(added))
+        }
+        FilterCmd::BfExists(key, item) => {
+            let exists = load_bloom(store, &key)?.is_some_and(|bloom| bloom.contains(&item));
+            Ok(Frame::Integer(exists as i64))
+        }
+        FilterCmd::BfMExists(key, items) => {
+            let bloom = load_bloom(store, &key)?;
+            let exists = items
+                .iter()
+                .map(|item| Frame::Integer(bloom.as_ref().is_some_and(|b| b.contains(item)) as i64))
+                .collect();
+            Ok(Frame::Array(exists))
+        }
+        FilterCmd::CfReserve { key, capacity, bucket_size, max_iterations, expansion } => {
+            if store.exists(&key) {
+                return Err(item_exists());
+            }
+            reserve(store, &key, Cuckoo::estimate_size(capacity, bucket_size))?;
+            let cuckoo = Cuckoo::new(capacity, bucket_size, max_iterations, expansion)?;
+            store.set_value(key, Value::Cuckoo(cuckoo))?;
+            Ok(Frame::Simple("Ok".to_string()))
+        }
+        FilterCmd::CfAdd(key, item) => {
+            let mut cuckoo = load_cuckoo(store, &key)?.unwrap_or_else(default_cuckoo);
+            cuckoo.add(&item)?;
+            store.update_value(key, Value::Cuckoo(cuckoo))?;
+            Ok(Frame::Integer(1))
+        }
+        FilterCmd::CfAddNx(key, item) => {
+            let mut cuckoo = load_cuckoo(store, &key)?.unwrap_or_else(default_cuckoo);
+            if cuckoo.contains(&item) {
+                return Ok(Frame::Integer(0));
+            }
+            cuckoo.add(&item)?;
+            store.update_value(key, Value::Cuckoo(cuckoo))?;
+            Ok(Frame::Integer(1))
+        }
+        FilterCmd::CfExists(key, item) => {
+            let exists = load_cuckoo(store, &key)?.is_some_and(|cuckoo| cuckoo.contains(&item));
+            Ok(Frame::Integer(exists as i64))
+        }
+        FilterCmd::CfDel(key, item) => {
+            let Some(mut cuckoo) = load_cuckoo(store, &key)? else {
+                return Err(Error::Storage("Not found".to_string()));
+            };
+            let deleted = cuckoo.delete(&item);
+            if deleted {
+                store.update_value(key, Value::Cuckoo(cuckoo))?;
+            }
+            Ok(Frame::Integer(deleted as i64))
+        }
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use bytes::Bytes;
+    use crate::storage::eviction::Policy;
+    use crate::storage::Store;
+    use super::*;
+
+    #[test]
+    fn bloom_commands() {
+        let mut store = Store::default();
+        let key = Bytes::from("seen");
+
+        let add = |item: &str| FilterCmd::BfAdd(key.clone(), Bytes::from(item.to_string()));
+        assert_eq!(Ok(Frame::Integer(1)), execute(&mut store, add("a")));
+        assert_eq!(Ok(Frame::Integer(0)), execute(&mut store, add("a")));
+
+        let exists = FilterCmd::BfMExists(key.clone(), vec![Bytes::from("a"), Bytes::from("b")]);
+        assert_eq!(Ok(Frame::Array(vec![Frame::Integer(1), Frame::Integer(0)])), execute(&mut store, exists));
+
+        let reserve = FilterCmd::BfReserve { key: key.clone(), error_rate: 0.01, capacity: 10, expansion: 2 };
+        assert_eq!(Err(item_exists()), execute(&mut store, reserve));
+        assert_eq!(Err(wrong_type()), execute(&mut store, FilterCmd::CfAdd(key, Bytes::from("a"))));
+    }
+
+    #[test]
+    fn cuckoo_commands() {
+        let mut store = Store::default();
+        let key = Bytes::from("titles");
+        let item = Bytes::from("write docs");
+
+        assert_eq!(Ok(Frame::Integer(1)), execute(&mut store, FilterCmd::CfAddNx(key.clone(), item.clone())));
+        assert_eq!(Ok(Frame::Integer(0)), execute(&mut store, FilterCmd::CfAddNx(key.clone(), item.clone())));
+        assert_eq!(Ok(Frame::Integer(1)), execute(&mut store, FilterCmd::CfDel(key.clone(), item.clone())));
+        assert_eq!(Ok(Frame::Integer(0)), execute(&mut store, FilterCmd::CfExists(key, item)));
+    }
+
+    #[test]
+    fn reserve_checks_maxmemory_first() {
+        let mut store = Store::new(1024 * 1024, Policy::NoEviction, 5);
+        let bloom = FilterCmd::BfReserve { key: Bytes::from("b"), error_rate: 0.01, capacity: 10_000_000, expansion: 2 };
+        assert_eq!(Err(Error::Oom), execute(&mut store, bloom));
+        let cuckoo = FilterCmd::CfReserve { key: Bytes::from("c"), capacity: 10_000_000, bucket_size: 2, max_iterations: 20, expansion: 1 };
+        assert_eq!(Err(Error::Oom), execute(&mut store, cuckoo));
+        assert_eq!(0, store.len());
+    }
+}
@@ -0,0 +1,457 @@
+use bytes::Bytes;
+use crate::core::command::hyperloglog::HllCmd;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::storage::engine::StorageEngine;
+
+///Раскладка значения как в redis:
+///заголовок `HYLL`, байт кодировки, 3 свободных байта, 8 байт кэша мощности (не используется),
+///дальше регистры в плотной или разреженной кодировке
+const MAGIC: &[u8; 4] = b"HYLL";
+const HEADER_LEN: usize = 16;
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+
+///2^14 регистров по 6 бит, стандартная ошибка около 0.81%
+const P: u32 = 14;
+const REGISTERS: usize = 1 << P;
+const REGISTER_BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << REGISTER_BITS) - 1;
+const DENSE_LEN: usize = HEADER_LEN + (REGISTERS * REGISTER_BITS).div_ceil(8);
+///Биты хэша после индекса регистра
+const Q: usize = 64 - P as usize;
+
+///Разреженная кодировка выгодна, пока значение не больше этого размера,
+///как `hll-sparse-max-bytes` в redis
+const SPARSE_MAX_BYTES: usize = 3000;
+///Наибольшее значение регистра, которое помещается в код `VAL`
+const SPARSE_VAL_MAX: u8 = 32;
+const SPARSE_VAL_MAX_RUN: usize = 4;
+const SPARSE_ZERO_MAX_RUN: usize = 64;
+const SPARSE_XZERO_MAX_RUN: usize = 16384;
+
+const HASH_SEED: u64 = 0xadc83b19;
+
+fn wrong_type() -> Error {
+    Error::WrongType("Key is not a valid HyperLogLog string value.".to_string())
+}
+
+///Заголовок верный, но регистры невозможны, как ответ redis на испорченное значение
+fn corrupted() -> Error {
+    Error::Storage("INVALIDOBJ Corrupted HLL object detected".to_string())
+}
+
+///Регистры HyperLogLog в развернутом виде.
+///Значение в хранилище разворачивается целиком, меняется и кодируется обратно
+#[derive(Debug, Clone, PartialEq)]
+struct Hll {
+    registers: Vec<u8>,
+    dense: bool,
+}
+
+impl Hll {
+    fn new() -> Self {
+        Self { registers: vec![0; REGISTERS], dense: false }
+    }
+
+    fn decode(value: &[u8]) -> Result<Self, CashError> {
+        if value.len() < HEADER_LEN || &value[..4] != MAGIC {
+            return Err(wrong_type());
+        }
+
+        //значение можно записать через `SET`, поэтому регистры больше `Q + 1` не принимаются:
+        //по ним считается гистограмма в `count`
+        let body = &value[HEADER_LEN..];
+        match value[4] {
+            DENSE if value.len() == DENSE_LEN => {
+                let registers: Vec<u8> = (0..REGISTERS).map(|i| dense_get(body, i)).collect();
+                if registers.iter().any(|register| *register as usize > Q + 1) {
+                    return Err(corrupted());
+                }
+                Ok(Self { registers, dense: true })
+            }
+            SPARSE => Ok(Self { registers: sparse_decode(body)?, dense: false }),
+            _ => Err(wrong_type()),
+        }
+    }
+
+    ///Разреженная кодировка сохраняется, пока она короче `SPARSE_MAX_BYTES`
+    ///и все значения помещаются в `VAL`. Обратно в разреженную плотная не переводится
+    fn encode(&self) -> Bytes {
+        let mut value = Vec::with_capacity(DENSE_LEN);
+        value.extend_from_slice(MAGIC);
+        value.extend_from_slice(&[DENSE, 0, 0, 0]);
+        value.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0x80]);
+
+        if !self.dense {
+            if let Some(sparse) = sparse_encode(&self.registers) {
+                value[4] = SPARSE;
+                value.extend(sparse);
+                return Bytes::from(value);
+            }
+        }
+
+        let mut body = vec![0; DENSE_LEN - HEADER_LEN];
+        for (i, register) in self.registers.iter().enumerate() {
+            dense_set(&mut body, i, *register);
+        }
+        value.extend(body);
+        Bytes::from(value)
+    }
+
+    ///Добавляет элемент. `true` - изменился хотя бы один регистр
+    fn add(&mut self, element: &[u8]) -> bool {
+        let hash = murmur64a(element, HASH_SEED);
+        let index = (hash & (REGISTERS as u64 - 1)) as usize;
+        //Единица в старшем бите ограничивает длину серии нулей
+        let rest = (hash >> P) | (1 << Q);
+        let count = rest.trailing_zeros() as u8 + 1;
+
+        if count > self.registers[index] {
+            self.registers[index] = count;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn merge(&mut self, other: &Hll) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+        self.dense |= other.dense;
+    }
+
+    ///Оценка мощности по улучшенному алгоритму Ertl, как в redis
+    fn count(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let mut histogram = [0u32; Q + 2];
+        for register in &self.registers {
+            histogram[*register as usize] += 1;
+        }
+
+        let mut z = m * tau((m - histogram[Q + 1] as f64) / m);
+        for j in (1..=Q).rev() {
+            z += histogram[j] as f64;
+            z *= 0.5;
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+
+        let alpha_inf = 0.5 / std::f64::consts::LN_2;
+        (alpha_inf * m * m / z).round() as u64
+    }
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+///Регистры плотной кодировки упакованы по 6 бит, младшие биты первыми
+fn dense_get(body: &[u8], index: usize) -> u8 {
+    let bit = index * REGISTER_BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let low = body[byte] as u16;
+    let high = body.get(byte + 1).copied().unwrap_or(0) as u16;
+
+    (((low | (high << 8)) >> shift) as u8) & REGISTER_MAX
+}
+
+fn dense_set(body: &mut [u8], index: usize, value: u8) {
+    let bit = index * REGISTER_BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let mask = (REGISTER_MAX as u16) << shift;
+    let value = (value as u16) << shift;
+
+    body[byte] = (body[byte] & !(mask as u8)) | value as u8;
+    if let Some(next) = body.get_mut(byte + 1) {
+        *next = (*next & !((mask >> 8) as u8)) | (value >> 8) as u8;
+    }
+}
+
+///Коды разреженной кодировки:
+///`00xxxxxx` - до 64 нулевых регистров, `01xxxxxx yyyyyyyy` - до 16384 нулевых,
+///`1vvvvvxx` - до 4 регистров со значением 1..32
+fn sparse_decode(body: &[u8]) -> Result<Vec<u8>, CashError> {
+    let mut registers = Vec::with_capacity(REGISTERS);
+    let mut i = 0;
+
+    while i < body.len() {
+        let op = body[i];
+        match op >> 6 {
+            0b00 => {
+                registers.resize(registers.len() + (op & 0x3f) as usize + 1, 0);
+                i += 1;
+            }
+            0b01 => {
+                let low = *body.get(i + 1).ok_or_else(wrong_type)?;
+                let run = (((op & 0x3f) as usize) << 8 | low as usize) + 1;
+                registers.resize(registers.len() + run, 0);
+                i += 2;
+            }
+            _ => {
+                let value = ((op >> 2) & 0x1f) + 1;
+                let run = (op & 0x03) as usize + 1;
+                registers.resize(registers.len() + run, value);
+                i += 1;
+            }
+        }
+
+        if registers.len() > REGISTERS {
+            return Err(wrong_type());
+        }
+    }
+
+    if registers.len() != REGISTERS {
+        return Err(wrong_type());
+    }
+    Ok(registers)
+}
+
+///`None` - разреженная кодировка не подходит
+fn sparse_encode(registers: &[u8]) -> Option<Vec<u8>> {
+    let mut body = vec![];
+    let mut i = 0;
+
+    while i < registers.len() {
+        let value = registers[i];
+        let run = registers[i..].iter().take_while(|r| **r == value).count();
+
+        if value == 0 {
+            let mut left = run;
+            while left > 0 {
+                if left > SPARSE_ZERO_MAX_RUN {
+                    let len = left.min(SPARSE_XZERO_MAX_RUN) - 1;
+                    body.extend([0x40 | (len >> 8) as u8, len as u8]);
+                    left -= len + 1;
+                } else {
+                    body.push((left - 1) as u8);
+                    left = 0;
+                }
+            }
+        } else {
+            if value > SPARSE_VAL_MAX {
+                return None;
+            }
+            let mut left = run;
+            while left > 0 {
+                let len = left.min(SPARSE_VAL_MAX_RUN);
+                body.push(0x80 | ((value - 1) << 2) | (len - 1) as u8);
+                left -= len;
+            }
+        }
+
+        if body.len() > SPARSE_MAX_BYTES {
+            return None;
+        }
+        i += run;
+    }
+
+    Some(body)
+}
+
+///MurmurHash64A, которым redis хэширует элементы HyperLogLog
+pub(crate) fn murmur64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+    let mut chunks = key.chunks_exact(8);
+
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().expect("chunk of 8 bytes"));
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+fn load(store: &mut dyn StorageEngine, key: &[u8]) -> Result<Option<Hll>, CashError> {
+    store.get(key)?.map(|value| Hll::decode(&value)).transpose()
+}
+
+pub fn execute(store: &mut dyn StorageEngine, cmd: HllCmd) -> Result<Frame, CashError> {
+    match cmd {
+        HllCmd::Add(key, elements) => {
+            let (mut hll, mut changed) = match load(store, &key)? {
+                Some(hll) => (hll, false),
+                None => (Hll::new(), true),
+            };
+
+            for element in &elements {
+                changed |= hll.add(element);
+            }
+
+            if changed {
+                store.update(key, hll.encode())?;
+            }
+            Ok(Frame::Integer(changed as i64))
+        }
+        HllCmd::Count(keys) => {
+            let mut union = Hll::new();
+            for key in &keys {
+                if let Some(hll) = load(store, key)? {
+                    union.merge(&hll);
+                }
+            }
+            Ok(Frame::Integer(union.count() as i64))
+        }
+        HllCmd::Merge(dest, keys) => {
+            let mut union = load(store, &dest)?.unwrap_or_else(Hll::new);
+            for key in &keys {
+                if let Some(hll) = load(store, key)? {
+                    union.merge(&hll);
+                }
+            }
+
+            union.dense = true;
+            store.update(dest, union.encode())?;
+            Ok(Frame::Simple("Ok".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod hyperloglog_tests {
+    use crate::storage::Store;
+    use super::*;
+
+    fn add(store: &mut Store, key: &str, range: std::ops::Range<u32>) {
+        let elements = range.map(|i| Bytes::from(format!("user:{}", i))).collect();
+        execute(store, HllCmd::Add(Bytes::from(key.to_string()), elements)).unwrap();
+    }
+
+    fn count(store: &mut Store, keys: &[&str]) -> i64 {
+        let keys = keys.iter().map(|k| Bytes::from(k.to_string())).collect();
+        match execute(store, HllCmd::Count(keys)) {
+            Ok(Frame::Integer(count)) => count,
+            frame => panic!("{:?}", frame),
+        }
+    }
+
+    fn assert_close(expected: i64, actual: i64) {
+        let error = (expected - actual).abs() as f64 / expected as f64;
+        assert!(error < 0.02, "expected about {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn murmur_reference() {
+        assert_eq!(0, murmur64a(b"", 0));
+        assert_ne!(murmur64a(b"a", HASH_SEED), murmur64a(b"b", HASH_SEED));
+    }
+
+    #[test]
+    fn small_sets_are_sparse_and_exact_enough() {
+        let mut store = Store::default();
+        add(&mut store, "viewers", 0..100);
+        add(&mut store, "viewers", 0..100);
+
+        assert_eq!(SPARSE, store.get(b"viewers").unwrap().unwrap()[4]);
+        assert_close(100, count(&mut store, &["viewers"]));
+    }
+
+    #[test]
+    fn large_sets_switch_to_dense() {
+        let mut store = Store::default();
+        add(&mut store, "viewers", 0..20_000);
+
+        let value = store.get(b"viewers").unwrap().unwrap();
+        assert_eq!(DENSE, value[4]);
+        assert_eq!(DENSE_LEN, value.len());
+        assert_close(20_000, count(&mut store, &["viewers"]));
+    }
+
+    #[test]
+    fn union_and_merge() {
+        let mut store = Store::default();
+        add(&mut store, "week1", 0..1_000);
+        add(&mut store, "week2", 500..1_500);
+
+        assert_close(1_500, count(&mut store, &["week1", "week2"]));
+
+        let keys = vec![Bytes::from("week1"), Bytes::from("week2")];
+        execute(&mut store, HllCmd::Merge(Bytes::from("month"), keys)).unwrap();
+        assert_close(1_500, count(&mut store, &["month"]));
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let mut hll = Hll::new();
+        for i in 0..300 {
+            hll.add(format!("{}", i).as_bytes());
+        }
+
+        assert_eq!(hll, Hll::decode(&hll.encode()).unwrap());
+        hll.dense = true;
+        assert_eq!(hll, Hll::decode(&hll.encode()).unwrap());
+    }
+
+    #[test]
+    fn rejects_plain_strings() {
+        let mut store = Store::default();
+        store.set(Bytes::from("name"), Bytes::from("board")).unwrap();
+
+        let add = HllCmd::Add(Bytes::from("name"), vec![Bytes::from("x")]);
+        assert!(matches!(execute(&mut store, add), Err(Error::WrongType(_))));
+    }
+
+    #[test]
+    fn rejects_corrupted_registers() {
+        let mut store = Store::default();
+        let mut hll = Hll::new();
+        hll.dense = true;
+        let mut value = hll.encode().to_vec();
+        dense_set(&mut value[HEADER_LEN..], 7, REGISTER_MAX);
+        store.set(Bytes::from("hll"), Bytes::from(value)).unwrap();
+
+        let keys = vec![Bytes::from("hll")];
+        assert_eq!(Err(corrupted()), execute(&mut store, HllCmd::Count(keys.clone())));
+        assert_eq!(Err(corrupted()), execute(&mut store, HllCmd::Merge(Bytes::from("dest"), keys)));
+    }
+}
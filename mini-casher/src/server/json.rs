@@ -0,0 +1,322 @@
+use bytes::Bytes;
+use serde_json::{Map, Number, Value as Json};
+use crate::core::command::json::{JsonCmd, Path, Segment};
+use crate::core::command::string::Condition;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::storage::json::{node_mut, resolve, select, Step};
+use crate::storage::engine::StorageEngine;
+use crate::storage::{wrong_type, Value};
+
+fn type_name(value: &Json) -> &'static str {
+    match value {
+        Json::Null => "null",
+        Json::Bool(_) => "boolean",
+        Json::Number(n) if n.is_f64() => "number",
+        Json::Number(_) => "integer",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+///Сумма с сохранением целого типа, пока результат помещается в `i64`
+fn add(a: &Number, b: &Number) -> Option<Number> {
+    match (a.as_i64(), b.as_i64()) {
+        (Some(a), Some(b)) if a.checked_add(b).is_some() => Some(Number::from(a + b)),
+        _ => Number::from_f64(a.as_f64()? + b.as_f64()?),
+    }
+}
+
+fn no_path(path: &Path) -> Error {
+    Error::Storage(format!("Path '{}' does not exist", path.as_str()))
+}
+
+fn not_a(path: &Path, kind: &str) -> Error {
+    Error::Storage(format!("Path '{}' does not hold a value of type {}", path.as_str(), kind))
+}
+
+fn load(store: &mut dyn StorageEngine, key: &[u8]) -> Result<Option<Json>, CashError> {
+    match store.get_value(key)? {
+        Some(Value::Json(json)) => Ok(Some(json)),
+        Some(_) => Err(wrong_type()),
+        None => Ok(None),
+    }
+}
+
+///Ответ по всем совпадениям: путь `$` дает массив, старый синтаксис - первое значение
+///или ошибку, если совпадений нет
+fn reply(path: &Path, results: Vec<Frame>) -> Result<Frame, CashError> {
+    if path.is_legacy() {
+        results.into_iter().next().ok_or_else(|| no_path(path))
+    } else {
+        Ok(Frame::Array(results))
+    }
+}
+
+fn get(root: &Json, paths: &[Path]) -> Result<Frame, CashError> {
+    let values = |path: &Path| -> Result<Json, CashError> {
+        let found: Vec<Json> = select(root, path).into_iter().cloned().collect();
+
+        match (path.is_legacy(), found.is_empty()) {
+            (true, true) => Err(no_path(path)),
+            (true, false) => Ok(found.into_iter().next().unwrap_or_default()),
+            (false, _) => Ok(Json::Array(found)),
+        }
+    };
+
+    let json = match paths {
+        [path] => values(path)?,
+        _ => {
+            let mut map = Map::new();
+            for path in paths {
+                map.insert(path.as_str().to_string(), values(path)?);
+            }
+            Json::Object(map)
+        }
+    };
+
+    Ok(Frame::BulkString(Bytes::from(json.to_string())))
+}
+
+///`JSON.SET`: заменяет найденные значения. Если путь не найден, но его последний шаг - имя поля,
+///поле добавляется во все объекты, найденные по пути без последнего шага
+fn set(root: &mut Json, path: &Path, value: Json, condition: Option<Condition>) -> bool {
+    let found = resolve(root, path.segments());
+
+    if !found.is_empty() {
+        if condition == Some(Condition::Nx) {
+            return false;
+        }
+        for steps in found {
+            if let Some(node) = node_mut(root, &steps) {
+                *node = value.clone();
+            }
+        }
+        return true;
+    }
+
+    let Some((Segment::Key(key), parent)) = path.segments().split_last() else {
+        return false;
+    };
+    if condition == Some(Condition::Xx) {
+        return false;
+    }
+
+    let mut inserted = false;
+    for steps in resolve(root, parent) {
+        if let Some(Json::Object(map)) = node_mut(root, &steps) {
+            map.insert(key.clone(), value.clone());
+            inserted = true;
+        }
+    }
+    inserted
+}
+
+///Удаляет найденные значения. Индексы удаляются с конца, чтобы не сдвигать остальные
+fn delete(root: &mut Json, path: &Path) -> usize {
+    let mut found = resolve(root, path.segments());
+    found.sort();
+    found.dedup();
+
+    let mut deleted = 0;
+    for mut steps in found.into_iter().rev() {
+        let Some(last) = steps.pop() else { continue };
+        let removed = match (node_mut(root, &steps), last) {
+            (Some(Json::Object(map)), Step::Key(key)) => map.remove(&key).is_some(),
+            (Some(Json::Array(items)), Step::Index(i)) if i < items.len() => {
+                items.remove(i);
+                true
+            }
+            _ => false,
+        };
+        deleted += removed as usize;
+    }
+    deleted
+}
+
+pub fn execute(store: &mut dyn StorageEngine, cmd: JsonCmd) -> Result<Frame, CashError> {
+    match cmd {
+        JsonCmd::Set(key, path, value, condition) => {
+            let Some(mut root) = load(store, &key)? else {
+                if condition == Some(Condition::Xx) {
+                    return Ok(Frame::Null);
+                }
+                if !path.is_root() {
+                    return Err(Error::Storage("new objects must be created at the root".to_string()));
+                }
+                store.set_value(key, Value::Json(value))?;
+                return Ok(Frame::Simple("Ok".to_string()));
+            };
+
+            if !set(&mut root, &path, value, condition) {
+                return Ok(Frame::Null);
+            }
+            store.update_value(key, Value::Json(root))?;
+            Ok(Frame::Simple("Ok".to_string()))
+        }
+        JsonCmd::Get(key, paths) => match load(store, &key)? {
+            Some(root) => get(&root, &paths),
+            None => Ok(Frame::Null),
+        },
+        JsonCmd::Del(key, path) => {
+            let Some(mut root) = load(store, &key)? else {
+                return Ok(Frame::Integer(0));
+            };
+
+            if path.is_root() {
+                store.delete(&key);
+                return Ok(Frame::Integer(1));
+            }
+
+            let deleted = delete(&mut root, &path);
+            if deleted > 0 {
+                store.update_value(key, Value::Json(root))?;
+            }
+            Ok(Frame::Integer(deleted as i64))
+        }
+        JsonCmd::GetDel(key) => {
+            let Some(root) = load(store, &key)? else {
+                return Ok(Frame::Null);
+            };
+
+            store.delete(&key);
+            Ok(Frame::BulkString(Bytes::from(root.to_string())))
+        }
+        JsonCmd::ArrAppend(key, path, values) => {
+            let mut root = load(store, &key)?.ok_or_else(|| no_path(&path))?;
+            let mut results = vec![];
+
+            for steps in resolve(&root, path.segments()) {
+                match node_mut(&mut root, &steps) {
+                    Some(Json::Array(items)) => {
+                        items.extend(values.iter().cloned());
+                        results.push(Frame::Integer(items.len() as i64));
+                    }
+                    _ if path.is_legacy() => return Err(not_a(&path, "array")),
+                    _ => results.push(Frame::Null),
+                }
+            }
+
+            store.update_value(key, Value::Json(root))?;
+            reply(&path, results)
+        }
+        JsonCmd::NumIncrBy(key, path, incr) => {
+            let mut root = load(store, &key)?.ok_or_else(|| no_path(&path))?;
+            let mut results = vec![];
+
+            for steps in resolve(&root, path.segments()) {
+                match node_mut(&mut root, &steps) {
+                    Some(Json::Number(current)) => {
+                        *current = add(current, &incr)
+                            .ok_or_else(|| Error::Storage("result is not a finite number".to_string()))?;
+                        results.push(Json::Number(current.clone()));
+                    }
+                    _ if path.is_legacy() => return Err(not_a(&path, "number")),
+                    _ => results.push(Json::Null),
+                }
+            }
+
+            store.update_value(key, Value::Json(root))?;
+            let json = match path.is_legacy() {
+                true => results.into_iter().next().ok_or_else(|| no_path(&path))?,
+                false => Json::Array(results),
+            };
+            Ok(Frame::BulkString(Bytes::from(json.to_string())))
+        }
+        JsonCmd::Type(key, path) => {
+            let Some(root) = load(store, &key)? else {
+                return Ok(Frame::Null);
+            };
+
+            let types = select(&root, &path)
+                .into_iter()
+                .map(|value| Frame::BulkString(Bytes::from(type_name(value))))
+                .collect();
+            reply(&path, types)
+        }
+    }
+}
+
+#[cfg(test)]
+mod json_tests {
+    use serde_json::json;
+    use crate::storage::Store;
+    use super::*;
+
+    fn path(raw: &str) -> Path {
+        Path::parse(raw).unwrap()
+    }
+
+    fn todo() -> Json {
+        json!({"name": "write docs", "status": "new", "tags": ["docs"], "estimate": 3, "sub": [{"status": "new"}, {"status": "done"}]})
+    }
+
+    #[test]
+    fn set_replaces_and_inserts() {
+        let mut root = todo();
+
+        assert!(set(&mut root, &path("$.status"), json!("done"), None));
+        assert!(set(&mut root, &path("$.comments"), json!([]), None));
+        assert!(!set(&mut root, &path("$.status"), json!("x"), Some(Condition::Nx)));
+        assert!(!set(&mut root, &path("$.missing.deep"), json!(1), None));
+
+        assert_eq!(json!("done"), root["status"]);
+        assert_eq!(json!([]), root["comments"]);
+    }
+
+    #[test]
+    fn delete_array_items_from_the_end() {
+        let mut root = json!({"a": [1, 2, 3, 4]});
+
+        assert_eq!(4, delete(&mut root, &path("$.a[*]")));
+        assert_eq!(json!({"a": []}), root);
+    }
+
+    #[test]
+    fn partial_update_in_store() {
+        let mut store = Store::default();
+        let key = Bytes::from("todo:1");
+
+        let set = JsonCmd::Set(key.clone(), Path::root(), todo(), None);
+        assert_eq!(Ok(Frame::Simple("Ok".to_string())), execute(&mut store, set));
+
+        let append = JsonCmd::ArrAppend(key.clone(), path("$.tags"), vec![json!("urgent")]);
+        assert_eq!(Ok(Frame::Array(vec![Frame::Integer(2)])), execute(&mut store, append));
+
+        let incr = JsonCmd::NumIncrBy(key.clone(), path(".estimate"), Number::from(2));
+        assert_eq!(Ok(Frame::BulkString(Bytes::from("5"))), execute(&mut store, incr));
+        let incr = JsonCmd::NumIncrBy(key.clone(), path(".status"), Number::from(2));
+        assert!(execute(&mut store, incr).is_err());
+
+        let get = JsonCmd::Get(key.clone(), vec![path("tags")]);
+        assert_eq!(Ok(Frame::BulkString(Bytes::from(r#"["docs","urgent"]"#))), execute(&mut store, get));
+
+        let getdel = execute(&mut store, JsonCmd::GetDel(key.clone()));
+        assert!(matches!(getdel, Ok(Frame::BulkString(json)) if json.starts_with(br#"{"#)));
+        assert!(!store.exists(&key));
+        assert_eq!(Ok(Frame::Null), execute(&mut store, JsonCmd::GetDel(key.clone())));
+
+        store.set(Bytes::from("plain"), Bytes::from("x")).unwrap();
+        let get = JsonCmd::Get(Bytes::from("plain"), vec![path("$")]);
+        assert_eq!(Err(wrong_type()), execute(&mut store, get));
+    }
+
+    #[test]
+    fn set_xx_on_missing_key_is_nil() {
+        let mut store = Store::default();
+        let key = Bytes::from("todo:404");
+
+        let set = JsonCmd::Set(key.clone(), path("$.status"), json!("done"), Some(Condition::Xx));
+        assert_eq!(Ok(Frame::Null), execute(&mut store, set));
+        let set = JsonCmd::Set(key.clone(), path("$.status"), json!("done"), None);
+        assert!(execute(&mut store, set).is_err());
+        assert!(!store.exists(&key));
+    }
+
+    #[test]
+    fn numincrby_keeps_integers() {
+        assert_eq!(Some(Number::from(5)), add(&Number::from(2), &Number::from(3)));
+        assert_eq!(Number::from_f64(2.5), add(&Number::from(2), &Number::from_f64(0.5).unwrap()));
+    }
+}
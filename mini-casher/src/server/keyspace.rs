@@ -0,0 +1,278 @@
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use crate::client::Client;
+use crate::core::command::keyspace::{KeyspaceCmd, Migrate, Restore, Scan};
+use crate::core::command::string::Expiry;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::core::glob::glob_match;
+use crate::server::aof::Aof;
+use crate::storage::sharded::Sharded;
+use crate::storage::engine::StorageEngine;
+use crate::storage::snapshot::{dump_value, restore_value};
+use crate::Storage;
+
+///Тайм-аут `MIGRATE` при `timeout = 0`, как в redis
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+///`allowed` отбирает ключи, доступные пользователю, в ответе `SCAN`
+pub fn execute(store: &mut dyn StorageEngine, cmd: KeyspaceCmd, allowed: impl Fn(&[u8]) -> bool) -> Result<Frame, CashError> {
+    match cmd {
+        KeyspaceCmd::Scan(scan) => Ok(self::scan(store, scan, allowed)),
+        KeyspaceCmd::Type(key) => Ok(Frame::Simple(store.type_name(&key).unwrap_or("none").to_string())),
+        KeyspaceCmd::Ttl(key) => Ok(Frame::Integer(ttl(store, &key, Duration::from_secs(1)))),
+        KeyspaceCmd::Pttl(key) => Ok(Frame::Integer(ttl(store, &key, Duration::from_millis(1)))),
+        KeyspaceCmd::Dump(key) => Ok(store
+            .get_value(&key)?
+            .map(|value| Frame::BulkString(Bytes::from(dump_value(&value))))
+            .unwrap_or(Frame::Null)),
+        KeyspaceCmd::Restore(restore) => self::restore(store, restore),
+        KeyspaceCmd::Migrate(_) => Err(Error::Storage("'migrate' doesn't work on the storage alone".to_string())),
+    }
+}
+
+fn scan(store: &dyn StorageEngine, scan: Scan, allowed: impl Fn(&[u8]) -> bool) -> Frame {
+    let (cursor, keys) = store.scan(scan.cursor as usize, scan.count as usize);
+    scan_reply(&scan, cursor, keys, allowed, |key| store.type_name(key))
+}
+
+///Шаг `SCAN` по всем частям хранилища
+pub fn scan_shards<E: StorageEngine>(storage: &Sharded<E>, scan: Scan, allowed: impl Fn(&[u8]) -> bool) -> Result<Frame, CashError> {
+    let (cursor, keys) = storage.scan(scan.cursor as usize, scan.count as usize)?;
+    let type_name = |key: &[u8]| storage.shard(key).ok().and_then(|store| store.type_name(key));
+    Ok(scan_reply(&scan, cursor, keys, allowed, type_name))
+}
+
+fn scan_reply(
+    scan: &Scan,
+    cursor: usize,
+    keys: Vec<Bytes>,
+    allowed: impl Fn(&[u8]) -> bool,
+    type_name: impl Fn(&[u8]) -> Option<&'static str>,
+) -> Frame {
+    let keys = keys
+        .into_iter()
+        .filter(|key| allowed(key))
+        .filter(|key| scan.pattern.as_ref().is_none_or(|pattern| glob_match(pattern, key)))
+        .filter(|key| scan.kind.as_ref().is_none_or(|kind| type_name(key).is_some_and(|name| name.eq_ignore_ascii_case(kind))))
+        .map(Frame::BulkString)
+        .collect();
+
+    Frame::Array(vec![Frame::BulkString(Bytes::from(cursor.to_string())), Frame::Array(keys)])
+}
+
+///Оставшееся время в единицах `unit` с округлением вверх, `-1` - без срока, `-2` - ключа нет
+fn ttl(store: &mut dyn StorageEngine, key: &[u8], unit: Duration) -> i64 {
+    if !store.exists(key) {
+        return -2;
+    }
+    match store.deadline(key) {
+        Some(at) => at.saturating_duration_since(Instant::now()).as_nanos().div_ceil(unit.as_nanos()) as i64,
+        None => -1,
+    }
+}
+
+///Ключ со сроком, который уже истек, не создается: так же ведет себя `SET` с `PXAT` в прошлом
+fn restore(store: &mut dyn StorageEngine, restore: Restore) -> Result<Frame, CashError> {
+    if !restore.replace && store.exists(&restore.key) {
+        return Err(Error::Storage("target key name already exists".to_string()));
+    }
+
+    let value = restore_value(&restore.payload)?;
+    let deadline = match (restore.ttl, restore.absttl) {
+        (0, _) => None,
+        (ttl, false) => Expiry::Px(ttl).deadline(),
+        (ttl, true) => Expiry::PxAt(ttl).deadline(),
+    };
+
+    if deadline.is_some_and(|at| at <= Instant::now()) {
+        store.delete(&restore.key);
+    } else {
+        store.set_value(restore.key.clone(), value)?;
+        store.expire(&restore.key, deadline);
+    }
+    Ok(Frame::Simple("Ok".to_string()))
+}
+
+///Переносит ключи на другой сервер командами `RESTORE` и удаляет их здесь, если не задан `COPY`.
+///Блокировка `writes` на запись держится всё время переноса: команды записи ждут, и ключи
+///не меняются, пока их копии в пути. Журнал AOF держится до конца, чтобы удаление легло после всех записей. Удаление записывается в AOF как `DELETE`, чтобы при чтении журнала
+///перенос не повторялся. Ответ `NOKEY` - ни одного ключа нет
+pub async fn migrate<E: StorageEngine>(
+    storage: &Storage<E>,
+    aof: &tokio::sync::Mutex<Aof>,
+    writes: &tokio::sync::RwLock<()>,
+    migrate: Migrate,
+) -> Result<Frame, CashError> {
+    let _frozen = writes.write().await;
+    let mut aof = aof.lock().await;
+
+    let mut payloads = vec![];
+    for key in &migrate.keys {
+        let mut store = storage.shard(key)?;
+        let Some(value) = store.get_value(key)? else { continue };
+        let ttl = store.deadline(key).map(|at| (at.saturating_duration_since(Instant::now()).as_millis() as u64).max(1));
+        payloads.push(Restore {
+            key: key.clone(),
+            ttl: ttl.unwrap_or(0),
+            payload: Bytes::from(dump_value(&value)),
+            replace: migrate.replace,
+            absttl: false,
+        });
+    }
+
+    if payloads.is_empty() {
+        return Ok(Frame::Simple("NOKEY".to_string()));
+    }
+
+    let timeout = match migrate.timeout {
+        0 => DEFAULT_TIMEOUT,
+        millis => Duration::from_millis(millis),
+    };
+    let mut moved = vec![];
+    let transfer = tokio::time::timeout(timeout, send(&migrate, payloads, &mut moved)).await;
+
+    if !migrate.copy {
+        for key in &moved {
+            storage.shard(key)?.delete(key);
+            if aof.is_enabled() {
+                aof.append(&[Bytes::from("delete"), key.clone()])?;
+            }
+        }
+    }
+
+    match transfer {
+        Ok(Ok(())) => Ok(Frame::Simple("Ok".to_string())),
+        Ok(Err(err)) => Err(err),
+        Err(_) => Err(Error::Storage(format!("IOERR timeout migrating to {}:{}", migrate.host, migrate.port))),
+    }
+}
+
+///Отправляет ключи по одному. В `moved` попадают ключи, которые приняла целевая сторона
+async fn send(migrate: &Migrate, payloads: Vec<Restore>, moved: &mut Vec<Bytes>) -> Result<(), CashError> {
+    let addr = format!("{}:{}", migrate.host, migrate.port);
+    let mut client = Client::try_connect(&addr)
+        .await
+        .map_err(|err| Error::Storage(format!("IOERR can't connect to {}: {}", addr, err)))?;
+
+    if let Some(auth) = &migrate.auth {
+        if let Frame::Error(err) = client.auth(auth).await? {
+            return Err(Error::Storage(format!("target instance replied with error: {}", err)));
+        }
+    }
+
+    for restore in payloads {
+        let key = restore.key.clone();
+        match client.keyspace(&KeyspaceCmd::Restore(restore)).await? {
+            Frame::Error(err) => return Err(Error::Storage(format!("target instance replied with error: {}", err))),
+            _ => moved.push(key),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod keyspace_tests {
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use crate::core::connection::Connection;
+    use crate::storage::Store;
+    use super::*;
+
+    fn restore_cmd(key: &str, ttl: u64, payload: Vec<u8>, replace: bool, absttl: bool) -> Restore {
+        Restore { key: Bytes::from(key.to_string()), ttl, payload: Bytes::from(payload), replace, absttl }
+    }
+
+    #[test]
+    fn dump_and_restore() {
+        let mut store = Store::default();
+        store.set(Bytes::from("a"), Bytes::from("1")).unwrap();
+
+        let Ok(Frame::BulkString(payload)) = execute(&mut store, KeyspaceCmd::Dump(Bytes::from("a")), |_| true) else { panic!() };
+        assert_eq!(Ok(Frame::Null), execute(&mut store, KeyspaceCmd::Dump(Bytes::from("b")), |_| true));
+
+        assert!(restore(&mut store, restore_cmd("a", 0, payload.to_vec(), false, false)).is_err());
+        restore(&mut store, restore_cmd("b", 5000, payload.to_vec(), false, false)).unwrap();
+        assert_eq!(Ok(Some(Bytes::from("1"))), store.get(b"b"));
+        assert!(store.deadline(b"b").is_some());
+
+        restore(&mut store, restore_cmd("b", 0, payload.to_vec(), true, false)).unwrap();
+        assert_eq!(None, store.deadline(b"b"));
+
+        restore(&mut store, restore_cmd("b", 1, payload.to_vec(), true, true)).unwrap();
+        assert!(!store.exists(b"b"));
+
+        assert!(restore(&mut store, restore_cmd("c", 0, b"garbage".to_vec(), false, false)).is_err());
+    }
+
+    #[test]
+    fn scan_type_and_ttl() {
+        let mut store = Store::default();
+        for i in 0..25 {
+            store.set(Bytes::from(format!("todo:{}", i)), Bytes::from("x")).unwrap();
+        }
+        store.set(Bytes::from("session"), Bytes::from("x")).unwrap();
+        store.expire(b"session", Some(Instant::now() + Duration::from_millis(1500)));
+
+        let mut keys = vec![];
+        let mut cursor = 0;
+        loop {
+            let scan = Scan { cursor, pattern: Some(Bytes::from("todo:*")), count: 10, kind: Some("string".to_string()) };
+            let Ok(Frame::Array(reply)) = execute(&mut store, KeyspaceCmd::Scan(scan), |key| key != b"todo:0") else { panic!() };
+            let [Frame::BulkString(next), Frame::Array(batch)] = &reply[..] else { panic!() };
+            keys.extend(batch.iter().cloned());
+            cursor = std::str::from_utf8(next).unwrap().parse().unwrap();
+            if cursor == 0 {
+                break;
+            }
+        }
+        assert_eq!(24, keys.len());
+
+        let run = |store: &mut Store, cmd| execute(store, cmd, |_| true).unwrap();
+        assert_eq!(Frame::Simple("string".to_string()), run(&mut store, KeyspaceCmd::Type(Bytes::from("session"))));
+        assert_eq!(Frame::Simple("none".to_string()), run(&mut store, KeyspaceCmd::Type(Bytes::from("nope"))));
+        assert_eq!(Frame::Integer(2), run(&mut store, KeyspaceCmd::Ttl(Bytes::from("session"))));
+        assert_eq!(Frame::Integer(-1), run(&mut store, KeyspaceCmd::Pttl(Bytes::from("todo:1"))));
+        assert_eq!(Frame::Integer(-2), run(&mut store, KeyspaceCmd::Pttl(Bytes::from("nope"))));
+    }
+
+    ///Цель принимает одну команду `RESTORE` и отвечает `Ok`
+    async fn target() -> (u16, tokio::task::JoinHandle<Option<Frame>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(socket);
+            let frame = connection.read_frame().await.unwrap();
+            connection.write_frame(&Frame::Simple("Ok".to_string())).await.unwrap();
+            frame
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn migrate_moves_keys() {
+        let storage: Storage = Arc::new(Sharded::default());
+        storage.shard(b"a").unwrap().set(Bytes::from("a"), Bytes::from("1")).unwrap();
+        let aof = tokio::sync::Mutex::new(Aof::default());
+        let writes = tokio::sync::RwLock::new(());
+
+        let (port, target) = target().await;
+        let cmd = Migrate {
+            host: "127.0.0.1".to_string(),
+            port,
+            keys: vec![Bytes::from("a"), Bytes::from("missing")],
+            timeout: 1000,
+            copy: false,
+            replace: false,
+            auth: None,
+        };
+        assert_eq!(Ok(Frame::Simple("Ok".to_string())), super::migrate(&storage, &aof, &writes, cmd.clone()).await);
+        assert!(!storage.shard(b"a").unwrap().exists(b"a"));
+
+        let Some(Frame::Array(args)) = target.await.unwrap() else { panic!() };
+        assert_eq!(Frame::BulkString(Bytes::from("restore")), args[0]);
+
+        assert_eq!(Ok(Frame::Simple("NOKEY".to_string())), super::migrate(&storage, &aof, &writes, cmd).await);
+    }
+}
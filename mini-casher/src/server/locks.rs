@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use tokio::sync::Notify;
+use crate::core::command::lock::LockCmd;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+
+///Момент через `after`. Срок, который не помещается в `Instant`, - ошибка, а не паника
+///под блокировкой состояния
+fn after(now: Instant, after: Duration) -> Result<Instant, CashError> {
+    now.checked_add(after).ok_or_else(|| Error::CommandParse("lock timeout is out of range".to_string()))
+}
+
+#[derive(Debug)]
+struct Held {
+    token: u64,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    held: HashMap<Bytes, Held>,
+    last_token: u64,
+}
+
+///Блокировки с токенами ограждения. Токен растет с каждым захватом любой блокировки,
+///поэтому новый владелец всегда получает больший токен, чем все прежние,
+///и хранилище может отклонять запись с устаревшим токеном
+#[derive(Debug, Default)]
+pub struct Locks {
+    state: Mutex<State>,
+    released: Notify,
+}
+
+impl Locks {
+    ///Захват без ожидания. `Err` - момент, когда истечет блокировка текущего владельца
+    fn try_acquire(&self, name: &Bytes, ttl: Duration) -> Result<Result<u64, Instant>, CashError> {
+        let now = Instant::now();
+        let expires_at = after(now, ttl)?;
+        let mut state = self.state.lock()?;
+
+        if let Some(held) = state.held.get(name) {
+            if held.expires_at > now {
+                return Ok(Err(held.expires_at));
+            }
+        }
+
+        state.last_token += 1;
+        let token = state.last_token;
+        state.held.insert(name.clone(), Held { token, expires_at });
+        Ok(Ok(token))
+    }
+
+    ///Захват с ожиданием до `wait`. Ждущие просыпаются при освобождении блокировки
+    ///или когда истекает срок текущего владельца. `None` - не дождались
+    pub async fn acquire(&self, name: &Bytes, ttl: Duration, wait: Option<Duration>) -> Result<Option<u64>, CashError> {
+        let deadline = wait.map(|wait| after(Instant::now(), wait)).transpose()?;
+
+        loop {
+            //подписка до проверки, чтобы не пропустить освобождение между ними
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            let expires_at = match self.try_acquire(name, ttl)? {
+                Ok(token) => return Ok(Some(token)),
+                Err(expires_at) => expires_at,
+            };
+
+            let Some(deadline) = deadline.filter(|d| *d > Instant::now()) else {
+                return Ok(None);
+            };
+
+            tokio::select! {
+                _ = released => {}
+                _ = tokio::time::sleep_until(deadline.min(expires_at).into()) => {}
+            }
+        }
+    }
+
+    ///Освобождает блокировку, только если она ещё принадлежит владельцу токена
+    pub fn release(&self, name: &[u8], token: u64) -> Result<bool, CashError> {
+        let mut state = self.state.lock()?;
+
+        let owned = state.held.get(name).is_some_and(|held| held.token == token);
+        if !owned {
+            return Ok(false);
+        }
+
+        let held = state.held.remove(name).expect("lock is held");
+        self.released.notify_waiters();
+        Ok(held.expires_at > Instant::now())
+    }
+
+    ///Продлевает блокировку владельца токена на `ttl` от текущего момента.
+    ///Истекшую блокировку продлить нельзя: её уже мог захватить другой клиент
+    pub fn extend(&self, name: &[u8], token: u64, ttl: Duration) -> Result<bool, CashError> {
+        let now = Instant::now();
+        let expires_at = after(now, ttl)?;
+        let mut state = self.state.lock()?;
+
+        match state.held.get_mut(name) {
+            Some(held) if held.token == token && held.expires_at > now => {
+                held.expires_at = expires_at;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub async fn execute(&self, cmd: LockCmd) -> Result<Frame, CashError> {
+        match cmd {
+            LockCmd::Acquire { name, ttl, wait } => match self.acquire(&name, ttl, wait).await? {
+                Some(token) => Ok(Frame::Integer(token as i64)),
+                None => Ok(Frame::Null),
+            },
+            LockCmd::Release { name, token } => Ok(Frame::Integer(self.release(&name, token)? as i64)),
+            LockCmd::Extend { name, token, ttl } => Ok(Frame::Integer(self.extend(&name, token, ttl)? as i64)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod locks_tests {
+    use std::sync::Arc;
+    use super::*;
+
+    fn name() -> Bytes {
+        Bytes::from("jobs")
+    }
+
+    #[tokio::test]
+    async fn tokens_increase_and_release_checks_owner() {
+        let locks = Locks::default();
+        let ttl = Duration::from_secs(10);
+
+        let first = locks.acquire(&name(), ttl, None).await.unwrap().unwrap();
+        assert_eq!(None, locks.acquire(&name(), ttl, None).await.unwrap());
+        assert!(!locks.release(&name(), first + 1).unwrap());
+        assert!(locks.extend(&name(), first, ttl).unwrap());
+        assert!(locks.release(&name(), first).unwrap());
+
+        let second = locks.acquire(&name(), ttl, None).await.unwrap().unwrap();
+        assert!(second > first);
+    }
+
+    #[tokio::test]
+    async fn huge_timeouts_are_errors() {
+        let locks = Locks::default();
+        assert!(locks.acquire(&name(), Duration::MAX, None).await.is_err());
+        assert!(locks.acquire(&name(), Duration::from_secs(10), Some(Duration::MAX)).await.is_err());
+
+        //состояние не испорчено паникой, остальные команды работают
+        let token = locks.acquire(&Bytes::from("other"), Duration::from_secs(10), None).await.unwrap().unwrap();
+        assert!(locks.extend(b"other", token, Duration::MAX).is_err());
+        assert!(locks.release(b"other", token).unwrap());
+    }
+
+    #[tokio::test]
+    async fn expired_lock_is_taken_over() {
+        let locks = Locks::default();
+
+        let first = locks.acquire(&name(), Duration::from_millis(20), None).await.unwrap().unwrap();
+        let second = locks.acquire(&name(), Duration::from_secs(10), Some(Duration::from_secs(1))).await.unwrap();
+
+        assert!(second.is_some_and(|token| token > first));
+        assert!(!locks.extend(&name(), first, Duration::from_secs(1)).unwrap());
+        assert!(!locks.release(&name(), first).unwrap());
+    }
+
+    #[tokio::test]
+    async fn waiter_wakes_on_release() {
+        let locks = Arc::new(Locks::default());
+        let token = locks.acquire(&name(), Duration::from_secs(60), None).await.unwrap().unwrap();
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move { locks.acquire(&name(), Duration::from_secs(60), Some(Duration::from_secs(5))).await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let start = Instant::now();
+        locks.release(&name(), token).unwrap();
+
+        assert!(waiter.await.unwrap().unwrap().is_some());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,584 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use tokio::net::{TcpListener, TcpStream};
+use crate::Storage;
+use crate::core::command::acl::AclCmd;
+use crate::core::command::client::Kill;
+use crate::core::command::config::ConfigCmd;
+use crate::core::command::keyspace::KeyspaceCmd;
+use crate::core::command::persistence::PersistenceCmd;
+use crate::core::command::table::Flag;
+use crate::core::command::Command;
+use crate::core::connection::Connection;
+use crate::core::error::{CashError};
+use crate::core::frames::Frame;
+use crate::server::acl::Acl;
+use crate::server::actor::{Execution, KeyFilter, Keyspace};
+use crate::server::aof::{Aof, Fsync};
+use crate::server::clients::Clients;
+use crate::server::locks::Locks;
+use crate::server::config::Config;
+use crate::server::monitor::Monitor;
+use crate::server::persistence::Persistence;
+use crate::server::slowlog::SlowLog;
+use crate::storage::sharded::Sharded;
+use crate::storage::engine::{Engine, EngineOptions, StorageEngine};
+use crate::storage::logfile::LogStore;
+use crate::storage::Store;
+
+pub mod clients;
+pub mod actor;
+pub mod acl;
+pub mod aof;
+pub mod slowlog;
+pub mod monitor;
+pub mod config;
+pub mod strings;
+pub mod bitmap;
+pub mod check;
+pub mod filter;
+pub mod hyperloglog;
+pub mod json;
+pub mod keyspace;
+pub mod locks;
+pub mod persistence;
+pub mod search;
+pub mod throttle;
+
+///Общее состояние сервера, которое разделяют все соединения
+pub struct Shared<E: StorageEngine = Store> {
+    storage: Storage<E>,
+    clients: Arc<Clients>,
+    acl: Arc<Acl>,
+    slowlog: Arc<SlowLog>,
+    monitor: Arc<Monitor>,
+    config: Arc<Config>,
+    locks: Arc<Locks>,
+    persistence: Arc<Persistence>,
+    aof: Arc<tokio::sync::Mutex<Aof>>,
+    ///Включен ли AOF, без блокировки журнала. Меняется только под `writes` на запись
+    appendonly: Arc<AtomicBool>,
+    ///Команды записи держат её на чтение и идут параллельно. `MIGRATE` и включение
+    ///или выключение AOF берут её на запись и ждут, пока записи закончатся
+    writes: Arc<tokio::sync::RwLock<()>>,
+    ///Поток хранилища при `execution actor`
+    keyspace: Option<Keyspace>,
+}
+
+impl<E: StorageEngine> Clone for Shared<E> {
+    fn clone(&self) -> Self {
+        Shared {
+            storage: self.storage.clone(),
+            clients: self.clients.clone(),
+            acl: self.acl.clone(),
+            slowlog: self.slowlog.clone(),
+            monitor: self.monitor.clone(),
+            config: self.config.clone(),
+            locks: self.locks.clone(),
+            persistence: self.persistence.clone(),
+            aof: self.aof.clone(),
+            appendonly: self.appendonly.clone(),
+            writes: self.writes.clone(),
+            keyspace: self.keyspace.clone(),
+        }
+    }
+}
+
+///Состояние одного соединения
+#[derive(Debug)]
+pub struct Session {
+    id: u64,
+    user: String,
+    authenticated: bool,
+}
+
+pub async fn run(config: Config) {
+    let (engine, options) = config.storage_engine().unwrap();
+    match engine {
+        Engine::Memory => serve::<Store>(config, options).await,
+        Engine::Log => serve::<LogStore>(config, options).await,
+    }
+}
+
+async fn serve<E: StorageEngine + 'static>(config: Config, options: EngineOptions) {
+    let addr = config.addr().unwrap();
+    let listener = TcpListener::bind(&addr).await.unwrap();
+    let requirepass = Some(config.get("requirepass").unwrap()).filter(|p| !p.is_empty());
+    let slower_than = config.get_int("slowlog-log-slower-than").unwrap();
+    let slowlog_max_len = config.get_int("slowlog-max-len").unwrap() as usize;
+
+    let storage = match Sharded::<E>::open(&options) {
+        Ok(storage) => storage,
+        Err(err) => {
+            log::error!("Can't open the storage: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let aof = match restore(&config, &storage) {
+        Ok(aof) => aof,
+        Err(err) => {
+            //пустое хранилище перезаписало бы данные при первом сохранении
+            log::error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let storage = Arc::new(storage);
+    let appendonly = aof.enabled();
+    let aof = Arc::new(tokio::sync::Mutex::new(aof));
+    let writes = Arc::new(tokio::sync::RwLock::new(()));
+    let keyspace = match config.execution().unwrap() {
+        Execution::Locks => None,
+        Execution::Actor => match Keyspace::spawn(storage.clone(), aof.clone(), appendonly.clone(), writes.clone()) {
+            Ok(keyspace) => Some(keyspace),
+            Err(err) => {
+                log::error!("{}", err);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let shared = Shared {
+        storage,
+        clients: Arc::new(Clients::default()),
+        acl: Arc::new(Acl::new(requirepass)),
+        slowlog: Arc::new(SlowLog::new(slower_than, slowlog_max_len)),
+        monitor: Arc::new(Monitor::default()),
+        config: Arc::new(config),
+        locks: Arc::new(Locks::default()),
+        persistence: Arc::new(Persistence::default()),
+        aof,
+        appendonly,
+        writes,
+        keyspace,
+    };
+
+    let cron = shared.persistence.clone().cron(shared.storage.clone(), shared.config.clone());
+    tokio::spawn(cron);
+    tokio::spawn(aof_cron(shared.clone()));
+
+    log::info!("Listening: {}", addr);
+
+    loop {
+        let (socket, _) = tokio::select! {
+            accepted = listener.accept() => accepted.unwrap(),
+            _ = tokio::signal::ctrl_c() => return shutdown(&shared).await,
+        };
+        let shared = shared.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = handler(socket, shared).await {
+                log::error!("{}", err);
+            }
+        });
+    }
+}
+
+///Восстанавливает данные при запуске. С включенным AOF данные читаются из него;
+///если файла ещё нет, загружается снимок, и с него начинается новый AOF.
+///Движок, который сам хранит данные на диске, уже открыт с ними: повтор AOF применил бы команды второй раз,
+///поэтому журнал только продолжается
+fn restore<E: StorageEngine>(config: &Config, storage: &Sharded<E>) -> Result<Aof, CashError> {
+    let (append_only, fsync) = config.append_only()?;
+    let aof_path = config.aof_path()?;
+    let mut aof = Aof::default();
+    aof.set_fsync(fsync);
+
+    if !storage.is_empty()? {
+        log::info!("DB loaded from the storage engine: {} keys", storage.len()?);
+        match append_only {
+            true if aof_path.exists() => aof.open(&aof_path)?,
+            true => aof.create(&aof_path, storage)?,
+            false => {}
+        }
+        return Ok(aof);
+    }
+
+    let loading = |path: &std::path::Path, err: CashError| CashError::Storage(format!("Can't load '{}': {}", path.display(), err));
+
+    if append_only {
+        if let Some(commands) = aof::replay(&aof_path, storage).map_err(|e| loading(&aof_path, e))? {
+            log::info!("DB loaded from append only file: {} keys, {} commands", storage.len()?, commands);
+            aof.open(&aof_path)?;
+            return Ok(aof);
+        }
+    }
+
+    let snapshot = config.snapshot_path()?;
+    if let Some(keys) = persistence::load(&snapshot, storage).map_err(|e| loading(&snapshot, e))? {
+        log::info!("DB loaded from disk: {} keys", keys);
+    }
+    if append_only {
+        aof.create(&aof_path, storage)?;
+    }
+    Ok(aof)
+}
+
+///Раз в секунду сбрасывает AOF на диск для `appendfsync everysec`
+///и запускает перезапись, когда журнал вырос на `auto-aof-rewrite-percentage`
+async fn aof_cron<E: StorageEngine>(shared: Shared<E>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+
+        let percentage = shared.config.get_int("auto-aof-rewrite-percentage").unwrap_or_default() as u64;
+        let min_size = shared.config.get_memory("auto-aof-rewrite-min-size").unwrap_or_default() as u64;
+
+        let rewrite = {
+            let mut aof = shared.aof.lock().await;
+            if aof.fsync() == Fsync::EverySec {
+                aof.sync();
+            }
+            aof.needs_rewrite(percentage, min_size)
+        };
+
+        if rewrite {
+            log::info!("Starting automatic rewriting of AOF on {}% growth", percentage);
+            if let Err(err) = aof::bgrewrite(shared.aof.clone(), shared.storage.clone()).await {
+                log::warn!("{}", err);
+            }
+        }
+    }
+}
+
+///Остановка по сигналу: как `SHUTDOWN` в redis, сохраняет снимок, если заданы правила `save`
+async fn shutdown<E: StorageEngine>(shared: &Shared<E>) {
+    log::info!("Received SIGINT, shutting down");
+    shared.aof.lock().await.close();
+
+    let save = shared.config.save_rules().is_ok_and(|rules| !rules.is_empty());
+    if save {
+        let result = shared
+            .config
+            .snapshot_path()
+            .and_then(|path| shared.persistence.save(&shared.storage, &path));
+        if let Err(err) = result {
+            log::error!("Error trying to save the DB: {}", err);
+        }
+    }
+}
+
+///Обслуживает соединение, пока клиент его не закроет или не придет `CLIENT KILL`
+async fn handler<E: StorageEngine + 'static>(socket: TcpStream, shared: Shared<E>) -> Result<(), CashError> {
+    let addr = socket.peer_addr()?;
+    let mut connection = Connection::new(socket);
+
+    if shared.clients.count()? as i64 >= shared.config.get_int("maxclients")? {
+        let error = Frame::Error("max number of clients reached".to_string());
+        return connection.write_frame(&error).await;
+    }
+
+    let (id, kill) = shared.clients.register(addr)?;
+    let mut session = Session {
+        id,
+        user: "default".to_string(),
+        authenticated: shared.acl.default_authenticated()?,
+    };
+
+    let result = loop {
+        let timeout = shared.config.get_int("timeout")?;
+        let command = tokio::select! {
+            command = read_command(&mut connection) => command,
+            _ = kill.notified() => {
+                log::info!("client {} killed", id);
+                break Ok(());
+            }
+            _ = idle(timeout) => {
+                log::info!("client {} closed after {}s of idle time", id, timeout);
+                break Ok(());
+            }
+        };
+
+        let response = match command {
+            Ok(Some((command, args))) => {
+                if !matches!(command, Command::Client(_)) {
+                    shared.clients.wait_unpaused(command.is_write()).await?;
+                }
+                shared.clients.touch(id, command.name())?;
+
+                let spec = command.spec();
+                let result = match authorize(&command, &args, &shared, &session) {
+                    Ok(()) if matches!(command, Command::Monitor) => {
+                        break shared.monitor.serve(&mut connection, &kill).await;
+                    }
+                    Ok(()) => {
+                        shared.monitor.feed(spec, &args, 0, addr);
+
+                        //команды записи попадают в AOF в том же порядке, в каком выполнены,
+                        //поэтому включенный журнал блокируется до выполнения. При выключенном
+                        //записи идут параллельно под `writes`. Блокировки не сохраняются,
+                        //`MIGRATE` блокирует журнал сам и записывает удаление ключей,
+                        //а поток хранилища так же блокирует журнал для своих команд
+                        let queued = shared.keyspace.is_some() && on_keyspace(&command);
+                        let logged = !queued && !matches!(command, Command::Lock(_) | Command::Keyspace(KeyspaceCmd::Migrate(_)));
+                        let write = command.is_write() && logged;
+                        let writing = match write {
+                            true => Some(shared.writes.read().await),
+                            false => None,
+                        };
+                        let mut aof = match write && shared.appendonly.load(Ordering::SeqCst) {
+                            true => Some(shared.aof.lock().await).filter(|aof| aof.is_enabled()),
+                            false => None,
+                        };
+
+                        let start = Instant::now();
+                        let result = execute(command, &args, &shared, &mut session).await;
+                        let duration = start.elapsed();
+
+                        if let (Some(aof), Ok(frame)) = (&mut aof, &result) {
+                            if let Err(err) = aof.log(&args, frame, &shared.storage) {
+                                log::error!("{}", err);
+                            }
+                        }
+                        drop(aof);
+                        drop(writing);
+
+                        //ожидание блокирующей команды - не медленное выполнение
+                        if !spec.has_flag(Flag::Blocking) && shared.slowlog.is_slow(duration) {
+                            let name = shared.clients.name(id)?;
+                            shared.slowlog.record(spec, &args, duration, addr, name)?;
+                        }
+                        result
+                    }
+                    Err(err) => Err(err),
+                };
+
+                match result {
+                    Ok(frame) => frame,
+                    Err(err) => Frame::Error(err.to_string())
+                }
+            }
+            Ok(None) => break Ok(()),
+            Err(CashError::CommandParse(err)) => Frame::Error(err),
+            Err(err) => break Err(err),
+        };
+
+        if let Err(err) = connection.write_frame(&response).await {
+            break Err(err);
+        }
+    };
+
+    shared.clients.unregister(id)?;
+    result
+}
+
+///Срабатывает, когда клиент простаивает дольше `timeout` секунд.
+///Нулевой `timeout` отключает проверку
+async fn idle(timeout: i64) {
+    if timeout > 0 {
+        tokio::time::sleep(Duration::from_secs(timeout as u64)).await
+    } else {
+        std::future::pending().await
+    }
+}
+
+///Читает следующую команду вместе с её аргументами.
+///Аргументы нужны для проверки ключей по позициям из таблицы команд
+async fn read_command(connection: &mut Connection) -> Result<Option<(Command, Vec<Bytes>)>, CashError> {
+    let some_frame = match connection.read_frame().await {
+        Ok(some_frame) => some_frame,
+        Err(err) => {
+            connection.write_frame(&Frame::Error(err.to_string())).await?;
+            return Err(err);
+        }
+    };
+
+    if let Some(frame) = some_frame {
+        let args = frame_args(&frame);
+        let command = Command::from_frame(frame)?;
+        Ok(Some((command, args)))
+    } else {
+        Ok(None)
+    }
+}
+
+///Аргументы команды из кадра
+pub(crate) fn frame_args(frame: &Frame) -> Vec<Bytes> {
+    match frame {
+        Frame::Array(array) => array
+            .iter()
+            .filter_map(|f| match f {
+                Frame::BulkString(arg) => Some(arg.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+///Проверяет, что соединение прошло `AUTH` и пользователю доступна команда
+fn authorize<E: StorageEngine>(command: &Command, args: &[Bytes], shared: &Shared<E>, session: &Session) -> Result<(), CashError> {
+    let spec = command.spec();
+
+    if spec.has_flag(Flag::NoAuth) {
+        return Ok(());
+    }
+
+    if !session.authenticated {
+        return Err(CashError::NoAuth);
+    }
+
+    shared.acl.check(&session.user, spec, args)
+}
+
+///Выполняет команду, которой нужно только хранилище, над частью хранилища
+pub fn apply(store: &mut dyn StorageEngine, command: Command) -> Result<Frame, CashError> {
+    match command {
+        Command::Get(get) => Ok(store.get(get.key())?.map(Frame::BulkString).unwrap_or(Frame::Null)),
+        Command::Set(set) => strings::set(store, set),
+        Command::String(cmd) => strings::execute(store, cmd),
+        Command::Bitmap(cmd) => bitmap::execute(store, cmd),
+        Command::Hll(cmd) => hyperloglog::execute(store, cmd),
+        Command::Json(cmd) => json::execute(store, cmd),
+        Command::Filter(cmd) => filter::execute(store, cmd),
+        Command::Throttle(cmd) => throttle::execute(store, cmd),
+        Command::Keyspace(cmd) => keyspace::execute(store, cmd, |_: &[u8]| true),
+        Command::Search(cmd) => search::execute(&mut [store], cmd, |_: &[u8]| true),
+        Command::Delete(delete) => {
+            store.delete(delete.key()).ok_or(CashError::Storage("remove failed".to_string()))?;
+            Ok(Frame::Simple("Ok".to_string()))
+        }
+        Command::Len => Ok(Frame::Integer(store.len() as i64)),
+        command => Err(CashError::Storage(format!("'{}' doesn't work on the storage alone", command.name()))),
+    }
+}
+
+///Выполняет команду хранилища в частях с её ключами, которые берутся из `args` по таблице команд.
+///Длина, `ALL`, поиск и `SCAN` используют все части. Так же команды применяются при чтении AOF
+pub fn dispatch<E: StorageEngine>(storage: &Sharded<E>, command: Command, args: &[Bytes], allowed: impl Fn(&[u8]) -> bool) -> Result<Frame, CashError> {
+    match command {
+        Command::Len => Ok(Frame::Integer(storage.len()? as i64)),
+        Command::All => {
+            let mut all = vec![];
+            storage.for_each(|store| {
+                let entries = store.entries()?.into_iter().filter(|(key, _, _)| allowed(key));
+                all.extend(entries.map(|(_, value, _)| Frame::BulkString(value.to_bytes())));
+                Ok(())
+            })?;
+            Ok(Frame::Array(all))
+        }
+        Command::Search(cmd) => search::execute(&mut storage.lock_all()?, cmd, allowed),
+        Command::Keyspace(KeyspaceCmd::Scan(scan)) => keyspace::scan_shards(storage, scan, allowed),
+        command => {
+            let keys = command.spec().key_args(args);
+            storage.with_keys(&keys, |store| apply(store, command))
+        }
+    }
+}
+
+///Команды, которые выполняет `dispatch`: при `execution actor` они уходят в поток хранилища.
+///`MIGRATE` ждет ответа другого сервера и выполняется соединением
+fn on_keyspace(command: &Command) -> bool {
+    match command {
+        Command::Keyspace(KeyspaceCmd::Migrate(_)) => false,
+        Command::Get(_)
+        | Command::Set(_)
+        | Command::String(_)
+        | Command::Bitmap(_)
+        | Command::Hll(_)
+        | Command::Json(_)
+        | Command::Filter(_)
+        | Command::Throttle(_)
+        | Command::Delete(_)
+        | Command::Keyspace(_)
+        | Command::Search(_)
+        | Command::All
+        | Command::Len => true,
+        _ => false,
+    }
+}
+
+async fn execute<E: StorageEngine + 'static>(command: Command, args: &[Bytes], shared: &Shared<E>, session: &mut Session) -> Result<Frame, CashError> {
+    let storage = &shared.storage;
+
+    match command {
+        Command::Keyspace(KeyspaceCmd::Migrate(migrate)) => keyspace::migrate(storage, &shared.aof, &shared.writes, migrate).await,
+        Command::Lock(cmd) => shared.locks.execute(cmd).await,
+        Command::Persistence(PersistenceCmd::BgRewriteAof) => {
+            aof::bgrewrite(shared.aof.clone(), storage.clone()).await?;
+            Ok(Frame::Simple("Background append only file rewriting started".to_string()))
+        }
+        Command::Persistence(cmd) => shared.persistence.execute(storage, &shared.config, cmd),
+        Command::Ping => Ok(Frame::Simple("PONG".to_string())),
+        Command::Client(cmd) => shared.clients.execute(session.id, cmd),
+        Command::Auth(auth) => {
+            shared.acl.authenticate(&auth)?;
+            session.user = auth.user().to_string();
+            session.authenticated = true;
+            shared.clients.set_user(session.id, &session.user)?;
+            Ok(Frame::Simple("Ok".to_string()))
+        }
+        Command::Acl(AclCmd::DelUser(users)) => {
+            let deleted = shared.acl.del_users(&users)?;
+            for user in &deleted {
+                shared.clients.kill(&Kill::by_user(user.clone()))?;
+            }
+            Ok(Frame::Integer(deleted.len() as i64))
+        }
+        Command::Acl(cmd) => shared.acl.execute(&session.user, cmd),
+        Command::Command(cmd) => Ok(cmd.execute()),
+        Command::Slowlog(cmd) => shared.slowlog.execute(cmd),
+        Command::Config(ConfigCmd::Set(pairs)) => {
+            shared.config.set(&pairs)?;
+            for (name, _) in &pairs {
+                apply_config(shared, name).await?;
+            }
+            Ok(Frame::Simple("Ok".to_string()))
+        }
+        Command::Config(cmd) => shared.config.execute(cmd),
+        Command::Monitor => Err(CashError::CommandParse("monitor is served by the connection handler".to_string())),
+        //остальные команды - команды хранилища, см. `on_keyspace`
+        command => {
+            //ключи команды уже проверены, фильтр нужен только командам, которые обходят хранилище
+            let allowed: KeyFilter = match command {
+                Command::Keyspace(_) | Command::Search(_) | Command::All => Box::new(shared.acl.key_filter(&session.user)?),
+                _ => Box::new(|_: &[u8]| true),
+            };
+            match &shared.keyspace {
+                Some(keyspace) => keyspace.execute(command, args, allowed).await,
+                None => dispatch(storage, command, args, allowed),
+            }
+        }
+    }
+}
+
+///Применяет параметр, измененный через `CONFIG SET`, к работающему серверу.
+///`maxclients` и `timeout` читаются из конфигурации при каждом использовании
+async fn apply_config<E: StorageEngine>(shared: &Shared<E>, name: &str) -> Result<(), CashError> {
+    let config = &shared.config;
+
+    match name {
+        "requirepass" => {
+            let requirepass = Some(config.get("requirepass")?).filter(|p| !p.is_empty());
+            shared.acl.set_requirepass(requirepass)?;
+        }
+        "loglevel" => log::set_max_level(config.log_level()?),
+        "slowlog-log-slower-than" | "slowlog-max-len" => {
+            let slower_than = config.get_int("slowlog-log-slower-than")?;
+            let max_len = config.get_int("slowlog-max-len")? as usize;
+            shared.slowlog.configure(slower_than, max_len);
+        }
+        "appendonly" | "appendfsync" => {
+            let (append_only, fsync) = config.append_only()?;
+            let _frozen = shared.writes.write().await;
+            let mut aof = shared.aof.lock().await;
+            aof.set_fsync(fsync);
+
+            match (append_only, aof.is_enabled()) {
+                (true, false) => aof.create(&config.aof_path()?, &shared.storage)?,
+                (false, true) => aof.close(),
+                _ => {}
+            }
+        }
+        "maxmemory" | "maxmemory-policy" | "maxmemory-samples" => {
+            let (maxmemory, policy, samples) = config.memory_limits()?;
+            shared.storage.configure(maxmemory, policy, samples)?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
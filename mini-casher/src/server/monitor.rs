@@ -0,0 +1,70 @@
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use bytes::Bytes;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, Notify};
+use crate::core::command::table::CommandSpec;
+use crate::core::connection::Connection;
+use crate::core::error::CashError;
+use crate::core::frames::Frame;
+
+///Сколько строк может накопиться у медленного подписчика,
+///прежде чем он начнет их пропускать
+const CAPACITY: usize = 1024;
+
+///Рассылка выполняемых команд всем соединениям в режиме `MONITOR`
+#[derive(Debug)]
+pub struct Monitor {
+    sender: broadcast::Sender<String>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self { sender: broadcast::channel(CAPACITY).0 }
+    }
+}
+
+impl Monitor {
+    ///Отправляет команду подписчикам в формате redis:
+    ///`1681000000.123456 [0 127.0.0.1:50000] "set" "key" "value"`.
+    ///Строка формируется только если есть хотя бы один подписчик
+    pub fn feed(&self, spec: &CommandSpec, args: &[Bytes], db: u64, addr: SocketAddr) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let args: Vec<String> = spec
+            .loggable_args(args)
+            .iter()
+            .map(|arg| format!("\"{}\"", arg.escape_ascii()))
+            .collect();
+
+        let line = format!("{}.{:06} [{} {}] {}", now.as_secs(), now.subsec_micros(), db, addr, args.join(" "));
+        let _ = self.sender.send(line);
+    }
+
+    ///Переводит соединение в режим `MONITOR`: каждая выполненная команда
+    ///отправляется клиенту простой строкой, пока клиент не закроет соединение
+    ///или не придет `CLIENT KILL`
+    pub async fn serve(&self, connection: &mut Connection, kill: &Notify) -> Result<(), CashError> {
+        let mut receiver = self.sender.subscribe();
+        connection.write_frame(&Frame::Simple("Ok".to_string())).await?;
+
+        loop {
+            tokio::select! {
+                line = receiver.recv() => match line {
+                    Ok(line) => connection.write_frame(&Frame::Simple(line)).await?,
+                    Err(RecvError::Lagged(skipped)) => log::warn!("monitor skipped {} commands", skipped),
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+                frame = connection.read_frame() => {
+                    if frame?.is_none() {
+                        return Ok(());
+                    }
+                }
+                _ = kill.notified() => return Ok(()),
+            }
+        }
+    }
+}
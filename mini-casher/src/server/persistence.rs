@@ -0,0 +1,199 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::core::command::persistence::PersistenceCmd;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::server::config::Config;
+use crate::storage::engine::StorageEngine;
+use crate::storage::sharded::Sharded;
+use crate::storage::snapshot::Snapshot;
+use crate::Storage;
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+///Запись через временный файл с `fsync`: при сбое на диске остается прежний снимок
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+///Загружает снимок при запуске. Отсутствие файла - не ошибка, это первый запуск
+pub fn load<E: StorageEngine>(path: &Path, storage: &Sharded<E>) -> Result<Option<usize>, CashError> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::Storage(format!("can't read '{}': {}", path.display(), err))),
+    };
+
+    Ok(Some(Snapshot::decode(&bytes)?.restore(storage)?))
+}
+
+///Снимки хранилища: `SAVE`, `BGSAVE`, `LASTSAVE` и правила `save`.
+///Фоновый снимок копирует данные под блокировкой хранилища, а кодирует и пишет файл
+///в отдельном потоке, поэтому клиенты ждут только копирования
+#[derive(Debug)]
+pub struct Persistence {
+    last_save: AtomicU64,
+    saving: AtomicBool,
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Self { last_save: AtomicU64::new(unix_now()), saving: AtomicBool::new(false) }
+    }
+}
+
+impl Persistence {
+    ///Unix-время последнего успешного снимка
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    fn saved<E: StorageEngine>(&self, storage: &Storage<E>, dirty: u64) -> Result<(), CashError> {
+        storage.saved(dirty)?;
+        self.last_save.store(unix_now(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    ///Снимок с блокировкой сервера на всё время записи
+    pub fn save<E: StorageEngine>(&self, storage: &Storage<E>, path: &Path) -> Result<(), CashError> {
+        if self.saving.load(Ordering::Acquire) {
+            return Err(Error::Storage("Background save already in progress".to_string()));
+        }
+
+        let mut guards = storage.lock_all()?;
+        let snapshot = Snapshot::capture_all(&mut guards)?;
+        write_atomic(path, &snapshot.encode())
+            .map_err(|e| Error::Storage(format!("can't write '{}': {}", path.display(), e)))?;
+
+        drop(guards);
+        storage.saved(snapshot.dirty)?;
+        self.last_save.store(unix_now(), Ordering::Relaxed);
+        log::info!("DB saved on disk");
+        Ok(())
+    }
+
+    ///Запускает фоновый снимок и сразу возвращается
+    pub fn bgsave<E: StorageEngine + 'static>(self: &Arc<Self>, storage: &Storage<E>, path: PathBuf) -> Result<(), CashError> {
+        if self.saving.swap(true, Ordering::AcqRel) {
+            return Err(Error::Storage("Background save already in progress".to_string()));
+        }
+
+        let snapshot = match storage.snapshot() {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                self.saving.store(false, Ordering::Release);
+                return Err(err);
+            }
+        };
+
+        let persistence = self.clone();
+        let storage = storage.clone();
+        tokio::task::spawn_blocking(move || {
+            match write_atomic(&path, &snapshot.encode()) {
+                Ok(()) => match persistence.saved(&storage, snapshot.dirty) {
+                    Ok(()) => log::info!("Background saving terminated with success"),
+                    Err(err) => log::error!("{}", err),
+                },
+                Err(err) => log::error!("Background saving error: can't write '{}': {}", path.display(), err),
+            }
+            persistence.saving.store(false, Ordering::Release);
+        });
+
+        Ok(())
+    }
+
+    ///Проверяет правила `save` раз в секунду и запускает фоновый снимок,
+    ///если хотя бы одно из них выполнено
+    pub async fn cron<E: StorageEngine + 'static>(self: Arc<Self>, storage: Storage<E>, config: Arc<Config>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+        loop {
+            interval.tick().await;
+
+            let (Ok(rules), Ok(path)) = (config.save_rules(), config.snapshot_path()) else { continue };
+            let Ok(dirty) = storage.dirty() else { continue };
+            let elapsed = unix_now().saturating_sub(self.last_save());
+
+            if let Some((seconds, changes)) = rules.into_iter().find(|(s, c)| dirty >= *c && dirty > 0 && elapsed >= *s) {
+                log::info!("{} changes in {} seconds. Saving...", changes, seconds);
+                if let Err(err) = self.bgsave(&storage, path) {
+                    log::warn!("{}", err);
+                }
+            }
+        }
+    }
+
+    pub fn execute<E: StorageEngine + 'static>(self: &Arc<Self>, storage: &Storage<E>, config: &Config, cmd: PersistenceCmd) -> Result<Frame, CashError> {
+        match cmd {
+            PersistenceCmd::Save => {
+                self.save(storage, &config.snapshot_path()?)?;
+                Ok(Frame::Simple("Ok".to_string()))
+            }
+            PersistenceCmd::BgSave => {
+                self.bgsave(storage, config.snapshot_path()?)?;
+                Ok(Frame::Simple("Background saving started".to_string()))
+            }
+            PersistenceCmd::LastSave => Ok(Frame::Integer(self.last_save() as i64)),
+            PersistenceCmd::BgRewriteAof => Err(Error::Storage("the append only file is served by the server".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod persistence_tests {
+    use bytes::Bytes;
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.cdb", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = path("save-and-load");
+        let storage: Storage = Arc::new(Sharded::default());
+        storage.shard(b"todo:1").unwrap().set(Bytes::from("todo:1"), Bytes::from("x")).unwrap();
+
+        let persistence = Persistence::default();
+        persistence.save(&storage, &path).unwrap();
+        assert_eq!(Ok(0), storage.dirty());
+
+        let loaded = Sharded::default();
+        assert_eq!(Ok(Some(1)), load(&path, &loaded));
+        assert_eq!(Ok(Some(Bytes::from("x"))), loaded.shard(b"todo:1").unwrap().get(b"todo:1"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Ok(None), load(&path, &loaded));
+    }
+
+    #[tokio::test]
+    async fn one_background_save_at_a_time() {
+        let path = path("bgsave");
+        let storage: Storage = Arc::new(Sharded::default());
+        storage.shard(b"k").unwrap().set(Bytes::from("k"), Bytes::from("v")).unwrap();
+
+        let persistence = Arc::new(Persistence::default());
+        persistence.saving.store(true, Ordering::Release);
+        assert!(persistence.bgsave(&storage, path.clone()).is_err());
+        assert!(persistence.save(&storage, &path).is_err());
+
+        persistence.saving.store(false, Ordering::Release);
+        persistence.bgsave(&storage, path.clone()).unwrap();
+        while persistence.saving.load(Ordering::Acquire) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert_eq!(Ok(0), storage.dirty());
+        assert!(load(&path, &Sharded::default()).is_ok_and(|loaded| loaded == Some(1)));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
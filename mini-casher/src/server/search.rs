@@ -0,0 +1,130 @@
+use std::ops::DerefMut;
+use bytes::Bytes;
+use crate::core::command::search::SearchCmd;
+use crate::core::error::CashError;
+use crate::core::frames::Frame;
+use crate::storage::engine::StorageEngine;
+
+///Индексы есть в каждой части хранилища и охватывают её документы, поэтому команда
+///выполняется над всеми частями сразу, а найденные ключи собираются по порядку.
+///`allowed` - фильтр ключей пользователя ACL: чужие документы не попадают ни в ответ, ни в общее количество
+pub fn execute<S: DerefMut<Target = E>, E: StorageEngine + ?Sized>(
+    stores: &mut [S],
+    cmd: SearchCmd,
+    allowed: impl Fn(&[u8]) -> bool,
+) -> Result<Frame, CashError> {
+    match cmd {
+        SearchCmd::Create(def) => {
+            for store in stores.iter_mut() {
+                store.create_index(def.clone())?;
+            }
+            Ok(Frame::Simple("Ok".to_string()))
+        }
+        SearchCmd::DropIndex(name) => {
+            let mut dropped = false;
+            for store in stores.iter_mut() {
+                dropped |= store.drop_index(&name);
+            }
+            match dropped {
+                true => Ok(Frame::Simple("Ok".to_string())),
+                false => Ok(Frame::Error(format!("{}: no such index", name))),
+            }
+        }
+        SearchCmd::Search(name, query, options) => {
+            let mut keys = vec![];
+            for (shard, store) in stores.iter().enumerate() {
+                keys.extend(store.search(&name, &query)?.into_iter().filter(|k| allowed(k)).map(|key| (key, shard)));
+            }
+            keys.sort_unstable();
+            let mut reply = vec![Frame::Integer(keys.len() as i64)];
+
+            for (key, shard) in keys.into_iter().skip(options.offset).take(options.limit) {
+                if options.no_content {
+                    reply.push(Frame::BulkString(key));
+                    continue;
+                }
+
+                let Some(value) = stores[shard].get_value(&key)? else { continue };
+                reply.push(Frame::BulkString(key));
+                reply.push(Frame::Array(vec![
+                    Frame::BulkString(Bytes::from("$")),
+                    Frame::BulkString(value.to_bytes()),
+                ]));
+            }
+
+            Ok(Frame::Array(reply))
+        }
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use serde_json::json;
+    use crate::core::command::Command;
+    use crate::storage::{Store, Value};
+    use super::*;
+
+    fn run(store: &mut Store, args: &[&str]) -> Result<Frame, CashError> {
+        let frame = Frame::Array(args.iter().map(|a| Frame::BulkString(Bytes::from(a.to_string()))).collect());
+        let Ok(Command::Search(cmd)) = Command::from_frame(frame) else { panic!("{:?}", args) };
+        execute(&mut [store], cmd, |key| !key.starts_with(b"todo:secret"))
+    }
+
+    #[test]
+    fn index_follows_writes() {
+        let mut store = Store::default();
+        store.set_value(Bytes::from("todo:a"), Value::Json(json!({"status": "new"}))).unwrap();
+
+        run(&mut store, &["ft.create", "idx", "on", "json", "prefix", "1", "todo:", "schema", "$.status", "tag"]).unwrap();
+        store.set_value(Bytes::from("todo:b"), Value::Json(json!({"status": "new"}))).unwrap();
+        store.set_value(Bytes::from("todo:secret"), Value::Json(json!({"status": "new"}))).unwrap();
+        store.set_value(Bytes::from("todo:a"), Value::Json(json!({"status": "done"}))).unwrap();
+
+        let found = run(&mut store, &["ft.search", "idx", "@status:{new}", "nocontent"]);
+        assert_eq!(Ok(Frame::Array(vec![Frame::Integer(1), Frame::BulkString(Bytes::from("todo:b"))])), found);
+
+        store.delete(b"todo:b");
+        let found = run(&mut store, &["ft.search", "idx", "@status:{new}"]);
+        assert_eq!(Ok(Frame::Array(vec![Frame::Integer(0)])), found);
+    }
+
+    #[test]
+    fn paging() {
+        let mut store = Store::default();
+        run(&mut store, &["ft.create", "idx", "on", "json", "schema", "$.n", "numeric"]).unwrap();
+        for i in 0..5 {
+            store.set_value(Bytes::from(format!("k{}", i)), Value::Json(json!({"n": i}))).unwrap();
+        }
+
+        let Ok(Frame::Array(page)) = run(&mut store, &["ft.search", "idx", "@n:[1 +inf]", "limit", "1", "2"]) else { panic!() };
+        assert_eq!(Frame::Integer(4), page[0]);
+        assert_eq!(Frame::BulkString(Bytes::from("k2")), page[1]);
+        assert_eq!(Frame::BulkString(Bytes::from("k3")), page[3]);
+        assert_eq!(5, page.len());
+    }
+
+    #[test]
+    fn results_of_all_shards() {
+        let (mut first, mut second) = (Store::default(), Store::default());
+        let frame = |args: &[&str]| Frame::Array(args.iter().map(|a| Frame::BulkString(Bytes::from(a.to_string()))).collect());
+        let search = |args: &[&str]| match Command::from_frame(frame(args)) {
+            Ok(Command::Search(cmd)) => cmd,
+            _ => panic!("{:?}", args),
+        };
+
+        let create = search(&["ft.create", "idx", "on", "json", "schema", "$.n", "numeric"]);
+        execute(&mut [&mut first, &mut second], create, |_| true).unwrap();
+        first.set_value(Bytes::from("k1"), Value::Json(json!({"n": 1}))).unwrap();
+        second.set_value(Bytes::from("k0"), Value::Json(json!({"n": 0}))).unwrap();
+        second.set_value(Bytes::from("k2"), Value::Json(json!({"n": 2}))).unwrap();
+
+        let query = search(&["ft.search", "idx", "@n:[0 +inf]", "nocontent"]);
+        let keys = ["k0", "k1", "k2"].map(|k| Frame::BulkString(Bytes::from(k)));
+        let found = execute(&mut [&mut first, &mut second], query, |_| true);
+        assert_eq!(Ok(Frame::Array([vec![Frame::Integer(3)], keys.to_vec()].concat())), found);
+
+        let drop = search(&["ft.dropindex", "idx"]);
+        assert_eq!(Ok(Frame::Simple("Ok".to_string())), execute(&mut [&mut first, &mut second], drop, |_| true));
+        assert!(!first.drop_index("idx") && !second.drop_index("idx"));
+    }
+}
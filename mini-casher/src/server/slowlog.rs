@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use bytes::Bytes;
+use crate::core::command::slowlog::SlowlogCmd;
+use crate::core::command::table::CommandSpec;
+use crate::core::error::CashError;
+use crate::core::frames::Frame;
+
+///Сколько аргументов и байт аргумента сохраняется в записи
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+#[derive(Debug)]
+struct Entry {
+    id: u64,
+    timestamp: u64,
+    duration: Duration,
+    args: Vec<Bytes>,
+    addr: SocketAddr,
+    name: String,
+}
+
+impl Entry {
+    fn frame(&self) -> Frame {
+        Frame::Array(vec![
+            Frame::Integer(self.id as i64),
+            Frame::Integer(self.timestamp as i64),
+            Frame::Integer(self.duration.as_micros() as i64),
+            Frame::Array(self.args.iter().cloned().map(Frame::BulkString).collect()),
+            Frame::BulkString(Bytes::from(self.addr.to_string())),
+            Frame::BulkString(Bytes::from(self.name.clone())),
+        ])
+    }
+}
+
+///Журнал команд, выполнявшихся дольше порога `slower_than` (в микросекундах).
+///Хранит не больше `max_len` последних записей, старые вытесняются.
+///Отрицательный порог отключает журнал, нулевой - записывает все команды
+#[derive(Debug)]
+pub struct SlowLog {
+    next_id: AtomicU64,
+    slower_than: AtomicI64,
+    max_len: AtomicUsize,
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl SlowLog {
+    pub fn new(slower_than: i64, max_len: usize) -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            slower_than: AtomicI64::new(slower_than),
+            max_len: AtomicUsize::new(max_len),
+            entries: Mutex::new(VecDeque::with_capacity(max_len)),
+        }
+    }
+
+    ///Новые порог и размер журнала из `CONFIG SET`.
+    ///Лишние записи вытесняются при следующей записи
+    pub fn configure(&self, slower_than: i64, max_len: usize) {
+        self.slower_than.store(slower_than, Ordering::Relaxed);
+        self.max_len.store(max_len, Ordering::Relaxed);
+    }
+
+    pub fn is_slow(&self, duration: Duration) -> bool {
+        let slower_than = self.slower_than.load(Ordering::Relaxed);
+        slower_than >= 0 && duration.as_micros() >= slower_than as u128
+    }
+
+    ///Записывает команду, если она выполнялась дольше порога
+    pub fn record(
+        &self,
+        spec: &CommandSpec,
+        args: &[Bytes],
+        duration: Duration,
+        addr: SocketAddr,
+        name: String,
+    ) -> Result<(), CashError> {
+        if !self.is_slow(duration) {
+            return Ok(());
+        }
+
+        let entry = Entry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            duration,
+            args: trim_args(&spec.loggable_args(args)),
+            addr,
+            name,
+        };
+
+        let max_len = self.max_len.load(Ordering::Relaxed);
+        let mut entries = self.entries.lock()?;
+        entries.push_front(entry);
+        entries.truncate(max_len);
+
+        Ok(())
+    }
+
+    pub fn execute(&self, cmd: SlowlogCmd) -> Result<Frame, CashError> {
+        match cmd {
+            SlowlogCmd::Get(count) => {
+                let entries = self.entries.lock()?;
+                let count = match count {
+                    Some(count) if count < 0 => entries.len(),
+                    Some(count) => count as usize,
+                    None => 10,
+                };
+
+                Ok(Frame::Array(entries.iter().take(count).map(|e| e.frame()).collect()))
+            }
+            SlowlogCmd::Len => Ok(Frame::Integer(self.entries.lock()?.len() as i64)),
+            SlowlogCmd::Reset => {
+                self.entries.lock()?.clear();
+                Ok(Frame::Simple("Ok".to_string()))
+            }
+        }
+    }
+}
+
+///Обрезает длинные команды, как это делает redis:
+///не больше 32 аргументов и 128 байт в аргументе
+fn trim_args(args: &[Bytes]) -> Vec<Bytes> {
+    let mut trimmed: Vec<Bytes> = args
+        .iter()
+        .take(if args.len() > MAX_ARGS { MAX_ARGS - 1 } else { MAX_ARGS })
+        .map(|arg| {
+            if arg.len() > MAX_ARG_LEN {
+                let rest = arg.len() - MAX_ARG_LEN;
+                let mut short = arg[..MAX_ARG_LEN].to_vec();
+                short.extend(format!("... ({} more bytes)", rest).as_bytes());
+                Bytes::from(short)
+            } else {
+                arg.clone()
+            }
+        })
+        .collect();
+
+    if args.len() > MAX_ARGS {
+        let rest = args.len() - MAX_ARGS + 1;
+        trimmed.push(Bytes::from(format!("... ({} more arguments)", rest)));
+    }
+
+    trimmed
+}
+
+#[cfg(test)]
+mod slowlog_tests {
+    use crate::core::command::table;
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<Bytes> {
+        args.iter().map(|a| Bytes::from(a.to_string())).collect()
+    }
+
+    #[test]
+    fn record_keeps_last_entries() {
+        let log = SlowLog::new(0, 2);
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let spec = table::lookup("get").unwrap();
+
+        for key in ["a", "b", "c"] {
+            log.record(spec, &args(&["get", key]), Duration::from_millis(1), addr, String::new()).unwrap();
+        }
+
+        assert_eq!(Ok(Frame::Integer(2)), log.execute(SlowlogCmd::Len));
+        let Ok(Frame::Array(entries)) = log.execute(SlowlogCmd::Get(Some(1))) else { panic!() };
+        assert!(matches!(&entries[0], Frame::Array(entry) if entry[0] == Frame::Integer(2)));
+    }
+
+    #[test]
+    fn record_skips_fast_commands() {
+        let log = SlowLog::new(1_000, 10);
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let spec = table::lookup("get").unwrap();
+
+        log.record(spec, &args(&["get", "a"]), Duration::from_micros(10), addr, String::new()).unwrap();
+        assert_eq!(Ok(Frame::Integer(0)), log.execute(SlowlogCmd::Len));
+    }
+
+    #[test]
+    fn trim_long_commands() {
+        let long = "x".repeat(200);
+        let many: Vec<&str> = std::iter::repeat_n(long.as_str(), 40).collect();
+
+        let trimmed = trim_args(&args(&many));
+        assert_eq!(MAX_ARGS, trimmed.len());
+        assert!(trimmed[0].ends_with(b"(72 more bytes)"));
+        assert_eq!(Bytes::from("... (9 more arguments)"), trimmed[MAX_ARGS - 1]);
+    }
+}
@@ -0,0 +1,227 @@
+use bytes::{Bytes, BytesMut};
+use crate::core::command::Set;
+use crate::core::command::string::{Condition, Expiry, StringCmd};
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::storage::engine::StorageEngine;
+
+///Наибольшая длина строки, как `proto-max-bulk-len` в redis
+const MAX_STRING_LEN: u64 = 512 * 1024 * 1024;
+
+fn ok() -> Frame {
+    Frame::Simple("Ok".to_string())
+}
+
+fn bulk_or_null(value: Option<Bytes>) -> Frame {
+    value.map(Frame::BulkString).unwrap_or(Frame::Null)
+}
+
+///Записывает значение с новым сроком жизни.
+///`KEEPTTL` переносит срок старого значения, без срока ключ становится постоянным
+fn write(store: &mut dyn StorageEngine, key: Bytes, value: Bytes, expiry: Option<Expiry>) -> Result<(), CashError> {
+    let deadline = match expiry {
+        Some(Expiry::KeepTtl) => return store.update(key, value),
+        Some(expiry) => expiry.deadline(),
+        None => None,
+    };
+
+    store.set(key.clone(), value)?;
+    if deadline.is_some() {
+        store.expire(&key, deadline);
+    }
+
+    Ok(())
+}
+
+///`SET` с опциями. Если условие `NX`/`XX` не выполнено, значение не меняется
+///и возвращается `Null` (или старое значение с `GET`)
+pub fn set(store: &mut dyn StorageEngine, set: Set) -> Result<Frame, CashError> {
+    let old = if set.get() { store.get(set.key())? } else { None };
+    let exists = store.exists(set.key());
+
+    let allowed = match set.condition() {
+        Some(Condition::Nx) => !exists,
+        Some(Condition::Xx) => exists,
+        None => true,
+    };
+
+    if allowed {
+        write(store, set.key().clone(), set.value().clone(), set.expiry())?;
+    }
+
+    match (set.get(), allowed) {
+        (true, _) => Ok(bulk_or_null(old)),
+        (false, true) => Ok(ok()),
+        (false, false) => Ok(Frame::Null),
+    }
+}
+
+pub fn execute(store: &mut dyn StorageEngine, cmd: StringCmd) -> Result<Frame, CashError> {
+    match cmd {
+        StringCmd::Append(key, value) => {
+            let mut new = BytesMut::from(&store.get(&key)?.unwrap_or_default()[..]);
+            new.extend_from_slice(&value);
+            let len = new.len();
+
+            store.update(key, new.freeze())?;
+            Ok(Frame::Integer(len as i64))
+        }
+        StringCmd::StrLen(key) => Ok(Frame::Integer(store.get(&key)?.map_or(0, |v| v.len()) as i64)),
+        StringCmd::GetRange(key, start, end) => {
+            let value = store.get(&key)?.unwrap_or_default();
+            Ok(Frame::BulkString(range(&value, start, end)))
+        }
+        StringCmd::SetRange(key, offset, value) => {
+            let old = store.get(&key)?;
+
+            if value.is_empty() {
+                return Ok(Frame::Integer(old.map_or(0, |v| v.len()) as i64));
+            }
+            if offset + value.len() as u64 > MAX_STRING_LEN {
+                return Err(Error::CommandParse("string exceeds maximum allowed size".to_string()));
+            }
+
+            let offset = offset as usize;
+            let mut new = BytesMut::from(&old.unwrap_or_default()[..]);
+            if new.len() < offset + value.len() {
+                new.resize(offset + value.len(), 0);
+            }
+            new[offset..offset + value.len()].copy_from_slice(&value);
+            let len = new.len();
+
+            store.update(key, new.freeze())?;
+            Ok(Frame::Integer(len as i64))
+        }
+        StringCmd::GetDel(key) => {
+            let value = store.get(&key)?;
+            if value.is_some() {
+                store.delete(&key);
+            }
+            Ok(bulk_or_null(value))
+        }
+        StringCmd::GetEx(key, expiry) => {
+            let value = store.get(&key)?;
+
+            if value.is_some() {
+                match expiry {
+                    Some(Expiry::Persist) => {
+                        store.expire(&key, None);
+                    }
+                    Some(expiry) => {
+                        store.expire(&key, expiry.deadline());
+                    }
+                    None => {}
+                }
+            }
+
+            Ok(bulk_or_null(value))
+        }
+        StringCmd::GetSet(key, value) => {
+            let old = store.get(&key)?;
+            write(store, key, value, None)?;
+            Ok(bulk_or_null(old))
+        }
+        StringCmd::SetNx(key, value) => {
+            if store.exists(&key) {
+                return Ok(Frame::Integer(0));
+            }
+            write(store, key, value, None)?;
+            Ok(Frame::Integer(1))
+        }
+    }
+}
+
+///Подстрока `GETRANGE`: отрицательные индексы считаются с конца,
+///границы обрезаются по длине значения, `end` включительно
+fn range(value: &Bytes, start: i64, end: i64) -> Bytes {
+    let len = value.len() as i64;
+    if len == 0 {
+        return Bytes::new();
+    }
+
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let end = if end < 0 { len + end } else { end.min(len - 1) };
+
+    if start > end || end < 0 {
+        return Bytes::new();
+    }
+
+    value.slice(start as usize..=end as usize)
+}
+
+#[cfg(test)]
+mod strings_tests {
+    use std::time::{Duration, Instant};
+    use crate::storage::Store;
+    use super::*;
+
+    fn key() -> Bytes {
+        Bytes::from("k")
+    }
+
+    fn run(store: &mut Store, cmd: StringCmd) -> Frame {
+        execute(store, cmd).unwrap()
+    }
+
+    #[test]
+    fn getrange_offsets() {
+        let value = Bytes::from("This is a string");
+
+        assert_eq!(Bytes::from("This"), range(&value, 0, 3));
+        assert_eq!(Bytes::from("ing"), range(&value, -3, -1));
+        assert_eq!(value, range(&value, 0, -1));
+        assert_eq!(Bytes::from("string"), range(&value, 10, 100));
+        assert_eq!(Bytes::new(), range(&value, 5, 2));
+        assert_eq!(Bytes::new(), range(&value, -100, -50));
+    }
+
+    #[test]
+    fn setrange_pads_with_zeros() {
+        let mut store = Store::default();
+
+        assert_eq!(Frame::Integer(8), run(&mut store, StringCmd::SetRange(key(), 5, Bytes::from("abc"))));
+        assert_eq!(Ok(Some(Bytes::from(&b"\0\0\0\0\0abc"[..]))), store.get(b"k"));
+        assert_eq!(Frame::Integer(8), run(&mut store, StringCmd::SetRange(key(), 0, Bytes::from("xy"))));
+        assert_eq!(Ok(Some(Bytes::from(&b"xy\0\0\0abc"[..]))), store.get(b"k"));
+    }
+
+    #[test]
+    fn append_keeps_ttl() {
+        let mut store = Store::default();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        store.set(key(), Bytes::from("to")).unwrap();
+        store.expire(b"k", Some(deadline));
+
+        assert_eq!(Frame::Integer(4), run(&mut store, StringCmd::Append(key(), Bytes::from("do"))));
+        assert_eq!(Some(deadline), store.deadline(b"k"));
+    }
+
+    #[test]
+    fn set_conditions_and_get() {
+        let mut store = Store::default();
+
+        let nx = Set::new(key(), Bytes::from("a")).with_condition(Condition::Nx);
+        assert_eq!(Ok(ok()), set(&mut store, nx));
+
+        let nx_again = Set::new(key(), Bytes::from("b")).with_condition(Condition::Nx).with_get();
+        assert_eq!(Ok(Frame::BulkString(Bytes::from("a"))), set(&mut store, nx_again));
+        assert_eq!(Ok(Some(Bytes::from("a"))), store.get(b"k"));
+
+        let xx = Set::new(Bytes::from("missing"), Bytes::from("c")).with_condition(Condition::Xx);
+        assert_eq!(Ok(Frame::Null), set(&mut store, xx));
+    }
+
+    #[test]
+    fn getex_persist_and_getdel() {
+        let mut store = Store::default();
+        store.set(key(), Bytes::from("v")).unwrap();
+
+        run(&mut store, StringCmd::GetEx(key(), Some(Expiry::Ex(10))));
+        assert!(store.deadline(b"k").is_some());
+        run(&mut store, StringCmd::GetEx(key(), Some(Expiry::Persist)));
+        assert!(store.deadline(b"k").is_none());
+
+        assert_eq!(Frame::BulkString(Bytes::from("v")), run(&mut store, StringCmd::GetDel(key())));
+        assert_eq!(Frame::Null, run(&mut store, StringCmd::GetDel(key())));
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use bytes::Bytes;
+use crate::core::command::throttle::Throttle;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::storage::engine::StorageEngine;
+
+///Время в наносекундах от начала эпохи. Хранится в ключе, поэтому не `Instant`
+fn now() -> i128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as i128
+}
+
+///Наносекунды в секунды с округлением вверх: повтор раньше срока всё равно будет отклонен
+fn seconds(nanos: i128) -> i64 {
+    ((nanos + 999_999_999) / 1_000_000_000) as i64
+}
+
+pub fn execute(store: &mut dyn StorageEngine, cmd: Throttle) -> Result<Frame, CashError> {
+    throttle(store, cmd, now())
+}
+
+///GCRA: каждый запрос сдвигает TAT на `quantity` интервалов `period / count`.
+///Запрос разрешен, если новый TAT опережает текущее время не больше, чем на `max_burst + 1` интервалов.
+///Ответ: `[limited, limit, remaining, retry_after, reset_after]`, время в секундах,
+///`retry_after = -1`, если запрос разрешен или не пройдет никогда
+fn throttle(store: &mut dyn StorageEngine, cmd: Throttle, now: i128) -> Result<Frame, CashError> {
+    let emission = cmd.period.as_nanos() as i128 / cmd.count as i128;
+    let limit = cmd.max_burst as i128 + 1;
+    let tolerance = emission * limit;
+    let increment = emission * cmd.quantity as i128;
+
+    let tat = match store.get(&cmd.key)? {
+        Some(value) => std::str::from_utf8(&value)
+            .ok()
+            .and_then(|tat| tat.parse::<i128>().ok())
+            .ok_or_else(|| Error::Storage("value is not a throttle state".to_string()))?,
+        None => now,
+    }
+    .max(now);
+
+    let new_tat = tat + increment;
+    let allow_at = new_tat - tolerance;
+
+    let (limited, retry_after, ttl) = if now < allow_at {
+        let retry_after = if increment <= tolerance { seconds(allow_at - now) } else { -1 };
+        (true, retry_after, tat - now)
+    } else {
+        let ttl = new_tat - now;
+        store.set(cmd.key.clone(), Bytes::from(new_tat.to_string()))?;
+        store.expire(&cmd.key, Some(Instant::now() + Duration::from_nanos(ttl as u64)));
+        (false, -1, ttl)
+    };
+
+    let next = tolerance - ttl;
+    let remaining = if next > -emission { next / emission } else { 0 };
+
+    Ok(Frame::Array(vec![
+        Frame::Integer(limited as i64),
+        Frame::Integer(limit as i64),
+        Frame::Integer(remaining.max(0) as i64),
+        Frame::Integer(retry_after),
+        Frame::Integer(seconds(ttl)),
+    ]))
+}
+
+#[cfg(test)]
+mod throttle_tests {
+    use crate::storage::Store;
+    use super::*;
+
+    const SECOND: i128 = 1_000_000_000;
+
+    fn cmd(quantity: u64) -> Throttle {
+        //всплеск до 3 запросов, дальше 1 запрос в секунду
+        Throttle { key: Bytes::from("api"), max_burst: 2, count: 1, period: Duration::from_secs(1), quantity }
+    }
+
+    fn reply(store: &mut Store, quantity: u64, now: i128) -> Vec<i64> {
+        match throttle(store, cmd(quantity), now) {
+            Ok(Frame::Array(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    Frame::Integer(n) => n,
+                    _ => panic!(),
+                })
+                .collect(),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn burst_then_steady_rate() {
+        let mut store = Store::default();
+        let start = 1_000 * SECOND;
+
+        assert_eq!(vec![0, 3, 2, -1, 1], reply(&mut store, 1, start));
+        assert_eq!(vec![0, 3, 1, -1, 2], reply(&mut store, 1, start));
+        assert_eq!(vec![0, 3, 0, -1, 3], reply(&mut store, 1, start));
+        assert_eq!(vec![1, 3, 0, 1, 3], reply(&mut store, 1, start));
+
+        //через секунду освобождается ровно одно место
+        assert_eq!(vec![0, 3, 0, -1, 3], reply(&mut store, 1, start + SECOND));
+        assert_eq!(1, reply(&mut store, 1, start + SECOND)[0]);
+    }
+
+    #[test]
+    fn quantity_above_limit_never_passes() {
+        let mut store = Store::default();
+
+        assert_eq!(vec![1, 3, 3, -1, 0], reply(&mut store, 4, SECOND));
+        assert!(!store.exists(b"api"));
+
+        store.set(Bytes::from("api"), Bytes::from("text")).unwrap();
+        assert!(throttle(&mut store, cmd(1), SECOND).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::sync::Arc;
+use std::time::Instant;
+use bytes::Bytes;
+use crate::core::command::search::{IndexDef, Query};
+use crate::core::error::{CashError, Error};
+use crate::storage::eviction::Policy;
+use crate::storage::{wrong_type, Value};
+
+///Движок хранилища из параметра `storage-engine`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    ///Все ключи и значения в памяти, `Store`
+    Memory,
+    ///Значения в журнале на диске, в памяти только ключи, `LogStore`
+    Log,
+}
+
+impl Engine {
+    pub const NAMES: &'static [&'static str] = &["memory", "log"];
+}
+
+impl FromStr for Engine {
+    type Err = CashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(Engine::Memory),
+            "log" => Ok(Engine::Log),
+            _ => Err(Error::Config(format!("unknown storage engine '{}'", s))),
+        }
+    }
+}
+
+///Параметры, с которыми открывается часть хранилища
+#[derive(Debug, Clone, Default)]
+pub struct EngineOptions {
+    pub maxmemory: usize,
+    pub policy: Policy,
+    pub samples: usize,
+    ///Каталог файлов движка
+    pub dir: PathBuf,
+    ///Число частей хранилища
+    pub shards: usize,
+    ///Счетчик изменений для правил `save`, общий у всех частей
+    pub dirty: Arc<AtomicU64>,
+    ///Память всех частей, с ней сравнивается `maxmemory`
+    pub used_memory: Arc<AtomicUsize>,
+}
+
+///Хранилище ключей, над которым выполняются команды.
+///Команды работают с `&mut dyn StorageEngine`, а сервер и `Sharded` обобщены по движку.
+///Строковые операции выражены через значения любого типа и есть у всех движков
+pub trait StorageEngine: Send + Debug {
+    ///Открывает часть `shard` хранилища
+    fn open(options: &EngineOptions, shard: usize) -> Result<Self, CashError>
+    where
+        Self: Sized;
+
+    ///Значение любого типа. Ключ с истекшим сроком жизни удаляется.
+    ///Ошибка - значение не удалось прочитать
+    fn get_value(&mut self, key: &[u8]) -> Result<Option<Value>, CashError>;
+
+    ///Записывает значение и сбрасывает срок жизни ключа, как `SET` в redis
+    fn set_value(&mut self, key: Bytes, value: Value) -> Result<(), CashError>;
+
+    fn delete(&mut self, key: &[u8]) -> Option<Value>;
+
+    ///Задает или снимает срок жизни ключа. `false` - ключа нет
+    fn expire(&mut self, key: &[u8], at: Option<Instant>) -> bool;
+
+    ///Момент истечения ключа, `None` - ключа нет или срок не задан
+    fn deadline(&self, key: &[u8]) -> Option<Instant>;
+
+    ///Имя типа значения без чтения самого значения
+    fn type_name(&self, key: &[u8]) -> Option<&'static str>;
+
+    ///Шаг обхода ключей для `SCAN`: следующий курсор и ключи, кроме истекших.
+    ///Курсор `0` - начало и конец обхода
+    fn scan(&self, cursor: usize, count: usize) -> (usize, Vec<Bytes>);
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///Все ключи со значениями и сроками, кроме истекших, для снимков и `ALL`
+    fn entries(&mut self) -> Result<Vec<(Bytes, Value, Option<Instant>)>, CashError>;
+
+    ///Память под ключи и значения, которые движок держит в памяти
+    fn used_memory(&self) -> usize;
+
+    ///Освобождает место под будущую запись значений `incoming` (ключ и размер), `OOM` - места нет.
+    ///Команда с ключами из нескольких частей сначала резервирует место во всех, а потом пишет,
+    ///чтобы не примениться наполовину
+    fn reserve(&mut self, incoming: &[(&[u8], usize)]) -> Result<(), CashError>;
+
+    ///Новые ограничения из `CONFIG SET`
+    fn configure(&mut self, maxmemory: usize, policy: Policy, samples: usize);
+
+    ///Число изменений с последнего снимка
+    fn dirty(&self) -> u64;
+
+    ///Снимок с `dirty` изменениями записан. Изменения, сделанные во время записи, остаются
+    fn saved(&self, dirty: u64);
+
+    ///Создает индекс и сразу индексирует уже записанные документы
+    fn create_index(&mut self, def: IndexDef) -> Result<(), CashError>;
+
+    fn drop_index(&mut self, name: &str) -> bool;
+
+    fn index_defs(&self) -> Vec<IndexDef>;
+
+    ///Ключи документов под запрос. Ключи с истекшим сроком жизни пропускаются
+    fn search(&self, name: &str, query: &Query) -> Result<Vec<Bytes>, CashError>;
+
+    ///Строковое значение по ключу, `WRONGTYPE` для других типов
+    fn get(&mut self, key: &[u8]) -> Result<Option<Bytes>, CashError> {
+        match self.get_value(key)? {
+            Some(Value::String(bytes)) => Ok(Some(bytes)),
+            Some(_) => Err(wrong_type()),
+            None => Ok(None),
+        }
+    }
+
+    ///Движки, которые хранят значения вне памяти, проверяют ключ без чтения значения
+    fn exists(&mut self, key: &[u8]) -> bool {
+        matches!(self.get_value(key), Ok(Some(_)))
+    }
+
+    fn set(&mut self, key: Bytes, value: Bytes) -> Result<(), CashError> {
+        self.set_value(key, Value::String(value))
+    }
+
+    ///Заменяет значение, сохраняя срок жизни ключа,
+    ///как команды, которые меняют значение на месте (`APPEND`, `SETBIT`, `JSON.SET`)
+    fn update_value(&mut self, key: Bytes, value: Value) -> Result<(), CashError> {
+        let deadline = self.deadline(&key);
+        self.set_value(key.clone(), value)?;
+        if deadline.is_some() {
+            self.expire(&key, deadline);
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, key: Bytes, value: Bytes) -> Result<(), CashError> {
+        self.update_value(key, Value::String(value))
+    }
+}
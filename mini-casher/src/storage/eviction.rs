@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Instant;
+use bytes::Bytes;
+use rand::Rng;
+use crate::core::error::{CashError, Error};
+
+///Начальное значение счетчика LFU, чтобы новые ключи не вытеснялись сразу
+pub const LFU_INIT: u8 = 5;
+///Чем больше множитель, тем медленнее растет счетчик обращений
+const LFU_LOG_FACTOR: f64 = 10.0;
+///Счетчик LFU уменьшается на единицу за каждую минуту без обращений
+const LFU_DECAY_SECS: u64 = 60;
+
+///Политика вытеснения при достижении `maxmemory`.
+///`volatile-*` выбирают только среди ключей со сроком жизни
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Policy {
+    #[default]
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    VolatileLru,
+    VolatileTtl,
+    AllKeysRandom,
+}
+
+impl Policy {
+    pub const NAMES: &'static [&'static str] = &[
+        "noeviction",
+        "allkeys-lru",
+        "allkeys-lfu",
+        "volatile-lru",
+        "volatile-ttl",
+        "allkeys-random",
+    ];
+
+    pub fn is_volatile(&self) -> bool {
+        matches!(self, Policy::VolatileLru | Policy::VolatileTtl)
+    }
+}
+
+impl FromStr for Policy {
+    type Err = CashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "noeviction" => Ok(Policy::NoEviction),
+            "allkeys-lru" => Ok(Policy::AllKeysLru),
+            "allkeys-lfu" => Ok(Policy::AllKeysLfu),
+            "volatile-lru" => Ok(Policy::VolatileLru),
+            "volatile-ttl" => Ok(Policy::VolatileTtl),
+            "allkeys-random" => Ok(Policy::AllKeysRandom),
+            _ => Err(Error::Config(format!("unknown maxmemory policy '{}'", s))),
+        }
+    }
+}
+
+///Сведения о доступе к ключу, по которым выбирается кандидат на вытеснение
+#[derive(Debug, Clone)]
+pub struct Usage {
+    pub accessed: Instant,
+    pub freq: u8,
+    pub expires_at: Option<Instant>,
+}
+
+impl Usage {
+    pub fn new(now: Instant) -> Self {
+        Self { accessed: now, freq: LFU_INIT, expires_at: None }
+    }
+
+    ///Обращение к ключу: обновляет время и логарифмический счетчик LFU как в redis,
+    ///вероятность увеличения падает с ростом счетчика
+    pub fn touch(&mut self, now: Instant) {
+        self.freq = self.decayed_freq(now);
+
+        if self.freq < u8::MAX {
+            let base = self.freq.saturating_sub(LFU_INIT) as f64;
+            let p = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+            if rand::thread_rng().gen::<f64>() < p {
+                self.freq += 1;
+            }
+        }
+
+        self.accessed = now;
+    }
+
+    fn decayed_freq(&self, now: Instant) -> u8 {
+        let minutes = now.duration_since(self.accessed).as_secs() / LFU_DECAY_SECS;
+        self.freq.saturating_sub(minutes.min(u8::MAX as u64) as u8)
+    }
+
+    ///Оценка кандидата: чем больше, тем раньше ключ вытесняется
+    fn score(&self, policy: Policy, now: Instant) -> u128 {
+        match policy {
+            Policy::AllKeysLru | Policy::VolatileLru => now.duration_since(self.accessed).as_micros(),
+            Policy::AllKeysLfu => (u8::MAX - self.decayed_freq(now)) as u128,
+            Policy::VolatileTtl => match self.expires_at {
+                Some(at) => u128::MAX - at.saturating_duration_since(now).as_micros(),
+                None => 0,
+            },
+            Policy::AllKeysRandom | Policy::NoEviction => 0,
+        }
+    }
+}
+
+///Множество ключей с выбором случайного ключа за O(1).
+///Нужно для выборки кандидатов: `HashMap` не умеет отдавать случайный элемент
+#[derive(Debug, Default)]
+pub struct KeySet {
+    keys: Vec<Bytes>,
+    index: HashMap<Bytes, usize>,
+}
+
+impl KeySet {
+    pub fn insert(&mut self, key: &Bytes) {
+        if !self.index.contains_key(key) {
+            self.index.insert(key.clone(), self.keys.len());
+            self.keys.push(key.clone());
+        }
+    }
+
+    pub fn remove(&mut self, key: &[u8]) {
+        if let Some(slot) = self.index.remove(key) {
+            self.keys.swap_remove(slot);
+            if let Some(moved) = self.keys.get(slot) {
+                self.index.insert(moved.clone(), slot);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    ///Часть ключей для `SCAN`. Ключи обходятся с конца: новые ключи добавляются в конец,
+    ///а при удалении на место удаленного переезжает последний, уже пройденный ключ.
+    ///Поэтому ключ, который был в наборе весь обход, вернется хотя бы раз.
+    ///Курсор `0` - начало и конец обхода
+    pub fn scan(&self, cursor: usize, count: usize) -> (usize, &[Bytes]) {
+        let end = match cursor {
+            0 => self.keys.len(),
+            cursor => cursor.min(self.keys.len()),
+        };
+        let start = end.saturating_sub(count.max(1));
+        (start, &self.keys[start..end])
+    }
+
+    pub fn random(&self) -> Option<&Bytes> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        self.keys.get(rand::thread_rng().gen_range(0..self.keys.len()))
+    }
+}
+
+///Приближенный выбор ключа для вытеснения, как в redis:
+///из `samples` случайных ключей выбирается худший по политике
+pub fn pick<'a>(
+    keys: &'a KeySet,
+    samples: usize,
+    policy: Policy,
+    usage: impl Fn(&[u8]) -> Option<&'a Usage>,
+) -> Option<&'a Bytes> {
+    let now = Instant::now();
+
+    (0..samples.max(1))
+        .filter_map(|_| keys.random())
+        .filter_map(|key| usage(key).map(|u| (key, u.score(policy, now))))
+        .max_by_key(|(_, score)| *score)
+        .map(|(key, _)| key)
+}
+
+#[cfg(test)]
+mod eviction_tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn key_set_scan_survives_removal() {
+        let mut keys = KeySet::default();
+        for key in ["a", "b", "c", "d", "e"] {
+            keys.insert(&Bytes::from(key));
+        }
+
+        let (cursor, first) = keys.scan(0, 2);
+        let mut seen = first.to_vec();
+        keys.remove(b"a");
+        keys.insert(&Bytes::from("f"));
+
+        let mut cursor = cursor;
+        loop {
+            let (next, batch) = keys.scan(cursor, 2);
+            seen.extend_from_slice(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        for key in ["b", "c", "d", "e"] {
+            assert!(seen.contains(&Bytes::from(key)));
+        }
+    }
+
+    #[test]
+    fn key_set_swap_remove() {
+        let mut keys = KeySet::default();
+        for key in ["a", "b", "c"] {
+            keys.insert(&Bytes::from(key));
+        }
+
+        keys.remove(b"a");
+        keys.remove(b"a");
+        assert_eq!(2, keys.len());
+        assert_eq!(Some(&1), keys.index.get(b"b".as_slice()));
+        assert_eq!(Some(&0), keys.index.get(b"c".as_slice()));
+    }
+
+    #[test]
+    fn lru_picks_oldest() {
+        let now = Instant::now();
+        let mut keys = KeySet::default();
+        let mut usage: HashMap<Bytes, Usage> = HashMap::new();
+
+        for (i, key) in ["a", "b", "c"].into_iter().enumerate() {
+            keys.insert(&Bytes::from(key));
+            usage.insert(Bytes::from(key), Usage::new(now - Duration::from_secs(10 - i as u64)));
+        }
+
+        let key = pick(&keys, 64, Policy::AllKeysLru, |k| usage.get(k));
+        assert_eq!(Some(&Bytes::from("a")), key);
+    }
+
+    #[test]
+    fn ttl_picks_nearest_expiry() {
+        let now = Instant::now();
+        let mut keys = KeySet::default();
+        let mut usage: HashMap<Bytes, Usage> = HashMap::new();
+
+        for (key, secs) in [("a", 30), ("b", 5), ("c", 60)] {
+            keys.insert(&Bytes::from(key));
+            let mut u = Usage::new(now);
+            u.expires_at = Some(now + Duration::from_secs(secs));
+            usage.insert(Bytes::from(key), u);
+        }
+
+        let key = pick(&keys, 64, Policy::VolatileTtl, |k| usage.get(k));
+        assert_eq!(Some(&Bytes::from("b")), key);
+    }
+}
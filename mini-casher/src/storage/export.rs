@@ -0,0 +1,184 @@
+use std::str::FromStr;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use serde_json::{json, Map};
+use crate::core::error::{CashError, Error};
+use crate::storage::filter::{Bloom, Cuckoo};
+use crate::storage::Value;
+
+///Формат файла `casher-export`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    JsonLines,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "jsonl" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            _ => Err(Error::Config(format!("unknown format '{}', expected jsonl or csv", value))),
+        }
+    }
+}
+
+///Заголовок CSV
+pub const CSV_HEADER: [&str; 6] = ["key", "key_encoding", "type", "ttl", "encoding", "value"];
+
+///Ключ с значением и сроком жизни в миллисекундах, `-1` - без срока
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub key: Bytes,
+    pub value: Value,
+    pub ttl: i64,
+}
+
+///Текст как есть, если это UTF-8, иначе base64
+fn text(bytes: &[u8]) -> (&'static str, String) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => ("utf8", text.to_string()),
+        Err(_) => ("base64", STANDARD.encode(bytes)),
+    }
+}
+
+fn bytes(encoding: &str, text: &str) -> Result<Bytes, CashError> {
+    match encoding {
+        "utf8" => Ok(Bytes::from(text.to_string())),
+        "base64" => STANDARD.decode(text).map(Bytes::from).map_err(|e| invalid(&format!("bad base64: {}", e))),
+        _ => Err(invalid(&format!("unknown encoding '{}'", encoding))),
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Storage(format!("invalid record: {}", reason))
+}
+
+///Значение по имени типа и данным в виде `Value::to_bytes`
+fn value(kind: &str, raw: Bytes) -> Result<Value, CashError> {
+    let value = match kind {
+        "string" => Some(Value::String(raw)),
+        "ReJSON-RL" => serde_json::from_slice(&raw).ok().map(Value::Json),
+        "MBbloom--" => Bloom::decode(&raw).map(Value::Bloom),
+        "MBbloomCF" => Cuckoo::decode(&raw).map(Value::Cuckoo),
+        _ => return Err(invalid(&format!("unknown type '{}'", kind))),
+    };
+    value.ok_or_else(|| invalid(&format!("bad {} value", kind)))
+}
+
+impl Record {
+    ///Строки хранятся текстом, если это UTF-8, документы JSON - как есть, фильтры - в base64
+    fn encoded_value(&self) -> (&'static str, serde_json::Value) {
+        match &self.value {
+            Value::Json(doc) => ("json", doc.clone()),
+            Value::String(raw) => {
+                let (encoding, text) = text(raw);
+                (encoding, json!(text))
+            }
+            value => ("base64", json!(STANDARD.encode(value.to_bytes()))),
+        }
+    }
+
+    ///Строка JSON Lines: `{"key", "type", "ttl", "encoding", "value"}`.
+    ///Ключ не в UTF-8 записывается в base64 с полем `"key_encoding": "base64"`
+    pub fn to_json_line(&self) -> String {
+        let (key_encoding, key) = text(&self.key);
+        let (encoding, value) = self.encoded_value();
+
+        let mut line = Map::new();
+        line.insert("key".to_string(), json!(key));
+        if key_encoding != "utf8" {
+            line.insert("key_encoding".to_string(), json!(key_encoding));
+        }
+        line.insert("type".to_string(), json!(self.value.type_name()));
+        line.insert("ttl".to_string(), json!(self.ttl));
+        line.insert("encoding".to_string(), json!(encoding));
+        line.insert("value".to_string(), value);
+        serde_json::Value::Object(line).to_string()
+    }
+
+    pub fn from_json_line(line: &str) -> Result<Record, CashError> {
+        let line: serde_json::Value = serde_json::from_str(line).map_err(|e| invalid(&e.to_string()))?;
+        let field = |name: &str| line.get(name).and_then(|v| v.as_str()).ok_or_else(|| invalid(&format!("no '{}'", name)));
+
+        let key = bytes(line.get("key_encoding").and_then(|v| v.as_str()).unwrap_or("utf8"), field("key")?)?;
+        let kind = field("type")?;
+        let ttl = line.get("ttl").and_then(|v| v.as_i64()).unwrap_or(-1);
+        let value = match (field("encoding")?, line.get("value")) {
+            ("json", Some(doc)) if kind == "ReJSON-RL" => Value::Json(doc.clone()),
+            (encoding, _) => value(kind, bytes(encoding, field("value")?)?)?,
+        };
+
+        Ok(Record { key, value, ttl })
+    }
+
+    ///Поля в порядке `CSV_HEADER`. Документ JSON записывается текстом
+    pub fn to_csv_row(&self) -> [String; 6] {
+        let (key_encoding, key) = text(&self.key);
+        let (encoding, value) = self.encoded_value();
+        let value = match value {
+            serde_json::Value::String(text) if encoding != "json" => text,
+            value => value.to_string(),
+        };
+
+        [key, key_encoding.to_string(), self.value.type_name().to_string(), self.ttl.to_string(), encoding.to_string(), value]
+    }
+
+    pub fn from_csv_row(row: &[&str]) -> Result<Record, CashError> {
+        let [key, key_encoding, kind, ttl, encoding, value] = row else {
+            return Err(invalid(&format!("expected {} columns", CSV_HEADER.len())));
+        };
+
+        let ttl = ttl.parse().map_err(|_| invalid("bad ttl"))?;
+        let raw = match *encoding {
+            "json" => Bytes::from(value.to_string()),
+            encoding => bytes(encoding, value)?,
+        };
+
+        Ok(Record { key: bytes(key_encoding, key)?, value: self::value(kind, raw)?, ttl })
+    }
+}
+
+#[cfg(test)]
+mod export_tests {
+    use crate::storage::filter::{BLOOM_CAPACITY, BLOOM_ERROR_RATE, BLOOM_EXPANSION};
+    use super::*;
+
+    fn records() -> Vec<Record> {
+        let mut bloom = Bloom::new(BLOOM_ERROR_RATE, BLOOM_CAPACITY, BLOOM_EXPANSION).unwrap();
+        bloom.add(b"item").unwrap();
+
+        vec![
+            Record { key: Bytes::from("todo:1"), value: Value::Json(json!({"title": "a, \"b\"\nc"})), ttl: -1 },
+            Record { key: Bytes::from("session"), value: Value::String(Bytes::from("x")), ttl: 5000 },
+            Record { key: Bytes::from_static(b"\xff"), value: Value::String(Bytes::from_static(b"\x00\xfe")), ttl: -1 },
+            Record { key: Bytes::from("seen"), value: Value::Bloom(bloom), ttl: -1 },
+        ]
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        for record in records() {
+            let line = record.to_json_line();
+            assert!(!line.contains('\n'));
+            assert_eq!(Ok(record), Record::from_json_line(&line));
+        }
+
+        let line = records()[0].to_json_line();
+        assert!(line.contains(r#""value":{"title""#));
+        assert!(Record::from_json_line(r#"{"key":"k","type":"set","ttl":-1,"encoding":"utf8","value":"x"}"#).is_err());
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        for record in records() {
+            let row = record.to_csv_row();
+            let fields: Vec<&str> = row.iter().map(String::as_str).collect();
+            assert_eq!(Ok(record), Record::from_csv_row(&fields));
+        }
+        assert!(Record::from_csv_row(&["k", "utf8", "string"]).is_err());
+    }
+}
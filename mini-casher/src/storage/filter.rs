@@ -0,0 +1,536 @@
+use rand::Rng;
+use crate::core::error::{CashError, Error};
+use crate::server::hyperloglog::murmur64a;
+
+const HASH_SEED: u64 = 0x5bd1e995;
+
+pub const BLOOM_ERROR_RATE: f64 = 0.01;
+pub const BLOOM_CAPACITY: u64 = 100;
+pub const BLOOM_EXPANSION: u32 = 2;
+///Во сколько раз уменьшается доля ошибок каждого следующего слоя,
+///чтобы общая доля ошибок фильтра оставалась в пределах заданной
+const BLOOM_TIGHTENING: f64 = 0.5;
+
+pub const CUCKOO_CAPACITY: u64 = 1024;
+pub const CUCKOO_BUCKET_SIZE: u8 = 2;
+pub const CUCKOO_MAX_ITERATIONS: u16 = 20;
+pub const CUCKOO_EXPANSION: u32 = 1;
+
+///Наибольший коэффициент роста фильтра, как в redis
+pub const MAX_EXPANSION: u32 = 32768;
+///Наибольший размер одного слоя или таблицы в байтах, как наибольшая строка в redis.
+///Память под слой выделяется до проверки `maxmemory`, поэтому без предела одна команда
+///могла бы запросить больше, чем есть у процесса
+pub const MAX_FILTER_SIZE: u64 = 512 * 1024 * 1024;
+
+const BLOOM_MAGIC: &[u8; 4] = b"BF01";
+const CUCKOO_MAGIC: &[u8; 4] = b"CF01";
+
+fn hash(item: &[u8]) -> (u64, u64) {
+    let h1 = murmur64a(item, HASH_SEED);
+    (h1, murmur64a(item, h1))
+}
+
+///Чтение полей закодированного фильтра, `None` - данные обрезаны
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Option<&[u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+///Слой фильтра Блума на `capacity` элементов с двойным хэшированием
+#[derive(Debug, Clone, PartialEq)]
+struct Layer {
+    bits: Vec<u8>,
+    size: u64,
+    hashes: u32,
+    capacity: u64,
+    count: u64,
+}
+
+fn filter_too_large() -> Error {
+    Error::Storage("filter is too large".to_string())
+}
+
+impl Layer {
+    ///Число бит слоя, `None` - слой больше `MAX_FILTER_SIZE`
+    fn bits(capacity: u64, error_rate: f64) -> Option<u64> {
+        let ln2 = std::f64::consts::LN_2;
+        let size = (-(capacity as f64) * error_rate.ln() / (ln2 * ln2)).ceil().max(8.0);
+        (size <= (MAX_FILTER_SIZE * 8) as f64).then_some(size as u64)
+    }
+
+    fn new(capacity: u64, error_rate: f64) -> Result<Self, CashError> {
+        let size = Layer::bits(capacity, error_rate).ok_or_else(filter_too_large)?;
+        let hashes = (size as f64 / capacity as f64 * std::f64::consts::LN_2).ceil().max(1.0) as u32;
+        Ok(Self { bits: vec![0; size.div_ceil(8) as usize], size, hashes, capacity, count: 0 })
+    }
+
+    fn positions(&self, (h1, h2): (u64, u64)) -> impl Iterator<Item = u64> + '_ {
+        (0..self.hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.size)
+    }
+
+    fn contains(&self, hash: (u64, u64)) -> bool {
+        self.positions(hash).all(|bit| self.bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+    }
+
+    fn insert(&mut self, hash: (u64, u64)) {
+        let positions: Vec<u64> = self.positions(hash).collect();
+        for bit in positions {
+            self.bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+        self.count += 1;
+    }
+}
+
+///Масштабируемый фильтр Блума: когда слой заполнен, добавляется слой
+///в `expansion` раз больше. `expansion = 0` - фильтр не растет и отказывает в добавлении
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bloom {
+    error_rate: f64,
+    expansion: u32,
+    layers: Vec<Layer>,
+}
+
+impl Bloom {
+    pub fn new(error_rate: f64, capacity: u64, expansion: u32) -> Result<Self, CashError> {
+        Ok(Self { error_rate, expansion, layers: vec![Layer::new(capacity, error_rate)?] })
+    }
+
+    ///Размер первого слоя в байтах, `None` - больше `MAX_FILTER_SIZE`
+    pub fn estimate_size(error_rate: f64, capacity: u64) -> Option<usize> {
+        Some(Layer::bits(capacity, error_rate)?.div_ceil(8) as usize)
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        let hash = hash(item);
+        self.layers.iter().any(|layer| layer.contains(hash))
+    }
+
+    ///`false` - элемент, возможно, уже был добавлен
+    pub fn add(&mut self, item: &[u8]) -> Result<bool, CashError> {
+        let hash = hash(item);
+        if self.layers.iter().any(|layer| layer.contains(hash)) {
+            return Ok(false);
+        }
+
+        let last = self.layers.last().expect("bloom filter has at least one layer");
+        if last.count >= last.capacity {
+            if self.expansion == 0 {
+                return Err(Error::Storage("non scaling filter is full".to_string()));
+            }
+            let capacity = last.capacity.saturating_mul(self.expansion as u64);
+            let error_rate = self.error_rate * BLOOM_TIGHTENING.powi(self.layers.len() as i32);
+            self.layers.push(Layer::new(capacity, error_rate)?);
+        }
+
+        self.layers.last_mut().expect("bloom filter has at least one layer").insert(hash);
+        Ok(true)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.layers.iter().map(|l| l.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.layers.iter().map(|l| l.bits.len()).sum()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = BLOOM_MAGIC.to_vec();
+        out.extend(self.error_rate.to_le_bytes());
+        out.extend(self.expansion.to_le_bytes());
+        out.extend((self.layers.len() as u32).to_le_bytes());
+        for layer in &self.layers {
+            out.extend(layer.size.to_le_bytes());
+            out.extend(layer.hashes.to_le_bytes());
+            out.extend(layer.capacity.to_le_bytes());
+            out.extend(layer.count.to_le_bytes());
+            out.extend(&layer.bits);
+        }
+        out
+    }
+
+    ///Данные приходят и от клиентов через `RESTORE`, поэтому проверяется всё,
+    ///на что опираются операции: размеры слоев, число хэшей и счетчики
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes.strip_prefix(BLOOM_MAGIC)?);
+        let error_rate = f64::from_bits(reader.u64()?);
+        let expansion = reader.u32()?;
+        if !(error_rate > 0.0 && error_rate < 1.0) || expansion > MAX_EXPANSION {
+            return None;
+        }
+
+        let mut layers = vec![];
+        for _ in 0..reader.u32()? {
+            let size = reader.u64()?;
+            let hashes = reader.u32()?;
+            let capacity = reader.u64()?;
+            let count = reader.u64()?;
+            if size == 0 || hashes == 0 || capacity == 0 || count > capacity {
+                return None;
+            }
+            let bits = reader.take(usize::try_from(size.div_ceil(8)).ok()?)?.to_vec();
+            layers.push(Layer { bits, size, hashes, capacity, count });
+        }
+
+        let total = layers.iter().try_fold(0u64, |total, layer| total.checked_add(layer.capacity));
+        let valid = !layers.is_empty() && total.is_some() && reader.0.is_empty();
+        valid.then_some(Self { error_rate, expansion, layers })
+    }
+}
+
+///Таблица фильтра с кукушкой: `buckets` корзин по `bucket_size` отпечатков, 0 - пустое место.
+///Число корзин - степень двойки, поэтому вторая корзина считается через xor
+#[derive(Debug, Clone, PartialEq)]
+struct Table {
+    slots: Vec<u8>,
+    buckets: u64,
+    count: u64,
+}
+
+impl Table {
+    ///Число корзин под `capacity` отпечатков, `None` - таблица больше `MAX_FILTER_SIZE`
+    fn buckets(capacity: u64, bucket_size: u8) -> Option<u64> {
+        let buckets = capacity.div_ceil(bucket_size as u64).max(1).checked_next_power_of_two()?;
+        (buckets.checked_mul(bucket_size as u64)? <= MAX_FILTER_SIZE).then_some(buckets)
+    }
+
+    fn new(capacity: u64, bucket_size: u8) -> Result<Self, CashError> {
+        let buckets = Table::buckets(capacity, bucket_size).ok_or_else(filter_too_large)?;
+        Ok(Self { slots: vec![0; (buckets * bucket_size as u64) as usize], buckets, count: 0 })
+    }
+
+    fn indexes(&self, h: u64, fingerprint: u8) -> (usize, usize) {
+        let i1 = h & (self.buckets - 1);
+        (i1 as usize, self.alt(i1 as usize, fingerprint))
+    }
+
+    fn alt(&self, index: usize, fingerprint: u8) -> usize {
+        index ^ (murmur64a(&[fingerprint], HASH_SEED) & (self.buckets - 1)) as usize
+    }
+
+    fn bucket(&mut self, index: usize, bucket_size: u8) -> &mut [u8] {
+        let size = bucket_size as usize;
+        &mut self.slots[index * size..(index + 1) * size]
+    }
+
+    fn contains(&self, index: usize, bucket_size: u8, fingerprint: u8) -> bool {
+        let size = bucket_size as usize;
+        self.slots[index * size..(index + 1) * size].contains(&fingerprint)
+    }
+
+    fn place(&mut self, index: usize, bucket_size: u8, fingerprint: u8) -> bool {
+        match self.bucket(index, bucket_size).iter_mut().find(|slot| **slot == 0) {
+            Some(slot) => {
+                *slot = fingerprint;
+                self.count += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///Вставка с вытеснением отпечатков в их другие корзины.
+    ///Если место не нашлось за `max_iterations` шагов, все перестановки откатываются
+    fn insert(&mut self, h: u64, fingerprint: u8, bucket_size: u8, max_iterations: u16) -> bool {
+        let (i1, i2) = self.indexes(h, fingerprint);
+        if self.place(i1, bucket_size, fingerprint) || self.place(i2, bucket_size, fingerprint) {
+            return true;
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut index = if rng.gen() { i1 } else { i2 };
+        let mut fingerprint = fingerprint;
+        let mut swaps = vec![];
+
+        for _ in 0..max_iterations {
+            let slot = rng.gen_range(0..bucket_size as usize);
+            let bucket = self.bucket(index, bucket_size);
+            swaps.push((index, slot, bucket[slot]));
+            std::mem::swap(&mut fingerprint, &mut bucket[slot]);
+
+            index = self.alt(index, fingerprint);
+            if self.place(index, bucket_size, fingerprint) {
+                return true;
+            }
+        }
+
+        for (index, slot, old) in swaps.into_iter().rev() {
+            self.bucket(index, bucket_size)[slot] = old;
+        }
+        false
+    }
+
+    fn remove(&mut self, index: usize, bucket_size: u8, fingerprint: u8) -> bool {
+        match self.bucket(index, bucket_size).iter_mut().find(|slot| **slot == fingerprint) {
+            Some(slot) => {
+                *slot = 0;
+                self.count -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+///Фильтр с кукушкой: как фильтр Блума, но поддерживает удаление.
+///Когда таблица заполнена, добавляется таблица в `expansion` раз больше
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cuckoo {
+    capacity: u64,
+    bucket_size: u8,
+    max_iterations: u16,
+    expansion: u32,
+    tables: Vec<Table>,
+}
+
+fn fingerprint(h: u64) -> u8 {
+    ((h >> 32) % 255 + 1) as u8
+}
+
+impl Cuckoo {
+    pub fn new(capacity: u64, bucket_size: u8, max_iterations: u16, expansion: u32) -> Result<Self, CashError> {
+        let table = Table::new(capacity, bucket_size)?;
+        Ok(Self { capacity, bucket_size, max_iterations, expansion, tables: vec![table] })
+    }
+
+    ///Размер первой таблицы в байтах, `None` - больше `MAX_FILTER_SIZE`
+    pub fn estimate_size(capacity: u64, bucket_size: u8) -> Option<usize> {
+        Some((Table::buckets(capacity, bucket_size)? * bucket_size as u64) as usize)
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        let (h, _) = hash(item);
+        let fp = fingerprint(h);
+
+        self.tables.iter().any(|table| {
+            let (i1, i2) = table.indexes(h, fp);
+            table.contains(i1, self.bucket_size, fp) || table.contains(i2, self.bucket_size, fp)
+        })
+    }
+
+    ///Добавляет элемент, даже если он уже есть: повторное добавление требует повторного удаления
+    pub fn add(&mut self, item: &[u8]) -> Result<(), CashError> {
+        let (h, _) = hash(item);
+        let fp = fingerprint(h);
+
+        for table in &mut self.tables {
+            if table.insert(h, fp, self.bucket_size, self.max_iterations) {
+                return Ok(());
+            }
+        }
+
+        if self.expansion == 0 {
+            return Err(Error::Storage("Filter is full".to_string()));
+        }
+
+        //таблица, которая не помещается в `MAX_FILTER_SIZE`, - тоже заполненный фильтр
+        let capacity = (self.expansion as u64)
+            .checked_pow(self.tables.len() as u32)
+            .and_then(|factor| self.capacity.checked_mul(factor))
+            .ok_or_else(|| Error::Storage("Filter is full".to_string()))?;
+        let mut table = Table::new(capacity, self.bucket_size).map_err(|_| Error::Storage("Filter is full".to_string()))?;
+        table.insert(h, fp, self.bucket_size, self.max_iterations);
+        self.tables.push(table);
+        Ok(())
+    }
+
+    ///Удаляет одно вхождение элемента. `false` - элемента не было
+    pub fn delete(&mut self, item: &[u8]) -> bool {
+        let (h, _) = hash(item);
+        let fp = fingerprint(h);
+        let bucket_size = self.bucket_size;
+
+        self.tables.iter_mut().rev().any(|table| {
+            let (i1, i2) = table.indexes(h, fp);
+            table.remove(i1, bucket_size, fp) || table.remove(i2, bucket_size, fp)
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.tables.iter().map(|t| t.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.tables.iter().map(|t| t.slots.len()).sum()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = CUCKOO_MAGIC.to_vec();
+        out.extend(self.capacity.to_le_bytes());
+        out.push(self.bucket_size);
+        out.extend(self.max_iterations.to_le_bytes());
+        out.extend(self.expansion.to_le_bytes());
+        out.extend((self.tables.len() as u32).to_le_bytes());
+        for table in &self.tables {
+            out.extend(table.buckets.to_le_bytes());
+            out.extend(table.count.to_le_bytes());
+            out.extend(&table.slots);
+        }
+        out
+    }
+
+    ///Как и у `Bloom::decode`: число корзин - степень двойки, длина таблицы
+    ///и счетчик отпечатков совпадают с данными
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes.strip_prefix(CUCKOO_MAGIC)?);
+        let capacity = reader.u64()?;
+        let bucket_size = *reader.take(1)?.first()?;
+        let max_iterations = u16::from_le_bytes(reader.take(2)?.try_into().ok()?);
+        let expansion = reader.u32()?;
+        if capacity == 0 || bucket_size == 0 || expansion > MAX_EXPANSION {
+            return None;
+        }
+
+        let mut tables = vec![];
+        for _ in 0..reader.u32()? {
+            let buckets = reader.u64()?;
+            let count = reader.u64()?;
+            if !buckets.is_power_of_two() {
+                return None;
+            }
+            let len = usize::try_from(buckets.checked_mul(bucket_size as u64)?).ok()?;
+            let slots = reader.take(len)?.to_vec();
+            if slots.iter().filter(|slot| **slot != 0).count() as u64 != count {
+                return None;
+            }
+            tables.push(Table { slots, buckets, count });
+        }
+
+        let valid = !tables.is_empty() && reader.0.is_empty();
+        valid.then_some(Self { capacity, bucket_size, max_iterations, expansion, tables })
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use super::*;
+
+    #[test]
+    fn bloom_scales_and_keeps_error_rate() {
+        let mut bloom = Bloom::new(0.01, 100, BLOOM_EXPANSION).unwrap();
+        for i in 0..1000 {
+            bloom.add(format!("key:{}", i).as_bytes()).unwrap();
+        }
+
+        assert!(bloom.layers.len() > 1);
+        assert!((0..1000).all(|i| bloom.contains(format!("key:{}", i).as_bytes())));
+
+        let false_positives = (0..10_000).filter(|i| bloom.contains(format!("other:{}", i).as_bytes())).count();
+        assert!(false_positives < 200, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn bloom_non_scaling_is_full() {
+        let mut bloom = Bloom::new(0.01, 10, 0).unwrap();
+        let added = (0..100).map(|i| bloom.add(format!("{}", i).as_bytes())).filter(|r| r.is_err()).count();
+
+        assert!(added > 0);
+        assert_eq!(1, bloom.layers.len());
+    }
+
+    #[test]
+    fn cuckoo_delete() {
+        let mut cuckoo = Cuckoo::new(64, CUCKOO_BUCKET_SIZE, CUCKOO_MAX_ITERATIONS, CUCKOO_EXPANSION).unwrap();
+        for i in 0..500 {
+            cuckoo.add(format!("key:{}", i).as_bytes()).unwrap();
+        }
+
+        assert_eq!(500, cuckoo.len());
+        assert!((0..500).all(|i| cuckoo.contains(format!("key:{}", i).as_bytes())));
+        assert!(cuckoo.delete(b"key:7"));
+        assert!(!cuckoo.delete(b"missing"));
+        assert_eq!(499, cuckoo.len());
+    }
+
+    #[test]
+    fn growth_stops_at_max_size() {
+        let mut cuckoo = Cuckoo::new(1, 1, 1, MAX_EXPANSION).unwrap();
+        let full = (0..100_000u32).find_map(|i| cuckoo.add(&i.to_le_bytes()).err());
+        assert_eq!(Some(Error::Storage("Filter is full".to_string())), full);
+        assert_eq!(2, cuckoo.tables.len());
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let mut bloom = Bloom::new(0.001, 50, 2).unwrap();
+        let mut cuckoo = Cuckoo::new(50, 4, 10, 2).unwrap();
+        for i in 0..120 {
+            bloom.add(&[i]).unwrap();
+            cuckoo.add(&[i]).unwrap();
+        }
+
+        assert_eq!(Some(bloom.clone()), Bloom::decode(&bloom.encode()));
+        assert_eq!(Some(cuckoo.clone()), Cuckoo::decode(&cuckoo.encode()));
+        assert_eq!(None, Bloom::decode(&bloom.encode()[..20]));
+    }
+
+    fn bloom_bytes(size: u64, hashes: u32, capacity: u64, count: u64, bits: usize) -> Vec<u8> {
+        let mut out = BLOOM_MAGIC.to_vec();
+        out.extend(0.01f64.to_le_bytes());
+        out.extend(2u32.to_le_bytes());
+        out.extend(1u32.to_le_bytes());
+        out.extend(size.to_le_bytes());
+        out.extend(hashes.to_le_bytes());
+        out.extend(capacity.to_le_bytes());
+        out.extend(count.to_le_bytes());
+        out.extend(vec![0; bits]);
+        out
+    }
+
+    fn cuckoo_bytes(bucket_size: u8, buckets: u64, count: u64, slots: &[u8]) -> Vec<u8> {
+        let mut out = CUCKOO_MAGIC.to_vec();
+        out.extend(64u64.to_le_bytes());
+        out.push(bucket_size);
+        out.extend(20u16.to_le_bytes());
+        out.extend(1u32.to_le_bytes());
+        out.extend(1u32.to_le_bytes());
+        out.extend(buckets.to_le_bytes());
+        out.extend(count.to_le_bytes());
+        out.extend(slots);
+        out
+    }
+
+    #[test]
+    fn malformed_payloads_are_rejected() {
+        assert!(Bloom::decode(&bloom_bytes(64, 3, 10, 0, 8)).is_some());
+        assert_eq!(None, Bloom::decode(&bloom_bytes(0, 3, 10, 0, 0)));
+        assert_eq!(None, Bloom::decode(&bloom_bytes(64, 0, 10, 0, 8)));
+        assert_eq!(None, Bloom::decode(&bloom_bytes(64, 3, 0, 0, 8)));
+        assert_eq!(None, Bloom::decode(&bloom_bytes(64, 3, 10, 11, 8)));
+        assert_eq!(None, Bloom::decode(&bloom_bytes(64, 3, 10, 0, 7)));
+        assert_eq!(None, Bloom::decode(&bloom_bytes(u64::MAX, 3, 10, 0, 8)));
+
+        assert!(Cuckoo::decode(&cuckoo_bytes(2, 4, 1, &[0, 0, 7, 0, 0, 0, 0, 0])).is_some());
+        assert_eq!(None, Cuckoo::decode(&cuckoo_bytes(2, 0, 0, &[])));
+        assert_eq!(None, Cuckoo::decode(&cuckoo_bytes(2, 3, 0, &[0; 6])));
+        assert_eq!(None, Cuckoo::decode(&cuckoo_bytes(2, 4, 0, &[0, 0, 7, 0, 0, 0, 0, 0])));
+        assert_eq!(None, Cuckoo::decode(&cuckoo_bytes(2, 4, 0, &[0; 7])));
+        assert_eq!(None, Cuckoo::decode(&cuckoo_bytes(0, 4, 0, &[])));
+        assert_eq!(None, Cuckoo::decode(&cuckoo_bytes(255, 1 << 63, 0, &[])));
+    }
+}
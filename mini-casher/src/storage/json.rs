@@ -0,0 +1,104 @@
+use serde_json::Value as Json;
+use crate::core::command::json::{Path, Segment};
+
+///Конкретное место в документе, в которое разворачивается JSONPath
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Step {
+    Key(String),
+    Index(usize),
+}
+
+///Все места документа, которые совпадают с путем, в порядке обхода
+pub fn resolve(root: &Json, segments: &[Segment]) -> Vec<Vec<Step>> {
+    let mut out = vec![];
+    walk(root, segments, &mut vec![], &mut out);
+    out
+}
+
+fn walk(node: &Json, segments: &[Segment], prefix: &mut Vec<Step>, out: &mut Vec<Vec<Step>>) {
+    let Some((segment, rest)) = segments.split_first() else {
+        out.push(prefix.clone());
+        return;
+    };
+
+    match (segment, node) {
+        (Segment::Key(key), Json::Object(map)) => {
+            if let Some(child) = map.get(key) {
+                descend(Step::Key(key.clone()), child, rest, prefix, out);
+            }
+        }
+        (Segment::Index(index), Json::Array(items)) => {
+            let len = items.len() as i64;
+            let index = if *index < 0 { len + index } else { *index };
+            if (0..len).contains(&index) {
+                descend(Step::Index(index as usize), &items[index as usize], rest, prefix, out);
+            }
+        }
+        (Segment::Wildcard, Json::Object(map)) => {
+            for (key, child) in map {
+                descend(Step::Key(key.clone()), child, rest, prefix, out);
+            }
+        }
+        (Segment::Wildcard, Json::Array(items)) => {
+            for (i, child) in items.iter().enumerate() {
+                descend(Step::Index(i), child, rest, prefix, out);
+            }
+        }
+        (Segment::Descend, _) => {
+            walk(node, rest, prefix, out);
+            match node {
+                Json::Object(map) => {
+                    for (key, child) in map {
+                        descend(Step::Key(key.clone()), child, segments, prefix, out);
+                    }
+                }
+                Json::Array(items) => {
+                    for (i, child) in items.iter().enumerate() {
+                        descend(Step::Index(i), child, segments, prefix, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+}
+
+fn descend(step: Step, child: &Json, segments: &[Segment], prefix: &mut Vec<Step>, out: &mut Vec<Vec<Step>>) {
+    prefix.push(step);
+    walk(child, segments, prefix, out);
+    prefix.pop();
+}
+
+pub fn node<'a>(root: &'a Json, steps: &[Step]) -> Option<&'a Json> {
+    steps.iter().try_fold(root, |node, step| match step {
+        Step::Key(key) => node.get(key),
+        Step::Index(i) => node.get(i),
+    })
+}
+
+pub fn node_mut<'a>(root: &'a mut Json, steps: &[Step]) -> Option<&'a mut Json> {
+    steps.iter().try_fold(root, |node, step| match step {
+        Step::Key(key) => node.get_mut(key),
+        Step::Index(i) => node.get_mut(i),
+    })
+}
+
+///Значения, найденные по пути, в порядке обхода
+pub fn select<'a>(root: &'a Json, path: &Path) -> Vec<&'a Json> {
+    resolve(root, path.segments()).iter().filter_map(|steps| node(root, steps)).collect()
+}
+
+#[cfg(test)]
+mod json_tests {
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn select_recursive_descent() {
+        let root = json!({"status": "new", "sub": [{"status": "new"}, {"status": "done"}]});
+        let path = Path::parse("$..status").unwrap();
+
+        assert_eq!(vec![&json!("new"), &json!("new"), &json!("done")], select(&root, &path));
+    }
+}
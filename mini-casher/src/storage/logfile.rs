@@ -0,0 +1,674 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use crate::core::command::search::{IndexDef, Query};
+use crate::core::error::{CashError, Error};
+use crate::storage::engine::{EngineOptions, StorageEngine};
+use crate::storage::eviction::{KeySet, Policy};
+use crate::storage::search::Index;
+use crate::storage::snapshot::{decode_index, decode_value, encode_index, instant, kind, kind_name, unix_ms};
+use crate::storage::Value;
+
+///Заголовок файла части
+const MAGIC: &[u8] = b"CASHLOG";
+
+///Версия формата. Файл другой версии не открывается: в версии 1 части выбирались
+///по `DefaultHasher`, с версии 2 - по FNV-1a, и ключи старых файлов лежат не в своих частях
+const VERSION: u16 = 2;
+
+///Длина заголовка: `MAGIC`, версия (u16), номер части и число частей (u32)
+const HEADER_LEN: u64 = MAGIC.len() as u64 + 2 + 4 + 4;
+
+///Длина контрольной суммы записи: первые байты SHA-256
+const CHECKSUM_LEN: usize = 4;
+
+///Служебные записи. Записи значений используют коды типов снимка
+const RECORD_DELETE: u8 = 0xFF;
+const RECORD_EXPIRE: u8 = 0xFE;
+const RECORD_INDEX: u8 = 0xFD;
+const RECORD_DROP_INDEX: u8 = 0xFC;
+
+///Без срока жизни
+const NO_EXPIRY: u64 = u64::MAX;
+
+///Сжатие начинается, когда устаревших байт больше, чем живых, и не меньше этого
+const COMPACT_MIN_DEAD: u64 = 4 * 1024 * 1024;
+
+///Приблизительные накладные расходы на ключ в памяти
+const SLOT_OVERHEAD: usize = 64;
+
+///Где лежит значение ключа
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    kind: u8,
+    ///Начало данных значения в файле
+    offset: u64,
+    len: u32,
+    ///Размер всей записи, который станет мусором после перезаписи ключа
+    size: u64,
+    expires_at: Option<Instant>,
+}
+
+impl Slot {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+///Запись журнала без длины и контрольной суммы
+struct Record<'a> {
+    kind: u8,
+    expires_at: u64,
+    key: &'a [u8],
+    data: &'a [u8],
+}
+
+impl Record<'_> {
+    ///Формат: длина тела (u32 LE), тело, первые 4 байта SHA-256 тела.
+    ///Тело: тип, срок (u64 LE, unix-время в миллисекундах, `u64::MAX` - без срока), ключ с длиной (u32 LE), данные
+    fn encode(&self) -> Vec<u8> {
+        let body_len = 1 + 8 + 4 + self.key.len() + self.data.len();
+        let mut buf = Vec::with_capacity(4 + body_len + CHECKSUM_LEN);
+        buf.extend_from_slice(&(body_len as u32).to_le_bytes());
+        buf.push(self.kind);
+        buf.extend_from_slice(&self.expires_at.to_le_bytes());
+        buf.extend_from_slice(&(self.key.len() as u32).to_le_bytes());
+        buf.extend_from_slice(self.key);
+        buf.extend_from_slice(self.data);
+        let checksum = Sha256::digest(&buf[4..]);
+        buf.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        buf
+    }
+
+    ///Смещение данных от начала записи
+    fn data_offset(&self) -> u64 {
+        4 + 1 + 8 + 4 + self.key.len() as u64
+    }
+
+    fn decode(body: &[u8]) -> Option<Record<'_>> {
+        let kind = *body.first()?;
+        let expires_at = u64::from_le_bytes(body.get(1..9)?.try_into().ok()?);
+        let key_len = u32::from_le_bytes(body.get(9..13)?.try_into().ok()?) as usize;
+        let key = body.get(13..13 + key_len)?;
+        Some(Record { kind, expires_at, key, data: &body[13 + key_len..] })
+    }
+}
+
+fn io_error(path: &Path, err: io::Error) -> Error {
+    Error::Storage(format!("log '{}': {}", path.display(), err))
+}
+
+fn header(shard: usize, shards: usize) -> Vec<u8> {
+    let mut buf = Vec::from(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&(shard as u32).to_le_bytes());
+    buf.extend_from_slice(&(shards as u32).to_le_bytes());
+    buf
+}
+
+///Часть хранилища в журнале на диске, как в Bitcask: каждая запись дописывается в конец файла,
+///а в памяти остаются только ключи с местом значения в файле и сроками жизни.
+///Значения читаются с диска при каждом обращении, поэтому `maxmemory` и вытеснение к ним не применяются.
+///Когда устаревших записей становится больше, чем живых, файл сжимается: живые ключи
+///переписываются в новый файл, который заменяет старый. Запись идет без `fsync`, как `appendfsync no`
+#[derive(Debug)]
+pub struct LogStore {
+    path: PathBuf,
+    file: File,
+    header: Vec<u8>,
+    end: u64,
+    dead: u64,
+    compact_min: u64,
+    keydir: HashMap<Bytes, Slot>,
+    keys: KeySet,
+    indexes: BTreeMap<String, Index>,
+    used_memory: usize,
+    dirty: Arc<AtomicU64>,
+}
+
+impl LogStore {
+    ///Путь к файлу части в каталоге `dir`
+    pub fn path(dir: &Path, shard: usize) -> PathBuf {
+        dir.join(format!("shard-{}.log", shard))
+    }
+
+    fn changed(&self) {
+        self.dirty.fetch_add(1, Ordering::Relaxed);
+    }
+
+    ///Читает файл: строит карту ключей и индексы. Недописанная последняя запись
+    ///(сбой во время записи) отбрасывается, и файл обрезается до неё
+    fn load(&mut self) -> Result<(), CashError> {
+        let path = self.path.clone();
+        let len = self.file.metadata().map_err(|e| io_error(&path, e))?.len();
+        self.file.seek(SeekFrom::Start(0)).map_err(|e| io_error(&path, e))?;
+        let mut reader = BufReader::new(self.file.try_clone().map_err(|e| io_error(&path, e))?);
+
+        let mut found = vec![0; HEADER_LEN as usize];
+        reader.read_exact(&mut found).map_err(|_| Error::Storage(format!("'{}' is not a log file", path.display())))?;
+        if found[..MAGIC.len()] != *MAGIC {
+            return Err(Error::Storage(format!("'{}' is not a log file", path.display())));
+        }
+        let version = u16::from_le_bytes([found[MAGIC.len()], found[MAGIC.len() + 1]]);
+        if version != VERSION {
+            return Err(Error::Storage(format!("log version {} is not supported, expected {}", version, VERSION)));
+        }
+        if found[MAGIC.len() + 2..] != self.header[MAGIC.len() + 2..] {
+            let shards = u32::from_le_bytes(found[HEADER_LEN as usize - 4..].try_into().unwrap_or_default());
+            return Err(Error::Storage(format!(
+                "'{}' was written with {} shards, start the server with the same 'shards'",
+                path.display(),
+                shards
+            )));
+        }
+
+        let mut defs = BTreeMap::new();
+        let mut at = HEADER_LEN;
+        while at < len {
+            let mut prefix = [0; 4];
+            let body_len = match reader.read_exact(&mut prefix) {
+                Ok(()) => u32::from_le_bytes(prefix) as u64,
+                Err(_) => break,
+            };
+            if at + 4 + body_len + CHECKSUM_LEN as u64 > len {
+                break;
+            }
+
+            let mut body = vec![0; body_len as usize + CHECKSUM_LEN];
+            reader.read_exact(&mut body).map_err(|e| io_error(&path, e))?;
+            let (body, checksum) = body.split_at(body_len as usize);
+            let record = match Record::decode(body) {
+                Some(record) if Sha256::digest(body)[..CHECKSUM_LEN] == *checksum => record,
+                _ => return Err(Error::Storage(format!("log '{}' is corrupted at byte {}", path.display(), at))),
+            };
+
+            let size = 4 + body_len + CHECKSUM_LEN as u64;
+            match record.kind {
+                RECORD_INDEX => match decode_index(record.data) {
+                    Some(def) => {
+                        defs.insert(def.name.clone(), def);
+                    }
+                    None => return Err(Error::Storage(format!("log '{}' is corrupted at byte {}", path.display(), at))),
+                },
+                RECORD_DROP_INDEX => {
+                    defs.remove(&*String::from_utf8_lossy(record.key));
+                }
+                RECORD_DELETE => {
+                    self.forget(record.key);
+                    self.dead += size;
+                }
+                RECORD_EXPIRE => {
+                    let expires_at = (record.expires_at != NO_EXPIRY).then(|| instant(record.expires_at));
+                    match (self.keydir.get_mut(record.key), expires_at) {
+                        (Some(_), Some(None)) => {
+                            self.forget(record.key);
+                        }
+                        (Some(slot), expires_at) => slot.expires_at = expires_at.flatten(),
+                        (None, _) => {}
+                    }
+                    self.dead += size;
+                }
+                kind => {
+                    let key = Bytes::copy_from_slice(record.key);
+                    let slot = Slot { kind, offset: at + record.data_offset(), len: record.data.len() as u32, size, expires_at: None };
+                    match record.expires_at {
+                        NO_EXPIRY => self.remember(key, slot),
+                        unix_ms => match instant(unix_ms) {
+                            Some(expires_at) => self.remember(key, Slot { expires_at: Some(expires_at), ..slot }),
+                            None => {
+                                self.forget(&key);
+                                self.dead += size;
+                            }
+                        },
+                    }
+                }
+            }
+            at += size;
+        }
+        drop(reader);
+
+        if at < len {
+            log::warn!("log '{}' ends with an incomplete record, truncating {} bytes", path.display(), len - at);
+            self.file.set_len(at).map_err(|e| io_error(&path, e))?;
+        }
+        self.end = at;
+
+        for def in defs.into_values() {
+            self.build_index(def)?;
+        }
+        Ok(())
+    }
+
+    ///Запоминает место нового значения ключа, прежняя запись становится мусором
+    fn remember(&mut self, key: Bytes, slot: Slot) {
+        self.forget(&key);
+        self.used_memory += key.len() + SLOT_OVERHEAD;
+        self.keys.insert(&key);
+        self.keydir.insert(key, slot);
+    }
+
+    ///Убирает ключ из памяти без записи в журнал
+    fn forget(&mut self, key: &[u8]) -> Option<Slot> {
+        let slot = self.keydir.remove(key)?;
+        self.dead += slot.size;
+        self.used_memory -= key.len() + SLOT_OVERHEAD;
+        self.keys.remove(key);
+        for index in self.indexes.values_mut() {
+            index.update(key, None);
+        }
+        Some(slot)
+    }
+
+    ///Дописывает запись и возвращает её начало и размер
+    fn append(&mut self, record: &Record) -> Result<(u64, u64), CashError> {
+        let buf = record.encode();
+        let at = self.end;
+        self.file.seek(SeekFrom::Start(at)).map_err(|e| io_error(&self.path, e))?;
+        self.file.write_all(&buf).map_err(|e| io_error(&self.path, e))?;
+        self.end += buf.len() as u64;
+        Ok((at, buf.len() as u64))
+    }
+
+    fn read(&mut self, slot: &Slot) -> Result<Vec<u8>, CashError> {
+        let mut data = vec![0; slot.len as usize];
+        self.file.seek(SeekFrom::Start(slot.offset)).map_err(|e| io_error(&self.path, e))?;
+        self.file.read_exact(&mut data).map_err(|e| io_error(&self.path, e))?;
+        Ok(data)
+    }
+
+    fn read_value(&mut self, slot: &Slot) -> Result<Value, CashError> {
+        let data = self.read(slot)?;
+        decode_value(slot.kind, &data)
+            .ok_or_else(|| Error::Storage(format!("log '{}' has a bad value at byte {}", self.path.display(), slot.offset)))
+    }
+
+    fn build_index(&mut self, def: IndexDef) -> Result<(), CashError> {
+        let mut index = Index::new(def);
+        let now = Instant::now();
+        let docs: Vec<(Bytes, Slot)> = self
+            .keydir
+            .iter()
+            .filter(|(_, slot)| kind_name(slot.kind) == Some("ReJSON-RL") && !slot.is_expired(now))
+            .map(|(key, slot)| (key.clone(), *slot))
+            .collect();
+
+        for (key, slot) in docs {
+            let value = self.read_value(&slot)?;
+            index.update(&key, Some(&value));
+        }
+        self.indexes.insert(index.name().to_string(), index);
+        Ok(())
+    }
+
+    ///Переписывает живые ключи и индексы в новый файл и заменяет им старый
+    fn compact(&mut self) -> Result<(), CashError> {
+        let tmp = self.path.with_extension("compact");
+        let now = Instant::now();
+        let mut out = BufWriter::new(File::create(&tmp).map_err(|e| io_error(&tmp, e))?);
+        let mut end = self.header.len() as u64;
+        out.write_all(&self.header).map_err(|e| io_error(&tmp, e))?;
+
+        let mut buf = vec![];
+        for index in self.indexes.values() {
+            buf.clear();
+            encode_index(&mut buf, index.def());
+            let record = Record { kind: RECORD_INDEX, expires_at: NO_EXPIRY, key: index.name().as_bytes(), data: &buf };
+            let encoded = record.encode();
+            out.write_all(&encoded).map_err(|e| io_error(&tmp, e))?;
+            end += encoded.len() as u64;
+        }
+
+        let live: Vec<(Bytes, Slot)> = self.keydir.iter().filter(|(_, s)| !s.is_expired(now)).map(|(k, s)| (k.clone(), *s)).collect();
+        let mut keydir = HashMap::with_capacity(live.len());
+        for (key, slot) in live {
+            let data = self.read(&slot)?;
+            let expires_at = slot.expires_at.map_or(NO_EXPIRY, |at| unix_ms(at, now));
+            let record = Record { kind: slot.kind, expires_at, key: &key, data: &data };
+            let (encoded, offset) = (record.encode(), record.data_offset());
+            out.write_all(&encoded).map_err(|e| io_error(&tmp, e))?;
+            keydir.insert(key, Slot { offset: end + offset, size: encoded.len() as u64, ..slot });
+            end += encoded.len() as u64;
+        }
+
+        let file = out.into_inner().map_err(|e| io_error(&tmp, e.into_error()))?;
+        file.sync_all().map_err(|e| io_error(&tmp, e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| io_error(&self.path, e))?;
+
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path).map_err(|e| io_error(&self.path, e))?;
+        log::info!("log '{}' compacted from {} to {} bytes", self.path.display(), self.end, end);
+        self.keys = KeySet::default();
+        for key in keydir.keys() {
+            self.keys.insert(key);
+        }
+        self.keydir = keydir;
+        self.end = end;
+        self.dead = 0;
+        Ok(())
+    }
+
+    fn maybe_compact(&mut self) {
+        if self.dead >= self.compact_min && self.dead * 2 > self.end {
+            if let Err(err) = self.compact() {
+                log::error!("{}", err);
+            }
+        }
+    }
+}
+
+impl StorageEngine for LogStore {
+    ///Открывает или создает файл части в `options.dir`. Число частей записано в файле:
+    ///ключи разложены по частям по хэшу, поэтому открыть файлы с другим `shards` нельзя
+    fn open(options: &EngineOptions, shard: usize) -> Result<Self, CashError> {
+        std::fs::create_dir_all(&options.dir).map_err(|e| io_error(&options.dir, e))?;
+        let path = LogStore::path(&options.dir, shard);
+        let header = header(shard, options.shards);
+
+        let exists = path.exists();
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path).map_err(|e| io_error(&path, e))?;
+        if !exists {
+            file.write_all(&header).map_err(|e| io_error(&path, e))?;
+        }
+
+        let mut store = LogStore {
+            path,
+            file,
+            header,
+            end: HEADER_LEN,
+            dead: 0,
+            compact_min: COMPACT_MIN_DEAD,
+            keydir: HashMap::new(),
+            keys: KeySet::default(),
+            indexes: BTreeMap::new(),
+            used_memory: 0,
+            dirty: options.dirty.clone(),
+        };
+        store.load()?;
+        Ok(store)
+    }
+
+    fn get_value(&mut self, key: &[u8]) -> Result<Option<Value>, CashError> {
+        let Some(slot) = self.keydir.get(key).copied() else { return Ok(None) };
+        if slot.is_expired(Instant::now()) {
+            self.forget(key);
+            return Ok(None);
+        }
+        self.read_value(&slot).map(Some)
+    }
+
+    fn set_value(&mut self, key: Bytes, value: Value) -> Result<(), CashError> {
+        let data = value.to_bytes();
+        let record = Record { kind: kind(&value), expires_at: NO_EXPIRY, key: &key, data: &data };
+        let offset = record.data_offset();
+        let (at, size) = self.append(&record)?;
+
+        self.remember(key.clone(), Slot { kind: kind(&value), offset: at + offset, len: data.len() as u32, size, expires_at: None });
+        for index in self.indexes.values_mut() {
+            index.update(&key, Some(&value));
+        }
+        self.changed();
+        self.maybe_compact();
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Option<Value> {
+        let slot = *self.keydir.get(key)?;
+        let value = match slot.is_expired(Instant::now()) {
+            true => None,
+            false => self.read_value(&slot).ok(),
+        };
+
+        match self.append(&Record { kind: RECORD_DELETE, expires_at: NO_EXPIRY, key, data: &[] }) {
+            Ok((_, size)) => self.dead += size,
+            Err(err) => log::error!("{}", err),
+        }
+        self.forget(key);
+        self.changed();
+        self.maybe_compact();
+        value
+    }
+
+    fn expire(&mut self, key: &[u8], at: Option<Instant>) -> bool {
+        if !self.keydir.contains_key(key) {
+            return false;
+        }
+
+        let expires_at = at.map_or(NO_EXPIRY, |at| unix_ms(at, Instant::now()));
+        match self.append(&Record { kind: RECORD_EXPIRE, expires_at, key, data: &[] }) {
+            Ok((_, size)) => self.dead += size,
+            Err(err) => log::error!("{}", err),
+        }
+        if let Some(slot) = self.keydir.get_mut(key) {
+            slot.expires_at = at;
+        }
+        self.changed();
+        true
+    }
+
+    fn deadline(&self, key: &[u8]) -> Option<Instant> {
+        self.keydir.get(key)?.expires_at
+    }
+
+    ///Без чтения значения с диска
+    fn exists(&mut self, key: &[u8]) -> bool {
+        match self.keydir.get(key).copied() {
+            Some(slot) if slot.is_expired(Instant::now()) => {
+                self.forget(key);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    fn type_name(&self, key: &[u8]) -> Option<&'static str> {
+        self.keydir.get(key).filter(|slot| !slot.is_expired(Instant::now())).and_then(|slot| kind_name(slot.kind))
+    }
+
+    fn scan(&self, cursor: usize, count: usize) -> (usize, Vec<Bytes>) {
+        let now = Instant::now();
+        let (next, keys) = self.keys.scan(cursor, count);
+        let keys = keys
+            .iter()
+            .filter(|key| self.keydir.get(*key).is_some_and(|slot| !slot.is_expired(now)))
+            .cloned()
+            .collect();
+        (next, keys)
+    }
+
+    fn len(&self) -> usize {
+        self.keydir.len()
+    }
+
+    ///Непрочитанное значение - ошибка, а не пропуск: снимок без ключа потерял бы его
+    fn entries(&mut self) -> Result<Vec<(Bytes, Value, Option<Instant>)>, CashError> {
+        let now = Instant::now();
+        let live: Vec<(Bytes, Slot)> = self.keydir.iter().filter(|(_, s)| !s.is_expired(now)).map(|(k, s)| (k.clone(), *s)).collect();
+
+        live.into_iter()
+            .map(|(key, slot)| Ok((key, self.read_value(&slot)?, slot.expires_at)))
+            .collect()
+    }
+
+    fn used_memory(&self) -> usize {
+        self.used_memory
+    }
+
+    ///Значения на диске, `maxmemory` не действует
+    fn reserve(&mut self, _incoming: &[(&[u8], usize)]) -> Result<(), CashError> {
+        Ok(())
+    }
+
+    ///Значения на диске, вытеснять нечего
+    fn configure(&mut self, _maxmemory: usize, _policy: Policy, _samples: usize) {}
+
+    fn dirty(&self) -> u64 {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    fn saved(&self, dirty: u64) {
+        let _ = self.dirty.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |d| Some(d.saturating_sub(dirty)));
+    }
+
+    fn create_index(&mut self, def: IndexDef) -> Result<(), CashError> {
+        if self.indexes.contains_key(&def.name) {
+            return Err(Error::Storage("Index already exists".to_string()));
+        }
+
+        let mut data = vec![];
+        encode_index(&mut data, &def);
+        self.append(&Record { kind: RECORD_INDEX, expires_at: NO_EXPIRY, key: def.name.as_bytes(), data: &data })?;
+        self.build_index(def)
+    }
+
+    fn drop_index(&mut self, name: &str) -> bool {
+        if self.indexes.remove(name).is_none() {
+            return false;
+        }
+        if let Err(err) = self.append(&Record { kind: RECORD_DROP_INDEX, expires_at: NO_EXPIRY, key: name.as_bytes(), data: &[] }) {
+            log::error!("{}", err);
+        }
+        true
+    }
+
+    fn index_defs(&self) -> Vec<IndexDef> {
+        self.indexes.values().map(|index| index.def().clone()).collect()
+    }
+
+    fn search(&self, name: &str, query: &Query) -> Result<Vec<Bytes>, CashError> {
+        let index = self.indexes.get(name).ok_or_else(|| Error::Storage(format!("{}: no such index", name)))?;
+        let now = Instant::now();
+
+        Ok(index
+            .search(query)?
+            .into_iter()
+            .filter(|key| self.keydir.get(*key).is_some_and(|slot| !slot.is_expired(now)))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod logfile_tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use crate::core::command::Command;
+    use crate::core::command::search::SearchCmd;
+    use super::*;
+
+    fn options(name: &str) -> EngineOptions {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        EngineOptions { dir, shards: 1, ..Default::default() }
+    }
+
+    #[test]
+    fn survives_reopen() {
+        let options = options("log-reopen");
+        let mut store = LogStore::open(&options, 0).unwrap();
+        let Ok(Command::Search(SearchCmd::Create(def))) =
+            Command::from_cmd("ft.create idx on json prefix todo: schema $.status tag".to_string())
+        else {
+            panic!()
+        };
+        store.create_index(def).unwrap();
+
+        store.set(Bytes::from("a"), Bytes::from("1")).unwrap();
+        store.set(Bytes::from("a"), Bytes::from("2")).unwrap();
+        store.set(Bytes::from("gone"), Bytes::from("x")).unwrap();
+        store.delete(b"gone");
+        store.set(Bytes::from("session"), Bytes::from("x")).unwrap();
+        store.expire(b"session", Some(Instant::now() + Duration::from_secs(60)));
+        store.set(Bytes::from("old"), Bytes::from("x")).unwrap();
+        store.expire(b"old", Some(Instant::now()));
+        store.set_value(Bytes::from("todo:1"), Value::Json(json!({"status": "new"}))).unwrap();
+        assert_eq!(Some("ReJSON-RL"), store.type_name(b"todo:1"));
+        drop(store);
+
+        let mut store = LogStore::open(&options, 0).unwrap();
+        assert_eq!(Ok(Some(Bytes::from("2"))), store.get(b"a"));
+        assert!(!store.exists(b"gone") && !store.exists(b"old"));
+        assert!(store.deadline(b"session").is_some());
+        assert_eq!(3, store.len());
+        let found = store.search("idx", &Query::parse("@status:{new}").unwrap()).unwrap();
+        assert_eq!(vec![Bytes::from("todo:1")], found);
+
+        assert!(LogStore::open(&EngineOptions { shards: 2, ..options.clone() }, 0).is_err());
+        std::fs::remove_dir_all(&options.dir).unwrap();
+    }
+
+    #[test]
+    fn torn_tail_is_truncated_and_corruption_is_reported() {
+        let options = options("log-torn");
+        let mut store = LogStore::open(&options, 0).unwrap();
+        store.set(Bytes::from("a"), Bytes::from("1")).unwrap();
+        let end = store.end;
+        drop(store);
+
+        let path = LogStore::path(&options.dir, 0);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&Record { kind: 0, expires_at: NO_EXPIRY, key: b"b", data: b"2" }.encode()[..10]).unwrap();
+        drop(file);
+
+        let mut store = LogStore::open(&options, 0).unwrap();
+        assert_eq!(Ok(Some(Bytes::from("1"))), store.get(b"a"));
+        assert_eq!(end, std::fs::metadata(&path).unwrap().len());
+        drop(store);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(LogStore::open(&options, 0).is_err());
+        std::fs::remove_dir_all(&options.dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_value_is_an_error() {
+        let options = options("log-unreadable");
+        let mut store = LogStore::open(&options, 0).unwrap();
+        store.set(Bytes::from("a"), Bytes::from("1")).unwrap();
+
+        let path = LogStore::path(&options.dir, 0);
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(HEADER_LEN).unwrap();
+        assert!(store.exists(b"a"));
+        assert!(store.get_value(b"a").is_err());
+        assert!(store.entries().is_err());
+        drop(store);
+
+        //файл версии 1 разложен по частям другим хэшем
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&1u16.to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+        assert!(LogStore::open(&options, 0).is_err());
+        std::fs::remove_dir_all(&options.dir).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_live_keys() {
+        let options = options("log-compact");
+        let mut store = LogStore::open(&options, 0).unwrap();
+        store.compact_min = 1;
+
+        for i in 0..100 {
+            store.set(Bytes::from("k"), Bytes::from(i.to_string())).unwrap();
+        }
+        store.set(Bytes::from("session"), Bytes::from("x")).unwrap();
+        store.expire(b"session", Some(Instant::now() + Duration::from_secs(60)));
+        store.set(Bytes::from("k"), Bytes::from("last")).unwrap();
+
+        let path = LogStore::path(&options.dir, 0);
+        assert!(std::fs::metadata(&path).unwrap().len() < 200);
+        drop(store);
+
+        let mut store = LogStore::open(&options, 0).unwrap();
+        assert_eq!(Ok(Some(Bytes::from("last"))), store.get(b"k"));
+        assert!(store.deadline(b"session").is_some());
+        std::fs::remove_dir_all(&options.dir).unwrap();
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use bytes::Bytes;
+use crate::core::command::search::{IndexDef, Query};
+use crate::core::error::{CashError, Error};
+use crate::storage::engine::{EngineOptions, StorageEngine};
+use crate::storage::eviction::{KeySet, Policy, Usage};
+use crate::storage::filter::{Bloom, Cuckoo};
+use crate::storage::search::Index;
+
+pub mod engine;
+pub mod eviction;
+pub mod export;
+pub mod filter;
+pub mod json;
+pub mod logfile;
+pub mod search;
+pub mod sharded;
+pub mod snapshot;
+
+///Приблизительные накладные расходы на запись: заголовки `HashMap`,
+///`Bytes` и сведений о доступе
+const ENTRY_OVERHEAD: usize = 64;
+
+///Значение ключа. Команды строк работают только со `String`,
+///для остальных типов возвращается `WRONGTYPE`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(Bytes),
+    Json(serde_json::Value),
+    Bloom(Bloom),
+    Cuckoo(Cuckoo),
+}
+
+impl Value {
+    ///Имя типа, как его показывает redis
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Json(_) => "ReJSON-RL",
+            Value::Bloom(_) => "MBbloom--",
+            Value::Cuckoo(_) => "MBbloomCF",
+        }
+    }
+
+    ///Значение в виде байт: JSON сериализуется в текст, фильтры - в двоичный вид
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            Value::String(bytes) => bytes.clone(),
+            Value::Json(json) => Bytes::from(json.to_string()),
+            Value::Bloom(bloom) => Bytes::from(bloom.encode()),
+            Value::Cuckoo(cuckoo) => Bytes::from(cuckoo.encode()),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Value::String(bytes) => bytes.len(),
+            Value::Json(json) => json.to_string().len(),
+            Value::Bloom(bloom) => bloom.size(),
+            Value::Cuckoo(cuckoo) => cuckoo.size(),
+        }
+    }
+}
+
+pub fn wrong_type() -> Error {
+    Error::WrongType("Operation against a key holding the wrong kind of value".to_string())
+}
+
+#[derive(Debug)]
+struct Entry {
+    value: Value,
+    size: usize,
+    usage: Usage,
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.usage.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+fn entry_size(key: &[u8], value: &Value) -> usize {
+    key.len() + value.size() + ENTRY_OVERHEAD
+}
+
+///Хранилище ключей с учетом занятой памяти.
+///При превышении `maxmemory` запись вытесняет ключи по политике
+///или возвращает `-OOM`, если политика `noeviction`.
+///`maxmemory = 0` снимает ограничение.
+///Вторичные индексы обновляются при каждой записи и удалении.
+///`dirty` считает изменения с последнего снимка для правил `save`, счетчик общий у всех частей `Sharded`.
+///`total` - память всех частей: `maxmemory` ограничивает её, а вытесняются ключи той части, куда идет запись
+#[derive(Debug)]
+pub struct Store {
+    entries: HashMap<Bytes, Entry>,
+    keys: KeySet,
+    volatile: KeySet,
+    used_memory: usize,
+    total: Arc<AtomicUsize>,
+    maxmemory: usize,
+    policy: Policy,
+    samples: usize,
+    indexes: BTreeMap<String, Index>,
+    dirty: Arc<AtomicU64>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Store::new(0, Policy::NoEviction, 5)
+    }
+}
+
+impl Store {
+    pub fn new(maxmemory: usize, policy: Policy, samples: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            keys: KeySet::default(),
+            volatile: KeySet::default(),
+            used_memory: 0,
+            total: Arc::default(),
+            maxmemory,
+            policy,
+            samples,
+            indexes: BTreeMap::new(),
+            dirty: Arc::default(),
+        }
+    }
+
+    fn changed(&self) {
+        self.dirty.fetch_add(1, Ordering::Relaxed);
+    }
+
+    ///Все ключи и значения, кроме истекших
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &Value)> {
+        let now = Instant::now();
+        self.entries
+            .iter()
+            .filter(move |(_, e)| !e.is_expired(now))
+            .map(|(k, e)| (k, &e.value))
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.used_memory -= entry.size;
+        self.total.fetch_sub(entry.size, Ordering::Relaxed);
+        self.keys.remove(key);
+        self.volatile.remove(key);
+        for index in self.indexes.values_mut() {
+            index.update(key, None);
+        }
+        Some(entry)
+    }
+
+    ///Вытесняет ключи, пока запись значений `incoming` (ключ и размер) не уложится в `maxmemory`
+    ///вместе с памятью остальных частей. Старые значения этих ключей при подсчете не учитываются,
+    ///они будут перезаписаны
+    fn free(&mut self, incoming: &[(&[u8], usize)]) -> Result<(), CashError> {
+        if self.maxmemory == 0 {
+            return Ok(());
+        }
+
+        let size: usize = incoming.iter().map(|(_, size)| size).sum();
+        loop {
+            let replaced: usize = incoming.iter().filter_map(|(key, _)| self.entries.get(*key)).map(|e| e.size).sum();
+            if self.total.load(Ordering::Relaxed).saturating_sub(replaced) + size <= self.maxmemory {
+                return Ok(());
+            }
+
+            let pool = if self.policy.is_volatile() { &self.volatile } else { &self.keys };
+            if self.policy == Policy::NoEviction || pool.is_empty() {
+                return Err(Error::Oom);
+            }
+
+            let entries = &self.entries;
+            let victim = eviction::pick(pool, self.samples, self.policy, |k| entries.get(k).map(|e| &e.usage))
+                .cloned()
+                .ok_or(Error::Oom)?;
+
+            log::debug!("evicted '{}' by {:?}", victim.escape_ascii(), self.policy);
+            self.remove(&victim);
+        }
+    }
+}
+
+impl StorageEngine for Store {
+    ///Часть в памяти: каталог и номер части не нужны
+    fn open(options: &EngineOptions, _shard: usize) -> Result<Self, CashError> {
+        let mut store = Store::new(options.maxmemory, options.policy, options.samples);
+        store.dirty = options.dirty.clone();
+        store.total = options.used_memory.clone();
+        Ok(store)
+    }
+
+    ///Обращение учитывается для LRU и LFU
+    fn get_value(&mut self, key: &[u8]) -> Result<Option<Value>, CashError> {
+        let now = Instant::now();
+
+        match self.entries.get_mut(key) {
+            Some(entry) if entry.is_expired(now) => {
+                self.remove(key);
+                Ok(None)
+            }
+            Some(entry) => {
+                entry.usage.touch(now);
+                Ok(Some(entry.value.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    ///Перед записью освобождает память под новое значение
+    fn set_value(&mut self, key: Bytes, value: Value) -> Result<(), CashError> {
+        let size = entry_size(&key, &value);
+        self.free(&[(&key, size)])?;
+
+        let now = Instant::now();
+        let mut usage = Usage::new(now);
+        if let Some(old) = self.remove(&key) {
+            usage.freq = old.usage.freq;
+            usage.touch(now);
+        }
+
+        for index in self.indexes.values_mut() {
+            index.update(&key, Some(&value));
+        }
+
+        self.used_memory += size;
+        self.total.fetch_add(size, Ordering::Relaxed);
+        self.changed();
+        self.keys.insert(&key);
+        self.entries.insert(key, Entry { value, size, usage });
+
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Option<Value> {
+        let now = Instant::now();
+        let entry = self.remove(key)?;
+        self.changed();
+        (!entry.is_expired(now)).then_some(entry.value)
+    }
+
+    fn expire(&mut self, key: &[u8], at: Option<Instant>) -> bool {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return false;
+        };
+
+        entry.usage.expires_at = at;
+        self.changed();
+        match at {
+            Some(_) => self.volatile.insert(&Bytes::copy_from_slice(key)),
+            None => self.volatile.remove(key),
+        }
+        true
+    }
+
+    fn deadline(&self, key: &[u8]) -> Option<Instant> {
+        self.entries.get(key)?.usage.expires_at
+    }
+
+    fn type_name(&self, key: &[u8]) -> Option<&'static str> {
+        self.entries.get(key).filter(|e| !e.is_expired(Instant::now())).map(|e| e.value.type_name())
+    }
+
+    fn scan(&self, cursor: usize, count: usize) -> (usize, Vec<Bytes>) {
+        let now = Instant::now();
+        let (next, keys) = self.keys.scan(cursor, count);
+        let keys = keys
+            .iter()
+            .filter(|key| self.entries.get(*key).is_some_and(|e| !e.is_expired(now)))
+            .cloned()
+            .collect();
+        (next, keys)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn entries(&mut self) -> Result<Vec<(Bytes, Value, Option<Instant>)>, CashError> {
+        let now = Instant::now();
+        Ok(self
+            .entries
+            .iter()
+            .filter(|(_, e)| !e.is_expired(now))
+            .map(|(key, e)| (key.clone(), e.value.clone(), e.usage.expires_at))
+            .collect())
+    }
+
+    fn used_memory(&self) -> usize {
+        self.used_memory
+    }
+
+    fn reserve(&mut self, incoming: &[(&[u8], usize)]) -> Result<(), CashError> {
+        self.free(incoming)
+    }
+
+    ///Если памяти уже больше лимита, лишние ключи вытесняются сразу
+    fn configure(&mut self, maxmemory: usize, policy: Policy, samples: usize) {
+        self.maxmemory = maxmemory;
+        self.policy = policy;
+        self.samples = samples;
+
+        if let Err(err) = self.free(&[]) {
+            log::warn!("{}", err);
+        }
+    }
+
+    fn dirty(&self) -> u64 {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    fn saved(&self, dirty: u64) {
+        let _ = self.dirty.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |d| Some(d.saturating_sub(dirty)));
+    }
+
+    fn create_index(&mut self, def: IndexDef) -> Result<(), CashError> {
+        if self.indexes.contains_key(&def.name) {
+            return Err(Error::Storage("Index already exists".to_string()));
+        }
+
+        let mut index = Index::new(def);
+        for (key, value) in self.iter() {
+            index.update(key, Some(value));
+        }
+        self.indexes.insert(index.name().to_string(), index);
+        Ok(())
+    }
+
+    fn drop_index(&mut self, name: &str) -> bool {
+        self.indexes.remove(name).is_some()
+    }
+
+    fn index_defs(&self) -> Vec<IndexDef> {
+        self.indexes.values().map(|index| index.def().clone()).collect()
+    }
+
+    fn search(&self, name: &str, query: &Query) -> Result<Vec<Bytes>, CashError> {
+        let index = self.indexes.get(name).ok_or_else(|| Error::Storage(format!("{}: no such index", name)))?;
+        let now = Instant::now();
+
+        Ok(index
+            .search(query)?
+            .into_iter()
+            .filter(|key| self.entries.get(*key).is_some_and(|e| !e.is_expired(now)))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod store_tests {
+    use std::time::Duration;
+    use super::*;
+
+    fn value(len: usize) -> Bytes {
+        Bytes::from(vec![b'x'; len])
+    }
+
+    #[test]
+    fn noeviction_returns_oom() {
+        let mut store = Store::new(200, Policy::NoEviction, 5);
+
+        assert_eq!(Ok(()), store.set("a".into(), value(100)));
+        assert_eq!(Err(Error::Oom), store.set("b".into(), value(100)));
+        assert_eq!(Ok(()), store.set("a".into(), value(120)));
+        assert_eq!(1, store.len());
+    }
+
+    #[test]
+    fn allkeys_lru_evicts_to_fit() {
+        let mut store = Store::new(1_000, Policy::AllKeysLru, 5);
+
+        for i in 0..20 {
+            store.set(Bytes::from(format!("key:{}", i)), value(100)).unwrap();
+        }
+
+        assert!(store.used_memory() <= 1_000);
+        assert!(store.len() < 20);
+        assert!(store.exists(b"key:19"));
+    }
+
+    #[test]
+    fn volatile_policy_keeps_persistent_keys() {
+        let mut store = Store::new(400, Policy::VolatileTtl, 5);
+        store.set("persistent".into(), value(100)).unwrap();
+        store.set("session".into(), value(100)).unwrap();
+        store.expire(b"session", Some(Instant::now() + Duration::from_secs(60)));
+
+        store.set("next".into(), value(100)).unwrap();
+        assert!(!store.exists(b"session"));
+        assert!(store.exists(b"persistent"));
+        assert_eq!(Err(Error::Oom), store.set("last".into(), value(100)));
+    }
+
+    #[test]
+    fn binary_keys() {
+        let mut store = Store::default();
+        let key = Bytes::from_static(&[0xff, 0x00, 0xfe]);
+        store.set(key.clone(), value(1)).unwrap();
+
+        assert_eq!(Ok(Some(value(1))), store.get(&key));
+        assert_eq!(Ok(None), store.get(&[0xff]));
+    }
+
+    #[test]
+    fn strings_only_from_get() {
+        let mut store = Store::default();
+        store.set_value(Bytes::from("doc"), Value::Json(serde_json::json!({"a": 1}))).unwrap();
+
+        assert_eq!(Err(wrong_type()), store.get(b"doc"));
+        assert_eq!(Some(Bytes::from(r#"{"a":1}"#)), store.get_value(b"doc").unwrap().map(|v| v.to_bytes()));
+    }
+
+    #[test]
+    fn expired_keys_are_hidden() {
+        let mut store = Store::default();
+        store.set("a".into(), value(1)).unwrap();
+        store.expire(b"a", Some(Instant::now()));
+
+        assert_eq!(Ok(None), store.get(b"a"));
+        assert_eq!(0, store.used_memory());
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, HashSet};
+use bytes::Bytes;
+use serde_json::Value as Json;
+use crate::core::command::search::{Bound, Clause, FieldKind, IndexDef, Query};
+use crate::core::error::{CashError, Error};
+use crate::storage::json::select;
+use crate::storage::Value;
+
+///Значения поля документа, приведенные к виду для поиска
+#[derive(Debug, Clone, PartialEq)]
+enum Indexed {
+    Tags(HashSet<String>),
+    Terms(HashSet<String>),
+    Numbers(Vec<f64>),
+}
+
+///Вторичный индекс над JSON-документами с префиксом ключа.
+///Хранит извлеченные значения полей, поэтому запрос не разбирает сами документы
+#[derive(Debug)]
+pub struct Index {
+    def: IndexDef,
+    docs: BTreeMap<Bytes, Vec<Indexed>>,
+}
+
+fn scalar(value: &Json) -> Option<String> {
+    match value {
+        Json::String(s) => Some(s.clone()),
+        Json::Number(n) => Some(n.to_string()),
+        Json::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+///Слова текста: нижний регистр, разделители - всё, кроме букв и цифр
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_lowercase)
+}
+
+impl Index {
+    pub fn new(def: IndexDef) -> Self {
+        Self { def, docs: BTreeMap::new() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.def.name
+    }
+
+    pub fn def(&self) -> &IndexDef {
+        &self.def
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    fn covers(&self, key: &[u8]) -> bool {
+        self.def.prefixes.is_empty() || self.def.prefixes.iter().any(|p| key.starts_with(p))
+    }
+
+    ///Обновляет документ после записи. `None` или не JSON - документ убирается из индекса
+    pub fn update(&mut self, key: &[u8], value: Option<&Value>) {
+        if !self.covers(key) {
+            return;
+        }
+
+        match value {
+            Some(Value::Json(json)) => {
+                let fields = self.def.fields.iter().map(|field| {
+                    let values = select(json, &field.path).into_iter().flat_map(|v| match v {
+                        Json::Array(items) => items.iter().collect(),
+                        _ => vec![v],
+                    });
+
+                    match field.kind {
+                        FieldKind::Tag(separator) => Indexed::Tags(
+                            values
+                                .filter_map(scalar)
+                                .flat_map(|s| s.split(separator).map(|t| t.trim().to_lowercase()).collect::<Vec<_>>())
+                                .filter(|t| !t.is_empty())
+                                .collect(),
+                        ),
+                        FieldKind::Text => Indexed::Terms(
+                            values.filter_map(scalar).flat_map(|s| terms(&s).collect::<Vec<_>>()).collect(),
+                        ),
+                        FieldKind::Numeric => Indexed::Numbers(values.filter_map(Json::as_f64).collect()),
+                    }
+                });
+                self.docs.insert(Bytes::copy_from_slice(key), fields.collect());
+            }
+            _ => {
+                self.docs.remove(key);
+            }
+        }
+    }
+
+    ///Ключи документов, подходящих под запрос, в порядке ключей
+    pub fn search(&self, query: &Query) -> Result<Vec<&Bytes>, CashError> {
+        let mut clauses = vec![];
+        for (negated, clause) in query.clauses() {
+            clauses.push((*negated, self.check(clause)?));
+        }
+
+        Ok(self
+            .docs
+            .iter()
+            .filter(|(_, doc)| clauses.iter().all(|(negated, matches)| matches(doc) != *negated))
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    fn field(&self, name: &str) -> Result<usize, CashError> {
+        self.def
+            .fields
+            .iter()
+            .position(|f| f.name == name)
+            .ok_or_else(|| Error::Storage(format!("unknown field '{}'", name)))
+    }
+
+    ///Проверяет условие по схеме и возвращает функцию проверки документа
+    #[allow(clippy::type_complexity)]
+    fn check<'a>(&self, clause: &'a Clause) -> Result<Box<dyn Fn(&[Indexed]) -> bool + 'a>, CashError> {
+        let wrong = |name: &str, kind: &str| Error::Storage(format!("field '{}' is not a {} field", name, kind));
+
+        match clause {
+            Clause::All => Ok(Box::new(|_| true)),
+            Clause::Tag(name, tags) => {
+                let i = self.field(name)?;
+                if !matches!(self.def.fields[i].kind, FieldKind::Tag(_)) {
+                    return Err(wrong(name, "TAG"));
+                }
+                Ok(Box::new(move |doc| matches!(&doc[i], Indexed::Tags(t) if tags.iter().any(|tag| t.contains(tag)))))
+            }
+            Clause::Numeric(name, min, max) => {
+                let i = self.field(name)?;
+                if self.def.fields[i].kind != FieldKind::Numeric {
+                    return Err(wrong(name, "NUMERIC"));
+                }
+                let above = |v: f64, b: &Bound| if b.inclusive { v >= b.value } else { v > b.value };
+                let below = |v: f64, b: &Bound| if b.inclusive { v <= b.value } else { v < b.value };
+                Ok(Box::new(move |doc| {
+                    matches!(&doc[i], Indexed::Numbers(n) if n.iter().any(|&v| above(v, min) && below(v, max)))
+                }))
+            }
+            Clause::Text(name, words) => {
+                let fields: Vec<usize> = match name {
+                    Some(name) => {
+                        let i = self.field(name)?;
+                        if self.def.fields[i].kind != FieldKind::Text {
+                            return Err(wrong(name, "TEXT"));
+                        }
+                        vec![i]
+                    }
+                    None => (0..self.def.fields.len()).filter(|&i| self.def.fields[i].kind == FieldKind::Text).collect(),
+                };
+
+                let matches = |terms: &HashSet<String>, word: &String| match word.strip_suffix('*') {
+                    Some(prefix) => terms.iter().any(|t| t.starts_with(prefix)),
+                    None => terms.contains(word),
+                };
+                Ok(Box::new(move |doc| {
+                    words.iter().all(|word| {
+                        fields.iter().any(|&i| matches!(&doc[i], Indexed::Terms(t) if matches(t, word)))
+                    })
+                }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use serde_json::json;
+    use crate::core::command::Command;
+    use crate::core::command::search::SearchCmd;
+    use super::*;
+
+    fn index() -> Index {
+        let create = "ft.create idx on json prefix 1 todo: schema $.status tag $.name text $.estimate numeric $.tags tag";
+        let Ok(Command::Search(SearchCmd::Create(def))) = Command::from_cmd(create.to_string()) else { panic!() };
+
+        let mut index = Index::new(def);
+        let docs = [
+            ("todo:1", json!({"name": "Write docs", "status": "new", "estimate": 3, "tags": ["docs"]})),
+            ("todo:2", json!({"name": "Fix the docs build", "status": "done", "estimate": 1, "tags": ["ci", "docs"]})),
+            ("todo:3", json!({"name": "Release", "status": "new", "estimate": 8})),
+            ("other:1", json!({"name": "Write docs", "status": "new"})),
+        ];
+        for (key, doc) in docs {
+            index.update(key.as_bytes(), Some(&Value::Json(doc)));
+        }
+        index
+    }
+
+    fn search(index: &Index, query: &str) -> Vec<&'static str> {
+        let query = Query::parse(query).unwrap();
+        let keys = index.search(&query).unwrap();
+        ["todo:1", "todo:2", "todo:3"].into_iter().filter(|k| keys.iter().any(|key| key == k)).collect()
+    }
+
+    #[test]
+    fn queries() {
+        let index = index();
+
+        assert_eq!(3, index.len());
+        assert_eq!(vec!["todo:1", "todo:3"], search(&index, "@status:{new}"));
+        assert_eq!(vec!["todo:1", "todo:2"], search(&index, "@tags:{docs}"));
+        assert_eq!(vec!["todo:1", "todo:3"], search(&index, "@estimate:[(1 +inf]"));
+        assert_eq!(vec!["todo:2"], search(&index, "doc* -@status:{new}"));
+        assert_eq!(vec!["todo:1", "todo:2", "todo:3"], search(&index, "*"));
+        assert!(index.search(&Query::parse("@name:{docs}").unwrap()).is_err());
+    }
+
+    #[test]
+    fn removed_documents_leave_the_index() {
+        let mut index = index();
+
+        index.update(b"todo:1", None);
+        index.update(b"todo:2", Some(&Value::String(Bytes::from("plain"))));
+        assert_eq!(vec!["todo:3"], search(&index, "*"));
+    }
+}
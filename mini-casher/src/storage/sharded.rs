@@ -0,0 +1,415 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+use bytes::Bytes;
+use crate::core::error::CashError;
+use crate::storage::engine::{EngineOptions, StorageEngine};
+use crate::storage::eviction::Policy;
+use crate::storage::snapshot::Snapshot;
+use crate::storage::{entry_size, Store, Value};
+
+///Число частей хранилища по умолчанию
+pub const DEFAULT_SHARDS: usize = 16;
+
+///Хранилище, разделенное на части со своими блокировками.
+///Часть выбирается по хэшу ключа, поэтому команды с разными ключами не ждут друг друга.
+///Команды с ключами из нескольких частей блокируют их по возрастанию номера, так что взаимных
+///блокировок нет; индексы поиска и снимки используют все части сразу.
+///`maxmemory` - лимит памяти всех частей вместе, вытесняются ключи части, в которую идет запись
+#[derive(Debug)]
+pub struct Sharded<E: StorageEngine = Store> {
+    shards: Vec<Mutex<E>>,
+}
+
+impl Default for Sharded<Store> {
+    fn default() -> Self {
+        Sharded::new(DEFAULT_SHARDS, 0, Policy::NoEviction, 5)
+    }
+}
+
+impl Sharded<Store> {
+    ///Хранилище в памяти
+    pub fn new(count: usize, maxmemory: usize, policy: Policy, samples: usize) -> Self {
+        let options = EngineOptions { maxmemory, policy, samples, shards: count, ..Default::default() };
+        Sharded::open(&options).expect("memory storage opens without errors")
+    }
+}
+
+impl<E: StorageEngine> Sharded<E> {
+    ///Открывает `options.shards` частей с общим счетчиком памяти
+    pub fn open(options: &EngineOptions) -> Result<Self, CashError> {
+        let count = options.shards.max(1);
+        let options = EngineOptions { shards: count, ..options.clone() };
+
+        let shards = (0..count).map(|shard| Ok(Mutex::new(E::open(&options, shard)?))).collect::<Result<_, CashError>>()?;
+        Ok(Self { shards })
+    }
+
+    ///Часть ключа выбирается по FNV-1a: `DefaultHasher` может поменяться с версией Rust,
+    ///а файлы `LogStore` разложены по частям и должны открываться после обновления
+    fn index(&self, key: &[u8]) -> usize {
+        (fnv1a(key) % self.shards.len() as u64) as usize
+    }
+
+    ///Часть с ключом `key`
+    pub fn shard(&self, key: &[u8]) -> Result<MutexGuard<'_, E>, CashError> {
+        Ok(self.shards[self.index(key)].lock()?)
+    }
+
+    ///Все части по порядку, например для снимка или индексов
+    pub fn lock_all(&self) -> Result<Vec<MutexGuard<'_, E>>, CashError> {
+        self.shards.iter().map(|shard| Ok(shard.lock()?)).collect()
+    }
+
+    ///Снимает отметку об ошибке с частей после паники команды, которая держала их блокировку.
+    ///Иначе каждая следующая команда на этих частях заканчивалась бы ошибкой
+    pub fn clear_poison(&self) {
+        for shard in &self.shards {
+            shard.clear_poison();
+        }
+    }
+
+    ///Обходит части по одной: пока `f` работает с частью, остальные доступны другим командам
+    pub fn for_each(&self, mut f: impl FnMut(&mut E) -> Result<(), CashError>) -> Result<(), CashError> {
+        for shard in &self.shards {
+            f(&mut *shard.lock()?)?;
+        }
+        Ok(())
+    }
+
+    ///Выполняет `f` над хранилищем, в котором есть все ключи `keys`.
+    ///Если ключи в одной части, `f` работает прямо с ней. Иначе части блокируются по возрастанию,
+    ///ключи копируются во временное хранилище, и после `f` измененные ключи записываются обратно.
+    ///Перед записью место резервируется во всех частях: при `OOM` не меняется ни одна.
+    ///Лимит памяти общий, поэтому каждая часть учитывает и прирост памяти в других частях.
+    ///Как и в одной части, изменения, сделанные `f` до ошибки, остаются
+    pub fn with_keys<T>(
+        &self,
+        keys: &[&Bytes],
+        f: impl FnOnce(&mut dyn StorageEngine) -> Result<T, CashError>,
+    ) -> Result<T, CashError> {
+        let mut indexes: Vec<usize> = keys.iter().map(|key| self.index(key)).collect();
+        indexes.sort_unstable();
+        indexes.dedup();
+
+        match indexes[..] {
+            [] => f(&mut *self.shards[0].lock()?),
+            [index] => f(&mut *self.shards[index].lock()?),
+            _ => {
+                let mut guards = vec![];
+                for index in &indexes {
+                    guards.push((*index, self.shards[*index].lock()?));
+                }
+
+                let mut unique: Vec<&Bytes> = vec![];
+                for key in keys {
+                    if !unique.contains(key) {
+                        unique.push(key);
+                    }
+                }
+
+                let mut scratch = Store::default();
+                let mut before = vec![];
+                for key in unique {
+                    let entry = peek(locked(&mut guards, self.index(key)), key)?;
+                    if let Some((value, deadline)) = &entry {
+                        scratch.set_value(key.clone(), value.clone())?;
+                        scratch.expire(key, *deadline);
+                    }
+                    before.push((key.clone(), entry));
+                }
+
+                let result = f(&mut scratch);
+
+                let mut changes = vec![];
+                for (key, entry) in before {
+                    let after = peek(&mut scratch, &key)?;
+                    if after != entry {
+                        let old = entry.map_or(0, |(value, _)| entry_size(&key, &value));
+                        changes.push((key, after, old));
+                    }
+                }
+
+                for index in &indexes {
+                    //ключи этой части с полным размером, она сама вычтет старые значения,
+                    //ключи других частей - только с приростом
+                    let incoming: Vec<(&[u8], usize)> = changes
+                        .iter()
+                        .filter_map(|(key, after, old)| {
+                            let size = entry_size(key, &after.as_ref()?.0);
+                            match self.index(key) == *index {
+                                true => Some((&key[..], size)),
+                                false => Some((&key[..], size.saturating_sub(*old))),
+                            }
+                        })
+                        .collect();
+                    locked(&mut guards, *index).reserve(&incoming)?;
+                }
+
+                //удаления первыми, чтобы записи заняли уже освобожденное место
+                let mut failed = None;
+                let (deleted, written): (Vec<_>, Vec<_>) = changes.into_iter().partition(|(_, after, _)| after.is_none());
+                for (key, after, _) in deleted.into_iter().chain(written) {
+                    let store = locked(&mut guards, self.index(&key));
+                    match after {
+                        Some((value, deadline)) => match store.set_value(key.clone(), value) {
+                            Ok(()) => {
+                                store.expire(&key, deadline);
+                            }
+                            Err(err) => {
+                                failed.get_or_insert(err);
+                            }
+                        },
+                        None => {
+                            store.delete(&key);
+                        }
+                    }
+                }
+
+                match (result, failed) {
+                    (Ok(_), Some(err)) => Err(err),
+                    (result, _) => result,
+                }
+            }
+        }
+    }
+
+    ///Новые ограничения памяти. Если памяти уже больше лимита, части вытесняют ключи по очереди
+    pub fn configure(&self, maxmemory: usize, policy: Policy, samples: usize) -> Result<(), CashError> {
+        for shard in &self.shards {
+            shard.lock()?.configure(maxmemory, policy, samples);
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> Result<usize, CashError> {
+        self.shards.iter().map(|shard| Ok(shard.lock()?.len())).sum()
+    }
+
+    pub fn is_empty(&self) -> Result<bool, CashError> {
+        Ok(self.len()? == 0)
+    }
+
+    pub fn used_memory(&self) -> Result<usize, CashError> {
+        self.shards.iter().map(|shard| Ok(shard.lock()?.used_memory())).sum()
+    }
+
+    ///Число изменений с последнего снимка во всех частях
+    pub fn dirty(&self) -> Result<u64, CashError> {
+        Ok(self.shards[0].lock()?.dirty())
+    }
+
+    pub fn saved(&self, dirty: u64) -> Result<(), CashError> {
+        self.shards[0].lock()?.saved(dirty);
+        Ok(())
+    }
+
+    ///Шаг `SCAN` по всем частям. Курсор хранит номер части в младших разрядах
+    ///и курсор внутри части в старших; `0` - начало и конец обхода
+    pub fn scan(&self, cursor: usize, count: usize) -> Result<(usize, Vec<Bytes>), CashError> {
+        let shards = self.shards.len();
+        let (mut index, mut inner) = (cursor % shards, cursor / shards);
+        let mut keys = vec![];
+
+        while keys.len() < count {
+            let (next, batch) = self.shards[index].lock()?.scan(inner, count - keys.len());
+            keys.extend(batch);
+
+            if next != 0 {
+                return Ok((next * shards + index, keys));
+            }
+            index += 1;
+            inner = 0;
+            if index == shards {
+                return Ok((0, keys));
+            }
+        }
+        Ok((index + inner * shards, keys))
+    }
+
+    ///Снимок всех частей на один момент: части блокируются на время копирования
+    pub fn snapshot(&self) -> Result<Snapshot, CashError> {
+        Snapshot::capture_all(&mut self.lock_all()?)
+    }
+}
+
+fn locked<'a, E: StorageEngine>(guards: &'a mut [(usize, MutexGuard<'_, E>)], index: usize) -> &'a mut E {
+    let (_, guard) = guards.iter_mut().find(|(i, _)| *i == index).expect("the shard of the key is locked");
+    guard
+}
+
+///64-битный FNV-1a, хэш ключей для выбора части. Его нельзя менять без новой версии `LogStore`
+fn fnv1a(key: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    key.iter().fold(OFFSET, |hash, byte| (hash ^ *byte as u64).wrapping_mul(PRIME))
+}
+
+///Значение и срок ключа
+fn peek<E: StorageEngine + ?Sized>(store: &mut E, key: &[u8]) -> Result<Option<(Value, Option<Instant>)>, CashError> {
+    let Some(value) = store.get_value(key)? else { return Ok(None) };
+    Ok(Some((value, store.deadline(key))))
+}
+
+#[cfg(test)]
+mod sharded_tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crate::core::error::Error;
+    use super::*;
+
+    fn keys(count: usize) -> Vec<Bytes> {
+        (0..count).map(|i| Bytes::from(format!("todo:{}", i))).collect()
+    }
+
+    #[test]
+    fn keys_are_spread_over_shards() {
+        let storage = Sharded::new(4, 0, Policy::NoEviction, 5);
+        for key in keys(100) {
+            storage.shard(&key).unwrap().set(key.clone(), Bytes::from("x")).unwrap();
+        }
+
+        assert_eq!(Ok(100), storage.len());
+        assert_eq!(Ok(100), storage.dirty());
+        assert!(storage.lock_all().unwrap().iter().all(|store| !store.is_empty()));
+
+        storage.saved(60).unwrap();
+        assert_eq!(Ok(40), storage.dirty());
+    }
+
+    #[test]
+    fn multi_shard_commands_write_back_changes() {
+        let storage = Sharded::new(4, 0, Policy::NoEviction, 5);
+        let keys = keys(8);
+        for key in &keys {
+            storage.shard(key).unwrap().set(key.clone(), Bytes::from("1")).unwrap();
+        }
+        storage.shard(&keys[1]).unwrap().expire(&keys[1], Some(Instant::now() + Duration::from_secs(60)));
+
+        let refs: Vec<&Bytes> = keys.iter().collect();
+        storage
+            .with_keys(&refs, |store| {
+                store.set(keys[0].clone(), Bytes::from("2"))?;
+                store.delete(&keys[2]);
+                assert!(store.deadline(&keys[1]).is_some());
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(Ok(Some(Bytes::from("2"))), storage.shard(&keys[0]).unwrap().get(&keys[0]));
+        assert!(!storage.shard(&keys[2]).unwrap().exists(&keys[2]));
+        assert!(storage.shard(&keys[1]).unwrap().deadline(&keys[1]).is_some());
+        assert_eq!(Ok(7), storage.len());
+    }
+
+    #[test]
+    fn shard_hash_is_fixed() {
+        assert_eq!(0xcbf29ce484222325, fnv1a(b""));
+        assert_eq!(0xaf63dc4c8601ec8c, fnv1a(b"a"));
+        assert_eq!(0x85944171f73967e8, fnv1a(b"foobar"));
+    }
+
+    #[test]
+    fn multi_shard_failure_is_not_half_applied() {
+        let storage = Sharded::new(2, 1000, Policy::NoEviction, 5);
+        let keys = keys(8);
+        let a = keys.iter().find(|key| storage.index(key) == 0).unwrap();
+        let b = keys.iter().find(|key| storage.index(key) == 1).unwrap();
+        for key in [a, b] {
+            storage.shard(key).unwrap().set(key.clone(), Bytes::from("1")).unwrap();
+        }
+
+        //место в части `b` кончилось: не меняется и часть `a`
+        let result = storage.with_keys(&[a, b], |store| {
+            store.set(a.clone(), Bytes::from("2"))?;
+            store.set(b.clone(), Bytes::from(vec![b'x'; 1000]))
+        });
+        assert_eq!(Err(Error::Oom), result);
+        assert_eq!(Ok(Some(Bytes::from("1"))), storage.shard(a).unwrap().get(a));
+        assert_eq!(Ok(Some(Bytes::from("1"))), storage.shard(b).unwrap().get(b));
+
+        //каждое значение помещается в лимит, а оба вместе - нет
+        let result = storage.with_keys(&[a, b], |store| {
+            store.set(a.clone(), Bytes::from(vec![b'x'; 450]))?;
+            store.set(b.clone(), Bytes::from(vec![b'x'; 450]))
+        });
+        assert_eq!(Err(Error::Oom), result);
+        assert_eq!(Ok(Some(Bytes::from("1"))), storage.shard(a).unwrap().get(a));
+
+        //ошибка самой команды: сделанное до неё остается, как в одной части
+        let result = storage.with_keys(&[a, b], |store| {
+            store.set(a.clone(), Bytes::from("2"))?;
+            Err::<(), _>(Error::Storage("failed".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(Ok(Some(Bytes::from("2"))), storage.shard(a).unwrap().get(a));
+    }
+
+    #[test]
+    fn maxmemory_is_shared_by_shards() {
+        let storage = Sharded::new(4, 1000, Policy::NoEviction, 5);
+        let keys = keys(32);
+        let mut first = keys.iter().filter(|key| storage.index(key) == 0);
+        let (a, c) = (first.next().unwrap(), first.next().unwrap());
+        let b = keys.iter().find(|key| storage.index(key) == 1).unwrap();
+
+        //значение больше четверти лимита помещается, пока остальные части пусты
+        storage.shard(a).unwrap().set(a.clone(), Bytes::from(vec![b'x'; 600])).unwrap();
+        assert_eq!(Err(Error::Oom), storage.shard(b).unwrap().set(b.clone(), Bytes::from(vec![b'x'; 600])));
+        assert!(storage.used_memory().unwrap() <= 1000);
+
+        //вытеснение освобождает место в части записи, пока не уложится общий лимит
+        storage.configure(1000, Policy::AllKeysLru, 5).unwrap();
+        storage.shard(c).unwrap().set(c.clone(), Bytes::from(vec![b'y'; 700])).unwrap();
+        assert!(!storage.shard(a).unwrap().exists(a));
+        assert!(storage.used_memory().unwrap() <= 1000);
+
+        storage.configure(500, Policy::AllKeysLru, 5).unwrap();
+        assert_eq!(Ok(0), storage.len());
+        assert_eq!(Ok(0), storage.used_memory());
+    }
+
+    #[test]
+    fn scan_visits_every_shard() {
+        let storage = Sharded::new(3, 0, Policy::NoEviction, 5);
+        for key in keys(50) {
+            storage.shard(&key).unwrap().set(key.clone(), Bytes::from("x")).unwrap();
+        }
+
+        let mut seen = vec![];
+        let mut cursor = 0;
+        loop {
+            let (next, batch) = storage.scan(cursor, 7).unwrap();
+            assert!(batch.len() <= 7);
+            seen.extend(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        seen.sort();
+        seen.dedup();
+        assert_eq!(50, seen.len());
+    }
+
+    #[test]
+    fn concurrent_writers() {
+        let storage = Arc::new(Sharded::default());
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    for i in 0..250 {
+                        let key = Bytes::from(format!("todo:{}:{}", t, i));
+                        storage.shard(&key).unwrap().set(key.clone(), Bytes::from("x")).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(Ok(1000), storage.len());
+    }
+}
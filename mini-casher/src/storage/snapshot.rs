@@ -0,0 +1,410 @@
+use std::ops::DerefMut;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use crate::core::command::search::{IndexDef, SearchCmd};
+use crate::core::command::Command;
+use crate::core::error::{CashError, Error};
+use crate::core::frames::Frame;
+use crate::storage::filter::{Bloom, Cuckoo};
+use crate::storage::engine::StorageEngine;
+use crate::storage::sharded::Sharded;
+use crate::storage::Value;
+
+///Заголовок файла снимка
+pub const MAGIC: &[u8] = b"CASHDB";
+
+///Версия формата. Файл более новой версии не загружается
+pub const VERSION: u16 = 1;
+
+///Длина контрольной суммы SHA-256 в конце файла
+const CHECKSUM_LEN: usize = 32;
+
+const KIND_STRING: u8 = 0;
+const KIND_JSON: u8 = 1;
+const KIND_BLOOM: u8 = 2;
+const KIND_CUCKOO: u8 = 3;
+const KIND_INDEX: u8 = 0xFD;
+const END: u8 = 0xFF;
+
+///Без срока жизни
+const NO_EXPIRY: u64 = u64::MAX;
+
+///Копия данных хранилища на момент снимка.
+///Сроки жизни хранятся как unix-время в миллисекундах, потому что `Instant` не переживает перезапуск
+#[derive(Debug, Default, PartialEq)]
+pub struct Snapshot {
+    pub indexes: Vec<IndexDef>,
+    pub entries: Vec<(Bytes, Value, Option<u64>)>,
+    ///Счетчик изменений хранилища в момент снимка
+    pub dirty: u64,
+}
+
+pub(crate) fn unix_ms(at: Instant, now: Instant) -> u64 {
+    let unix_now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    (unix_now + at.saturating_duration_since(now)).as_millis() as u64
+}
+
+pub(crate) fn instant(unix_ms: u64) -> Option<Instant> {
+    let unix_now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let left = Duration::from_millis(unix_ms).checked_sub(unix_now)?;
+    Some(Instant::now() + left)
+}
+
+pub(crate) fn kind(value: &Value) -> u8 {
+    match value {
+        Value::String(_) => KIND_STRING,
+        Value::Json(_) => KIND_JSON,
+        Value::Bloom(_) => KIND_BLOOM,
+        Value::Cuckoo(_) => KIND_CUCKOO,
+    }
+}
+
+pub(crate) fn decode_value(kind: u8, raw: &[u8]) -> Option<Value> {
+    match kind {
+        KIND_STRING => Some(Value::String(Bytes::copy_from_slice(raw))),
+        KIND_JSON => serde_json::from_slice(raw).ok().map(Value::Json),
+        KIND_BLOOM => Bloom::decode(raw).map(Value::Bloom),
+        KIND_CUCKOO => Cuckoo::decode(raw).map(Value::Cuckoo),
+        _ => None,
+    }
+}
+
+///Имя типа, как у `Value::type_name`, по коду типа
+pub(crate) fn kind_name(kind: u8) -> Option<&'static str> {
+    match kind {
+        KIND_STRING => Some("string"),
+        KIND_JSON => Some("ReJSON-RL"),
+        KIND_BLOOM => Some("MBbloom--"),
+        KIND_CUCKOO => Some("MBbloomCF"),
+        _ => None,
+    }
+}
+
+///Определение индекса как аргументы `FT.CREATE` с длинами
+pub(crate) fn encode_index(buf: &mut Vec<u8>, def: &IndexDef) {
+    let Frame::Array(args) = SearchCmd::Create(def.clone()).frame() else { return };
+    buf.extend_from_slice(&(args.len() as u32).to_le_bytes());
+    for arg in args {
+        if let Frame::BulkString(arg) = arg {
+            put(buf, &arg);
+        }
+    }
+}
+
+pub(crate) fn decode_index(bytes: &[u8]) -> Option<IndexDef> {
+    let mut reader = Reader { bytes, pos: 0 };
+    read_index(&mut reader, 0).ok()
+}
+
+///`record` - начало записи для сообщения о повреждении
+fn read_index(reader: &mut Reader, record: usize) -> Result<IndexDef, CashError> {
+    let count = reader.u32()?;
+    let mut args = vec![];
+    for _ in 0..count {
+        args.push(Frame::BulkString(Bytes::copy_from_slice(reader.chunk()?)));
+    }
+    match Command::from_frame(Frame::Array(args)) {
+        Ok(Command::Search(SearchCmd::Create(def))) => Ok(def),
+        _ => Err(corrupted(record)),
+    }
+}
+
+fn put(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+///Чтение записей файла с проверкой границ
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CashError> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len()).ok_or_else(|| corrupted(self.pos))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, CashError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, CashError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+    }
+
+    fn u32(&mut self) -> Result<u32, CashError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap_or_default()))
+    }
+
+    fn chunk(&mut self) -> Result<&'a [u8], CashError> {
+        let len = self.u32()?;
+        self.take(len as usize)
+    }
+}
+
+///Ошибка с местом повреждения: `casher-check` показывает его пользователю
+fn corrupted(at: usize) -> Error {
+    Error::Storage(format!("snapshot is corrupted at byte {}", at))
+}
+
+impl Snapshot {
+    ///Копирует данные под блокировкой хранилища. Запись на диск идет уже без неё
+    pub fn capture(store: &mut dyn StorageEngine) -> Result<Snapshot, CashError> {
+        Snapshot::capture_all(&mut [store])
+    }
+
+    ///Снимок всех частей `Sharded` под их блокировками.
+    ///Индексы у частей одинаковые, их определения берутся из первой
+    pub fn capture_all<S, E>(stores: &mut [S]) -> Result<Snapshot, CashError>
+    where
+        S: DerefMut<Target = E>,
+        E: StorageEngine + ?Sized,
+    {
+        let now = Instant::now();
+        let mut entries = vec![];
+        for store in stores.iter_mut() {
+            let captured = store.entries()?;
+            entries.extend(captured.into_iter().map(|(key, value, expires_at)| (key, value, expires_at.map(|at| unix_ms(at, now)))));
+        }
+
+        let Some(first) = stores.first() else { return Ok(Snapshot::default()) };
+        Ok(Snapshot { indexes: first.index_defs(), entries, dirty: first.dirty() })
+    }
+
+    ///Формат: `CASHDB`, версия (u16 LE), записи, `0xFF` и SHA-256 всего предыдущего.
+    ///Запись ключа: тип, срок (u64 LE, `u64::MAX` - без срока), ключ и значение с длиной (u32 LE).
+    ///Индекс хранится как аргументы `FT.CREATE`
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::from(MAGIC);
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+
+        for def in &self.indexes {
+            buf.push(KIND_INDEX);
+            encode_index(&mut buf, def);
+        }
+
+        for (key, value, expires_at) in &self.entries {
+            buf.push(kind(value));
+            buf.extend_from_slice(&expires_at.unwrap_or(NO_EXPIRY).to_le_bytes());
+            put(&mut buf, key);
+            put(&mut buf, &value.to_bytes());
+        }
+
+        buf.push(END);
+        let checksum = Sha256::digest(&buf);
+        buf.extend_from_slice(&checksum);
+        buf
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Snapshot, CashError> {
+        let (snapshot, len) = Snapshot::decode_prefix(bytes)?;
+        if len != bytes.len() {
+            return Err(corrupted(len));
+        }
+        Ok(snapshot)
+    }
+
+    ///Разбирает снимок в начале `bytes` и возвращает его длину.
+    ///После снимка могут идти другие данные, например команды AOF
+    pub fn decode_prefix(bytes: &[u8]) -> Result<(Snapshot, usize), CashError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(Error::Storage("not a snapshot file".to_string()));
+        }
+
+        let mut reader = Reader { bytes, pos: MAGIC.len() };
+        let version = u16::from_le_bytes(reader.take(2)?.try_into().unwrap_or_default());
+        if version > VERSION {
+            return Err(Error::Storage(format!("unsupported snapshot version {}", version)));
+        }
+
+        let mut snapshot = Snapshot::default();
+        loop {
+            let record = reader.pos;
+            let kind = reader.u8()?;
+            if kind == END {
+                break;
+            }
+
+            if kind == KIND_INDEX {
+                snapshot.indexes.push(read_index(&mut reader, record)?);
+                continue;
+            }
+
+            let expires_at = Some(reader.u64()?).filter(|at| *at != NO_EXPIRY);
+            let key = Bytes::copy_from_slice(reader.chunk()?);
+            let value = decode_value(kind, reader.chunk()?).ok_or_else(|| corrupted(record))?;
+            snapshot.entries.push((key, value, expires_at));
+        }
+
+        let body = reader.pos;
+        if Sha256::digest(&bytes[..body]).as_slice() != reader.take(CHECKSUM_LEN)? {
+            return Err(Error::Storage("snapshot checksum mismatch".to_string()));
+        }
+
+        Ok((snapshot, reader.pos))
+    }
+
+    ///Заполняет хранилище данными снимка. Ключи, срок которых истек, пока сервер не работал,
+    ///пропускаются. Возвращает число загруженных ключей
+    pub fn restore<E: StorageEngine>(self, storage: &Sharded<E>) -> Result<usize, CashError> {
+        for mut store in storage.lock_all()? {
+            for def in &self.indexes {
+                store.create_index(def.clone())?;
+            }
+        }
+
+        let mut loaded = 0;
+        for (key, value, expires_at) in self.entries {
+            let deadline = match expires_at {
+                Some(at) => match instant(at) {
+                    Some(deadline) => Some(deadline),
+                    None => continue,
+                },
+                None => None,
+            };
+
+            let mut store = storage.shard(&key)?;
+            store.set_value(key.clone(), value)?;
+            store.expire(&key, deadline);
+            loaded += 1;
+        }
+
+        storage.saved(storage.dirty()?)?;
+        Ok(loaded)
+    }
+}
+
+///Длина контрольной суммы в `DUMP`: первые байты SHA-256
+const PAYLOAD_CHECKSUM_LEN: usize = 8;
+
+///Значение для `DUMP`: тип, данные, версия формата (u16 LE) и первые 8 байт SHA-256 всего предыдущего.
+///Тип и данные записываются так же, как в снимке
+pub fn dump_value(value: &Value) -> Vec<u8> {
+    let mut buf = vec![kind(value)];
+    buf.extend_from_slice(&value.to_bytes());
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    let checksum = Sha256::digest(&buf);
+    buf.extend_from_slice(&checksum[..PAYLOAD_CHECKSUM_LEN]);
+    buf
+}
+
+///Значение из данных `DUMP`. Данные более новой версии или с неверной суммой не принимаются
+pub fn restore_value(payload: &[u8]) -> Result<Value, CashError> {
+    let wrong = || Error::Storage("DUMP payload version or checksum are wrong".to_string());
+    if payload.len() < 1 + 2 + PAYLOAD_CHECKSUM_LEN {
+        return Err(wrong());
+    }
+
+    let (body, checksum) = payload.split_at(payload.len() - PAYLOAD_CHECKSUM_LEN);
+    if Sha256::digest(body)[..PAYLOAD_CHECKSUM_LEN] != *checksum {
+        return Err(wrong());
+    }
+
+    let (data, version) = body.split_at(body.len() - 2);
+    if u16::from_le_bytes([version[0], version[1]]) > VERSION {
+        return Err(wrong());
+    }
+
+    decode_value(data[0], &data[1..]).ok_or_else(|| Error::Storage("bad data format".to_string()))
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use serde_json::json;
+    use crate::core::command::search::Query;
+    use crate::storage::filter::{BLOOM_CAPACITY, BLOOM_ERROR_RATE, BLOOM_EXPANSION};
+    use crate::storage::Store;
+    use super::*;
+
+    fn store() -> Store {
+        let mut store = Store::default();
+        let Ok(Command::Search(SearchCmd::Create(def))) =
+            Command::from_cmd("ft.create idx on json prefix todo: schema $.status tag".to_string())
+        else {
+            panic!()
+        };
+        store.create_index(def).unwrap();
+
+        let mut bloom = Bloom::new(BLOOM_ERROR_RATE, BLOOM_CAPACITY, BLOOM_EXPANSION).unwrap();
+        bloom.add(b"item").unwrap();
+
+        store.set(Bytes::from("plain"), Bytes::from_static(b"\x00bytes")).unwrap();
+        store.set_value(Bytes::from("todo:1"), Value::Json(json!({"status": "new"}))).unwrap();
+        store.set_value(Bytes::from("seen"), Value::Bloom(bloom)).unwrap();
+        store.set(Bytes::from("session"), Bytes::from("x")).unwrap();
+        store.expire(b"session", Some(Instant::now() + Duration::from_secs(60)));
+        store
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut source = store();
+        let snapshot = Snapshot::capture(&mut source).unwrap();
+        let decoded = Snapshot::decode(&snapshot.encode()).unwrap();
+
+        assert_eq!(4, decoded.entries.len());
+        assert_eq!(snapshot.indexes, decoded.indexes);
+
+        let storage = Sharded::default();
+        assert_eq!(Ok(4), decoded.restore(&storage));
+        assert_eq!(Ok(0), storage.dirty());
+        for key in ["plain", "todo:1", "seen"] {
+            assert_eq!(source.get_value(key.as_bytes()), storage.shard(key.as_bytes()).unwrap().get_value(key.as_bytes()));
+        }
+
+        let left = storage.shard(b"session").unwrap().deadline(b"session").unwrap() - Instant::now();
+        assert!(left > Duration::from_secs(58) && left <= Duration::from_secs(60));
+        let found = storage.shard(b"todo:1").unwrap().search("idx", &Query::parse("@status:{new}").unwrap()).unwrap();
+        assert_eq!(1, found.len());
+    }
+
+    #[test]
+    fn damaged_files_are_rejected() {
+        let bytes = Snapshot::capture(&mut store()).unwrap().encode();
+
+        let mut flipped = bytes.clone();
+        flipped[10] ^= 1;
+        assert!(Snapshot::decode(&flipped).is_err());
+        assert!(Snapshot::decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Snapshot::decode(b"REDIS0009").is_err());
+
+        let mut newer = bytes[..bytes.len() - CHECKSUM_LEN].to_vec();
+        newer[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let checksum = Sha256::digest(&newer);
+        newer.extend_from_slice(&checksum);
+        assert!(Snapshot::decode(&newer).is_err());
+    }
+
+    #[test]
+    fn dump_payload_round_trip() {
+        let mut store = store();
+        for key in ["plain", "todo:1", "seen"] {
+            let value = store.get_value(key.as_bytes()).unwrap().unwrap();
+            assert_eq!(Ok(value.clone()), restore_value(&dump_value(&value)));
+        }
+
+        let mut payload = dump_value(&Value::String(Bytes::from("x")));
+        assert!(restore_value(&payload[1..]).is_err());
+        payload[1] ^= 1;
+        assert!(restore_value(&payload).is_err());
+    }
+
+    #[test]
+    fn expired_keys_are_skipped_on_restore() {
+        let snapshot = Snapshot {
+            entries: vec![(Bytes::from("old"), Value::String(Bytes::from("x")), Some(1))],
+            ..Default::default()
+        };
+
+        let storage = Sharded::default();
+        assert_eq!(Ok(0), snapshot.restore(&storage));
+        assert_eq!(Ok(true), storage.is_empty());
+    }
+}